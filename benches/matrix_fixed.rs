@@ -0,0 +1,77 @@
+//! Appending a quantum to a second of history, `MatrixFixed`'s flat ring
+//! against a `VecDeque` per channel fed a sample at a time, as it used to be.
+//!
+//! Run with `cargo bench --bench matrix_fixed`.
+
+use std::collections::VecDeque;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use wav_viewer::backend::{Matrix, MatrixFixed};
+
+/// Samples kept per channel.
+const LEN: usize = 48000;
+const CHANNELS: usize = 2;
+/// Appends per measurement, several times around the history.
+const APPENDS: usize = 2000;
+
+/// The previous layout of `MatrixFixed`.
+struct Deques(Vec<VecDeque<f32>>);
+
+impl Deques {
+    fn new(len: usize, channel: usize) -> Self {
+        Self(vec![VecDeque::from(vec![0.; len]); channel])
+    }
+    fn append(&mut self, matrix: &Matrix) {
+        for (samples, data) in self.0.iter_mut().zip(matrix.channels()) {
+            for sample in data {
+                samples.pop_front();
+                samples.push_back(*sample);
+            }
+        }
+    }
+    /// The samples of `channel` in one slice, as the views need them.
+    fn contiguous(&mut self, channel: usize) -> &[f32] {
+        self.0[channel].make_contiguous()
+    }
+}
+
+/// Runs `append` `APPENDS` times, returning the time per call.
+fn measure(mut append: impl FnMut()) -> Duration {
+    let started = Instant::now();
+    for _ in 0..APPENDS {
+        append();
+    }
+    started.elapsed() / APPENDS as u32
+}
+
+fn main() {
+    for quantum in [64, 256, 1024, 4096] {
+        let matrix = Matrix::from_channels(
+            (0..CHANNELS)
+                .map(|channel| (0..quantum).map(|i| (i + channel) as f32).collect())
+                .collect(),
+        );
+
+        let mut fixed = MatrixFixed::new(LEN, CHANNELS);
+        let flat = measure(|| {
+            // appending takes the matrix, the capture sends a new one each time
+            fixed.append(matrix.clone()).unwrap();
+            black_box(&fixed[0]);
+        });
+
+        let mut deques = Deques::new(LEN, CHANNELS);
+        let deque = measure(|| {
+            // cloned as well, so both pay the same for it
+            let matrix = black_box(matrix.clone());
+            deques.append(&matrix);
+            black_box(deques.contiguous(0));
+        });
+
+        println!(
+            "quantum {quantum:>4}: flat {:>8.2} µs, deques {:>8.2} µs per append",
+            flat.as_secs_f64() * 1e6,
+            deque.as_secs_f64() * 1e6
+        );
+    }
+}
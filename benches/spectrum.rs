@@ -0,0 +1,82 @@
+//! Time per spectrum of planning the FFT and allocating its buffers on every
+//! frame, as the process callback used to, against reusing them.
+//!
+//! Run with `cargo bench --bench spectrum`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use realfft::RealFftPlanner;
+use wav_viewer::backend::{FFT_SIZES, WindowType, apply_window};
+
+/// Counts allocations, the reused path should make none.
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// Spectra computed per measurement.
+const FRAMES: usize = 200;
+
+/// Runs `frame` `FRAMES` times, returning the time and allocations per call.
+fn measure(mut frame: impl FnMut()) -> (Duration, usize) {
+    // warm up the caches, and the planner of the reused path
+    frame();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let started = Instant::now();
+    for _ in 0..FRAMES {
+        frame();
+    }
+    let elapsed = started.elapsed() / FRAMES as u32;
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+    (elapsed, allocations / FRAMES)
+}
+
+fn main() {
+    for fft_size in FFT_SIZES {
+        let samples: Vec<f32> = (0..fft_size).map(|i| (i as f32 * 0.05).sin()).collect();
+
+        let replanned = measure(|| {
+            let mut planner = RealFftPlanner::<f32>::new();
+            let fft = planner.plan_fft_forward(fft_size);
+            let mut input = samples.clone();
+            let mut output = fft.make_output_vec();
+            apply_window(&mut input, WindowType::BlackmanHarris);
+            fft.process(&mut input, &mut output).unwrap();
+            black_box(&output);
+        });
+
+        let fft = RealFftPlanner::<f32>::new().plan_fft_forward(fft_size);
+        let mut input = fft.make_input_vec();
+        let mut output = fft.make_output_vec();
+        let mut scratch = fft.make_scratch_vec();
+        let reused = measure(|| {
+            input.copy_from_slice(&samples);
+            apply_window(&mut input, WindowType::BlackmanHarris);
+            fft.process_with_scratch(&mut input, &mut output, &mut scratch)
+                .unwrap();
+            black_box(&output);
+        });
+
+        for (name, (elapsed, allocations)) in [("replanned", replanned), ("reused", reused)] {
+            println!(
+                "fft {fft_size:>5} {name:>9}: {:>9.1} µs/frame, {allocations} allocations/frame",
+                elapsed.as_secs_f64() * 1e6
+            );
+        }
+    }
+}
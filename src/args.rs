@@ -0,0 +1,193 @@
+use std::fmt::Display;
+
+use crate::ShowType;
+use crate::headless::EVENTS;
+use wav_viewer::backend::{
+    ChannelMask, FFT_SIZES, Latency, SILENCE_THRESHOLD_RANGE, SampleFormat, StreamConfig,
+    WindowType,
+};
+
+pub const USAGE: &str = "\
+usage: wav_viewer [options]
+
+  --device <name>     node name to capture from, the default sink otherwise
+  --fft-size <n>      one of 1024, 2048, 4096, 8192, 16384
+  --view <view>       raw, spectrum, spectrogram, xy-scope or
+                      goniometer
+  --window <window>   rectangular, hann, hamming, blackman-harris or flat-top
+  --latency <frames>  buffer size to ask the server for, at 48 kHz
+  --format <format>   only ask for f32, s16, s32, s24-32 or s24 samples
+  --channels <list>   comma separated channels to capture, counting from 0,
+                      e.g. 2,3 for the center and LFE of 5.1
+  --idle-below <dB>   stop computing spectra after a second below this
+                      level in dBFS, between -120 and -30
+  --headless          print events to stdout as JSON lines instead of showing
+                      a window
+  --tui               draw the spectrum and levels in the terminal instead
+                      of a window
+  --events <list>     comma separated events printed by --headless, out of
+                      format, spectrum, levels, pitch, onset, correlation,
+                      loudness, clipped, idle, xrun, quantum, devices and
+                      error; format,spectrum,levels if not given
+  -h, --help          print this and exit";
+
+/// Settings given on the command line, applied once at startup over the
+/// saved ones.
+#[derive(Debug, Clone, Default)]
+pub struct Args {
+    pub device: Option<String>,
+    pub fft_size: Option<usize>,
+    pub show_type: Option<ShowType>,
+    pub window: Option<WindowType>,
+    pub latency: Option<Latency>,
+    pub format: Option<SampleFormat>,
+    pub channels: Option<ChannelMask>,
+    pub idle_below: Option<f32>,
+    pub headless: bool,
+    pub tui: bool,
+    pub events: Option<Vec<&'static str>>,
+}
+
+/// What to do instead of starting normally.
+#[derive(Debug)]
+pub enum ArgsError {
+    Help,
+    Invalid(String),
+}
+
+impl Args {
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, ArgsError> {
+        let mut parsed = Self::default();
+        while let Some(arg) = args.next() {
+            if arg == "-h" || arg == "--help" {
+                return Err(ArgsError::Help);
+            }
+            if arg == "--headless" {
+                parsed.headless = true;
+                continue;
+            }
+            if arg == "--tui" {
+                parsed.tui = true;
+                continue;
+            }
+            // both `--view spectrum` and `--view=spectrum`
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name.to_owned(), Some(value.to_owned())),
+                None => (arg, None),
+            };
+            let value = value
+                .or_else(|| args.next())
+                .ok_or_else(|| ArgsError::Invalid(format!("{name} needs a value")));
+            match name.as_str() {
+                "--device" => parsed.device = Some(value?),
+                "--fft-size" => {
+                    let value = value?;
+                    parsed.fft_size = value
+                        .parse()
+                        .ok()
+                        .filter(|size| FFT_SIZES.contains(size))
+                        .ok_or_else(|| invalid("--fft-size", &value, &FFT_SIZES))
+                        .map(Some)?;
+                }
+                "--latency" => {
+                    let value = value?;
+                    let frames = value.parse().ok().filter(|frames| *frames > 0).ok_or_else(|| {
+                        ArgsError::Invalid(format!(
+                            "invalid value \"{value}\" for --latency, expected a number of frames"
+                        ))
+                    })?;
+                    parsed.latency = Some(Latency::Frames(frames));
+                }
+                "--idle-below" => {
+                    let value = value?;
+                    let db = value
+                        .parse()
+                        .ok()
+                        .filter(|db| SILENCE_THRESHOLD_RANGE.contains(db))
+                        .ok_or_else(|| {
+                            ArgsError::Invalid(format!(
+                                "invalid value \"{value}\" for --idle-below, expected a level \
+                                 between -120 and -30 dBFS"
+                            ))
+                        })?;
+                    parsed.idle_below = Some(db);
+                }
+                "--channels" => {
+                    let value = value?;
+                    let channels = value
+                        .split(',')
+                        .map(|channel| channel.parse().ok().filter(|channel| *channel < 64))
+                        .collect::<Option<Vec<usize>>>()
+                        .ok_or_else(|| {
+                            ArgsError::Invalid(format!(
+                                "invalid value \"{value}\" for --channels, expected channel \
+                                 numbers from 0 to 63"
+                            ))
+                        })?;
+                    parsed.channels = Some(ChannelMask::new(channels));
+                }
+                "--events" => {
+                    let value = value?;
+                    let events = value
+                        .split(',')
+                        .map(|name| {
+                            EVENTS
+                                .iter()
+                                .find(|event| **event == name)
+                                .copied()
+                                .ok_or_else(|| invalid("--events", name, EVENTS))
+                        })
+                        .collect::<Result<_, _>>()?;
+                    parsed.events = Some(events);
+                }
+                "--format" => parsed.format = Some(pick("--format", &value?, &SampleFormat::ALL)?),
+                "--view" => parsed.show_type = Some(pick("--view", &value?, &ShowType::ALL)?),
+                "--window" => parsed.window = Some(pick("--window", &value?, &WindowType::ALL)?),
+                _ => return Err(ArgsError::Invalid(format!("unknown option {name}"))),
+            }
+        }
+        if parsed.headless && parsed.tui {
+            return Err(ArgsError::Invalid(
+                "--headless and --tui can't be used together".to_owned(),
+            ));
+        }
+        Ok(parsed)
+    }
+
+    /// The stream asked for, for the frontends without saved settings.
+    pub fn stream_config(&self) -> StreamConfig {
+        let mut config = StreamConfig::new().latency(self.latency.unwrap_or_default());
+        if let Some(device) = &self.device {
+            config = config.target(device);
+        }
+        if let Some(format) = self.format {
+            config = config.format(format);
+        }
+        if let Some(fft_size) = self.fft_size {
+            config = config.fft_size(fft_size);
+        }
+        if let Some(channels) = self.channels {
+            config = config.channel_mask(channels);
+        }
+        config
+    }
+}
+
+/// Matches `value` against the names shown in the UI, spaces written as `-`.
+fn pick<T: Display + Copy>(name: &str, value: &str, all: &[T]) -> Result<T, ArgsError> {
+    all.iter()
+        .find(|option| option.to_string().replace(' ', "-") == value)
+        .copied()
+        .ok_or_else(|| invalid(name, value, all))
+}
+
+fn invalid<T: Display>(name: &str, value: &str, all: &[T]) -> ArgsError {
+    let expected: Vec<_> = all
+        .iter()
+        .map(|option| option.to_string().replace(' ', "-"))
+        .collect();
+    ArgsError::Invalid(format!(
+        "invalid value \"{value}\" for {name}, expected one of: {}",
+        expected.join(", ")
+    ))
+}
@@ -1,29 +1,92 @@
-use iced::futures::SinkExt;
-use iced::futures::channel::mpsc::Sender;
 use pipewire as pw;
+use pw::registry::GlobalObject;
+use pw::types::ObjectType;
 use pw::{properties::properties, spa};
-use realfft::RealFftPlanner;
+use realfft::num_complex::Complex;
+use realfft::{RealFftPlanner, RealToComplex};
+use serde::{Deserialize, Serialize};
+use spa::buffer::ChunkFlags;
+use spa::param::audio::AudioFormat;
 use spa::param::format::{MediaSubtype, MediaType};
 use spa::param::format_utils;
 use spa::pod::Pod;
+use spa::utils::dict::DictRef;
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
 use std::collections::VecDeque;
 use std::convert::TryInto;
 use std::f32::consts::PI;
-use std::mem;
+use std::fmt::Display;
+use std::ops::ControlFlow;
+use std::rc::Rc;
 use std::slice::Chunks;
-use std::sync::mpsc::{Sender as StdSender, channel};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver as StdReceiver, Sender as StdSender, channel};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
-pub const FFT_SIZE: usize = 8192;
+pub const DEFAULT_FFT_SIZE: usize = 8192;
+/// FFT sizes offered in the GUI, trading latency for frequency resolution.
+pub const FFT_SIZES: [usize; 5] = [1024, 2048, 4096, 8192, 16384];
+/// How many times the FFT is longer than the analysed block, the rest being
+/// zeros. More padding interpolates the spectrum more finely without needing
+/// more samples.
+pub const ZERO_PADDINGS: [usize; 4] = [1, 2, 4, 8];
 pub const MIN_FREQ: f64 = 15.;
 pub const POINTS_PER_OCTAVE: usize = 72;
+/// Fewest points a spectrum is drawn with, however narrow its band.
+pub const MIN_POINTS: usize = 32;
 
-#[derive(Debug, Clone)]
+/// Octaves from `MIN_FREQ` up to nyquist at `rate`, none when nyquist is
+/// below `MIN_FREQ`.
+pub fn octaves(rate: u32) -> f64 {
+    (rate as f64 / 2. / MIN_FREQ).log2().max(0.)
+}
+
+/// Points a spectrum on a log axis is drawn with at `rate`,
+/// `POINTS_PER_OCTAVE` of them per octave but never fewer than `MIN_POINTS`.
+///
+/// ```
+/// use wav_viewer::backend::{MIN_POINTS, log_points};
+///
+/// assert_eq!(log_points(48000), 766);
+/// // nyquist below MIN_FREQ, or no rate negotiated yet
+/// assert_eq!(log_points(20), MIN_POINTS);
+/// assert_eq!(log_points(0), MIN_POINTS);
+/// ```
+pub fn log_points(rate: u32) -> usize {
+    ((octaves(rate) * POINTS_PER_OCTAVE as f64).round() as usize).max(MIN_POINTS)
+}
+/// Range of the gain in dB applied to the captured samples before anything
+/// looks at them.
+pub const INPUT_GAIN_RANGE: std::ops::RangeInclusive<f32> = -24.0..=48.;
+/// Samples this loud are taken as clipped, full scale give or take rounding.
+pub const CLIP_THRESHOLD: f32 = 0.999;
+/// Range of the level in dBFS below which the input counts as silent.
+pub const SILENCE_THRESHOLD_RANGE: std::ops::RangeInclusive<f32> = -120.0..=-30.;
+/// How long the input has to stay below the threshold to go idle.
+const SILENCE_HOLD: Duration = Duration::from_secs(1);
+/// While buffers keep getting lost, they are reported at most this often.
+const XRUN_REPORT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Delay before the first reconnect attempt, doubled after each failure.
+pub const RECONNECT_DELAY_MIN: Duration = Duration::from_millis(250);
+/// Upper bound of the reconnect delay.
+pub const RECONNECT_DELAY_MAX: Duration = Duration::from_secs(4);
+/// How often the capture thread checks whether it was asked to stop.
+pub const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, Serialize)]
 pub struct AudioInfo {
     rate: u32,
     channels: u32,
 }
 
 impl AudioInfo {
+    pub fn new(rate: u32, channels: u32) -> Self {
+        Self { rate, channels }
+    }
     pub fn rate(&self) -> u32 {
         self.rate
     }
@@ -32,83 +95,1995 @@ impl AudioInfo {
     }
 }
 
+/// What the stream records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CaptureMode {
+    /// The monitor of an output, what is being played.
+    #[default]
+    SinkMonitor,
+    /// An input such as a microphone or line-in.
+    Source,
+}
+
+impl CaptureMode {
+    pub const ALL: [CaptureMode; 2] = [CaptureMode::SinkMonitor, CaptureMode::Source];
+}
+
+impl Display for CaptureMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SinkMonitor => f.write_str("what I hear"),
+            Self::Source => f.write_str("what the mic hears"),
+        }
+    }
+}
+
+/// Sample formats the stream can ask for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SampleFormat {
+    F32,
+    S16,
+    S32,
+    /// 24 bits padded to 4 bytes.
+    S24In32,
+    /// 24 bits packed in 3 bytes.
+    S24,
+}
+
+impl SampleFormat {
+    /// Everything decoded, in order of preference.
+    pub const ALL: [SampleFormat; 5] = [
+        SampleFormat::F32,
+        SampleFormat::S16,
+        SampleFormat::S32,
+        SampleFormat::S24In32,
+        SampleFormat::S24,
+    ];
+
+    fn audio_format(&self) -> AudioFormat {
+        match self {
+            Self::F32 => AudioFormat::F32LE,
+            Self::S16 => AudioFormat::S16LE,
+            Self::S32 => AudioFormat::S32LE,
+            Self::S24In32 => AudioFormat::S24_32LE,
+            Self::S24 => AudioFormat::S24LE,
+        }
+    }
+}
+
+impl Display for SampleFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::F32 => f.write_str("f32"),
+            Self::S16 => f.write_str("s16"),
+            Self::S32 => f.write_str("s32"),
+            Self::S24In32 => f.write_str("s24-32"),
+            Self::S24 => f.write_str("s24"),
+        }
+    }
+}
+
+/// How to set up the capture stream, the defaults record the default sink.
+///
+/// ```no_run
+/// use wav_viewer::backend::{CaptureMode, SampleFormat, StreamConfig, spawn_capture};
+///
+/// let config = StreamConfig::new()
+///     .capture_mode(CaptureMode::Source)
+///     .target("alsa_input.usb-mic")
+///     .format(SampleFormat::F32)
+///     .fft_size(4096);
+/// let (events, _guard) = spawn_capture(config);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StreamConfig {
+    mode: CaptureMode,
+    target: Option<String>,
+    latency: Latency,
+    /// Only this format, every one of `SampleFormat::ALL` when `None`.
+    format: Option<SampleFormat>,
+    fft_size: usize,
+    channel_mask: Option<ChannelMask>,
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        Self {
+            mode: CaptureMode::default(),
+            target: None,
+            latency: Latency::default(),
+            format: None,
+            fft_size: DEFAULT_FFT_SIZE,
+            channel_mask: None,
+        }
+    }
+}
+
+impl StreamConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn capture_mode(mut self, mode: CaptureMode) -> Self {
+        self.mode = mode;
+        self
+    }
+    /// A node name or serial to capture from instead of the default sink or
+    /// source.
+    pub fn target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+    pub fn latency(mut self, latency: Latency) -> Self {
+        self.latency = latency;
+        self
+    }
+    pub fn format(mut self, format: SampleFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+    /// FFT size until a `BackendCommand::FftSize` changes it, one of
+    /// `FFT_SIZES`. Any other size is ignored.
+    pub fn fft_size(mut self, fft_size: usize) -> Self {
+        if FFT_SIZES.contains(&fft_size) {
+            self.fft_size = fft_size;
+        }
+        self
+    }
+    /// Only decode these channels, the others are skipped in every frame.
+    /// The stream reports the channels kept as its channel count.
+    pub fn channel_mask(mut self, mask: ChannelMask) -> Self {
+        self.channel_mask = Some(mask);
+        self
+    }
+    fn formats(&self) -> &[SampleFormat] {
+        match &self.format {
+            Some(format) => std::slice::from_ref(format),
+            None => &SampleFormat::ALL,
+        }
+    }
+}
+
+/// Channels of a frame to keep, by index from 0 up to 63.
+///
+/// ```
+/// use wav_viewer::backend::ChannelMask;
+///
+/// let mask = ChannelMask::new([2, 5]);
+/// assert_eq!(mask.channels(6), [2, 5]);
+/// // only the ones the device has
+/// assert_eq!(mask.channels(4), [2]);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChannelMask(u64);
+
+impl ChannelMask {
+    /// Keeps `channels`, indices past 63 are ignored.
+    pub fn new(channels: impl IntoIterator<Item = usize>) -> Self {
+        Self(
+            channels
+                .into_iter()
+                .filter(|channel| *channel < u64::BITS as usize)
+                .fold(0, |mask, channel| mask | 1 << channel),
+        )
+    }
+
+    pub fn contains(&self, channel: usize) -> bool {
+        channel < u64::BITS as usize && self.0 & 1 << channel != 0
+    }
+
+    /// The kept channels out of `channels`, in order.
+    pub fn channels(&self, channels: usize) -> Vec<usize> {
+        (0..channels)
+            .filter(|channel| self.contains(*channel))
+            .collect()
+    }
+}
+
+/// Rate the requested latency is expressed at, the server scales it to the
+/// graph's actual rate.
+const LATENCY_RATE: u32 = 48000;
+
+/// Buffer size asked of the graph. Smaller buffers update the views more
+/// often, larger ones cost less CPU. The server may clamp it, the quantum
+/// actually used is reported with `PwEvent::Quantum`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Latency {
+    /// Whatever the graph runs at.
+    #[default]
+    Default,
+    /// This many frames at `LATENCY_RATE`.
+    Frames(u32),
+}
+
+impl Latency {
+    pub const ALL: [Latency; 7] = [
+        Latency::Default,
+        Latency::Frames(64),
+        Latency::Frames(128),
+        Latency::Frames(256),
+        Latency::Frames(512),
+        Latency::Frames(1024),
+        Latency::Frames(2048),
+    ];
+
+    /// Value of `node.latency`, a fraction such as `256/48000`.
+    fn node_latency(&self) -> Option<String> {
+        match self {
+            Self::Default => None,
+            Self::Frames(frames) => Some(format!("{frames}/{LATENCY_RATE}")),
+        }
+    }
+}
+
+impl Display for Latency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Default => f.write_str("default latency"),
+            Self::Frames(frames) => write!(f, "{frames} frames"),
+        }
+    }
+}
+
+/// An audio sink or source that can be captured.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DeviceInfo {
+    pub id: u32,
+    /// Node name, usable as a capture target.
+    pub name: String,
+    pub description: String,
+    /// The mode capturing from this device needs.
+    pub mode: CaptureMode,
+}
+
+impl Display for DeviceInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.description)
+    }
+}
+
+/// Window applied to each block before the FFT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WindowType {
+    Rectangular,
+    Hann,
+    Hamming,
+    #[default]
+    BlackmanHarris,
+    FlatTop,
+}
+
+impl WindowType {
+    pub const ALL: [WindowType; 5] = [
+        WindowType::Rectangular,
+        WindowType::Hann,
+        WindowType::Hamming,
+        WindowType::BlackmanHarris,
+        WindowType::FlatTop,
+    ];
+
+    /// Coefficients of the generalized cosine window
+    /// `w(k) = a0 - a1 cos(2πk) + a2 cos(4πk) - ...`
+    fn coefficients(&self) -> &'static [f32] {
+        match self {
+            Self::Rectangular => &[1.],
+            Self::Hann => &[0.5, 0.5],
+            Self::Hamming => &[0.54, 0.46],
+            Self::BlackmanHarris => &[0.35875, 0.48829, 0.14128, 0.01168],
+            // standard 5-term flat-top, for amplitude accuracy
+            Self::FlatTop => &[
+                0.215_578_95,
+                0.416_631_58,
+                0.277_263_16,
+                0.083_578_95,
+                0.006_947_37,
+            ],
+        }
+    }
+
+    /// Mean of the window, the fraction of a tone's amplitude left in its
+    /// bin: 0.5 for Hann, 0.54 for Hamming, 0.35875 for Blackman-Harris and
+    /// 0.2156 for flat-top.
+    pub fn coherent_gain(&self) -> f32 {
+        self.coefficients()[0]
+    }
+
+    /// Equivalent noise bandwidth in bins, how much wider than a bin white
+    /// noise makes the window look: 1.5 for Hann, 1.363 for Hamming, 2.004
+    /// for Blackman-Harris and 3.770 for flat-top.
+    ///
+    /// These are the values for long blocks, short ones differ slightly.
+    ///
+    /// ```
+    /// use wav_viewer::backend::{WindowType, apply_window};
+    ///
+    /// let references = [
+    ///     (WindowType::Rectangular, 1.),
+    ///     (WindowType::Hann, 1.5),
+    ///     (WindowType::Hamming, 1.3628),
+    ///     (WindowType::BlackmanHarris, 2.0044),
+    ///     (WindowType::FlatTop, 3.7702),
+    /// ];
+    /// for (window, reference) in references {
+    ///     assert!((window.enbw() - reference).abs() < 1e-3, "{window}");
+    ///
+    ///     // the same from the window as it is applied
+    ///     let mut block = vec![1_f32; 4096];
+    ///     apply_window(&mut block, window);
+    ///     let sum: f32 = block.iter().sum();
+    ///     let power: f32 = block.iter().map(|gain| gain * gain).sum();
+    ///     let enbw = block.len() as f32 * power / (sum * sum);
+    ///     assert!((enbw - reference).abs() < 1e-2, "{window}");
+    ///     assert!((sum / block.len() as f32 - window.coherent_gain()).abs() < 1e-3);
+    /// }
+    /// ```
+    pub fn enbw(&self) -> f32 {
+        let coefficients = self.coefficients();
+        // the cosines are orthogonal, each one adds half its square
+        let power = coefficients
+            .iter()
+            .skip(1)
+            .fold(coefficients[0] * coefficients[0], |power, a| {
+                power + a * a / 2.
+            });
+        power / (coefficients[0] * coefficients[0])
+    }
+}
+
+impl Display for WindowType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Rectangular => f.write_str("rectangular"),
+            Self::Hann => f.write_str("hann"),
+            Self::Hamming => f.write_str("hamming"),
+            Self::BlackmanHarris => f.write_str("blackman-harris"),
+            Self::FlatTop => f.write_str("flat-top"),
+        }
+    }
+}
+
+/// Overlap between consecutive FFT blocks.
+///
+/// The spectrum is recomputed every `hop` samples, so more overlap gives a
+/// smoother display at the cost of more FFTs per second: 87.5% overlap runs
+/// eight times as many FFTs as no overlap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overlap {
+    None,
+    Quarter,
+    Half,
+    ThreeQuarters,
+    #[default]
+    SevenEighths,
+}
+
+impl Overlap {
+    pub const ALL: [Overlap; 5] = [
+        Overlap::None,
+        Overlap::Quarter,
+        Overlap::Half,
+        Overlap::ThreeQuarters,
+        Overlap::SevenEighths,
+    ];
+
+    /// Number of new samples between two FFTs of `fft_size` samples.
+    pub fn hop(&self, fft_size: usize) -> usize {
+        let hop = match self {
+            Self::None => fft_size,
+            Self::Quarter => fft_size * 3 / 4,
+            Self::Half => fft_size / 2,
+            Self::ThreeQuarters => fft_size / 4,
+            Self::SevenEighths => fft_size / 8,
+        };
+        hop.max(1)
+    }
+}
+
+impl Display for Overlap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => f.write_str("0% overlap"),
+            Self::Quarter => f.write_str("25% overlap"),
+            Self::Half => f.write_str("50% overlap"),
+            Self::ThreeQuarters => f.write_str("75% overlap"),
+            Self::SevenEighths => f.write_str("87.5% overlap"),
+        }
+    }
+}
+
+/// How much the sum of the channels is turned down when downmixing to mono.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DownmixLaw {
+    /// -6 dB per doubling of channels, the plain average. A tone in every
+    /// channel keeps its level.
+    #[default]
+    Average,
+    /// -3 dB per doubling, uncorrelated content keeps its power.
+    EqualPower,
+}
+
+impl DownmixLaw {
+    pub const ALL: [DownmixLaw; 2] = [DownmixLaw::Average, DownmixLaw::EqualPower];
+
+    /// Gain applied to the sum of `channels` channels.
+    pub fn gain(&self, channels: usize) -> f32 {
+        let channels = channels.max(1) as f32;
+        match self {
+            Self::Average => 1. / channels,
+            Self::EqualPower => 1. / channels.sqrt(),
+        }
+    }
+}
+
+impl Display for DownmixLaw {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Average => f.write_str("-6 dB mix"),
+            Self::EqualPower => f.write_str("-3 dB mix"),
+        }
+    }
+}
+
+/// What becomes of the channels past `ChannelSelection::max`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HiddenChannels {
+    /// Left out.
+    #[default]
+    Hide,
+    /// Averaged into the shown channels, taking turns.
+    Mix,
+}
+
+impl HiddenChannels {
+    pub const ALL: [HiddenChannels; 2] = [HiddenChannels::Hide, HiddenChannels::Mix];
+}
+
+impl Display for HiddenChannels {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Hide => f.write_str("hide the rest"),
+            Self::Mix => f.write_str("mix in the rest"),
+        }
+    }
+}
+
+/// Which channels are shown when there are too many to read, the spectrum
+/// and its downmix only see those.
+///
+/// ```
+/// use wav_viewer::backend::{ChannelSelection, HiddenChannels};
+///
+/// // 7.1, two at a time, starting at the center
+/// let selection = ChannelSelection {
+///     max: Some(2),
+///     first: 2,
+///     hidden: HiddenChannels::Hide,
+/// };
+/// assert_eq!(selection.groups(8), vec![vec![2], vec![3]]);
+///
+/// let selection = ChannelSelection {
+///     hidden: HiddenChannels::Mix,
+///     ..selection
+/// };
+/// assert_eq!(selection.groups(8), vec![vec![2, 4, 6, 0], vec![3, 5, 7, 1]]);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChannelSelection {
+    /// Most channels shown, every one when `None`.
+    pub max: Option<usize>,
+    /// First channel shown, the others follow it and wrap around.
+    pub first: usize,
+    pub hidden: HiddenChannels,
+}
+
+impl ChannelSelection {
+    /// Number of channels shown out of `channels`.
+    pub fn shown(&self, channels: usize) -> usize {
+        self.max
+            .map_or(channels, |max| max.clamp(1, channels.max(1)))
+    }
+
+    /// Channels making up each shown one out of `channels`, the one it
+    /// shows first.
+    pub fn groups(&self, channels: usize) -> Vec<Vec<usize>> {
+        if channels == 0 {
+            return vec![];
+        }
+        let shown = self.shown(channels);
+        let channel = |slot: usize| (self.first + slot) % channels;
+        (0..shown)
+            .map(|slot| {
+                let mixed = (shown..channels)
+                    .filter(|rest| self.hidden == HiddenChannels::Mix && rest % shown == slot);
+                std::iter::once(slot).chain(mixed).map(channel).collect()
+            })
+            .collect()
+    }
+
+    /// The next channels, by as many as are shown.
+    pub fn next(&self, channels: usize) -> Self {
+        Self {
+            first: (self.first + self.shown(channels)) % channels.max(1),
+            ..*self
+        }
+    }
+
+    /// Samples of every shown channel, mixed ones averaged in. Borrows
+    /// `channels` when all of them are shown as they are.
+    pub fn apply<'a>(&self, channels: &'a [Vec<f32>]) -> Cow<'a, [Vec<f32>]> {
+        if self.shown(channels.len()) == channels.len()
+            && self.first.is_multiple_of(channels.len().max(1))
+        {
+            return Cow::Borrowed(channels);
+        }
+        let frames = channels.iter().map(Vec::len).min().unwrap_or(0);
+        let groups = self.groups(channels.len());
+        Cow::Owned(
+            groups
+                .iter()
+                .map(|group| {
+                    let gain = 1. / group.len() as f32;
+                    (0..frames)
+                        .map(|frame| {
+                            group
+                                .iter()
+                                .map(|channel| channels[*channel][frame])
+                                .sum::<f32>()
+                                * gain
+                        })
+                        .collect()
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Loudest peaks quieter than this, in dB, count as silence for the pitch.
+pub const PITCH_MIN_DB: f32 = -70.;
+
+const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// The nearest equal-tempered note to the loudest tone.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Pitch {
+    pub freq: f32,
+    /// Note name with its octave, `A4` being 440 Hz.
+    pub note: String,
+    /// Distance from the note, in `-50..=50`.
+    pub cents: f32,
+}
+
+impl Pitch {
+    pub fn from_frequency(freq: f32) -> Self {
+        let midi = 69. + 12. * (freq / 440.).log2();
+        let nearest = midi.round();
+        let index = nearest as i32;
+        Self {
+            freq,
+            note: format!(
+                "{}{}",
+                NOTE_NAMES[index.rem_euclid(12) as usize],
+                index.div_euclid(12) - 1
+            ),
+            cents: (midi - nearest) * 100.,
+        }
+    }
+}
+
+impl Display for Pitch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {:+.0}¢ ({:.1} Hz)", self.note, self.cents, self.freq)
+    }
+}
+
+/// Finds the loudest bin above DC and refines its frequency by fitting a
+/// parabola through it and its neighbours.
+fn detect_pitch(channels: &[Vec<f32>], rate: u32, fft_size: usize) -> Option<Pitch> {
+    let (channel, bin, db) = channels
+        .iter()
+        .flat_map(|channel| {
+            channel
+                .iter()
+                .enumerate()
+                .skip(1)
+                .map(move |(bin, db)| (channel, bin, *db))
+        })
+        .max_by(|a, b| a.2.total_cmp(&b.2))?;
+    if db < PITCH_MIN_DB {
+        return None;
+    }
+    let offset = match (channel.get(bin - 1), channel.get(bin + 1)) {
+        (Some(left), Some(right)) => {
+            let curvature = left - 2. * db + right;
+            if curvature.abs() > f32::EPSILON {
+                0.5 * (left - right) / curvature
+            } else {
+                0.
+            }
+        }
+        _ => 0.,
+    };
+    let freq = (bin as f32 + offset) * rate as f32 / fft_size as f32;
+    Some(Pitch::from_frequency(freq))
+}
+
+/// Flux has to exceed its running average by this factor to be an onset.
+const ONSET_THRESHOLD: f32 = 1.8;
+/// Weight of the newest spectrum in the running average of the flux.
+const ONSET_AVERAGE_WEIGHT: f32 = 0.05;
+/// Flux below this, in summed linear magnitude, is never an onset.
+const ONSET_MIN_FLUX: f32 = 0.01;
+
+/// Spots transients by the spectral flux, how much the magnitudes grew since
+/// the previous spectrum.
+#[derive(Debug, Default)]
+struct OnsetDetector {
+    /// Linear magnitudes of the previous spectrum, summed over the channels.
+    previous: Vec<f32>,
+    average: f32,
+    /// Set while the flux stays high, a single transient reports once.
+    triggered: bool,
+}
+
+impl OnsetDetector {
+    /// Forgets the previous spectrum, keeping its allocation.
+    fn reset(&mut self) {
+        self.previous.clear();
+        self.average = 0.;
+        self.triggered = false;
+    }
+
+    /// Takes the next spectrum in dB and tells whether it starts an onset.
+    fn process(&mut self, channels: &[Vec<f32>]) -> bool {
+        let bins = channels.first().map_or(0, Vec::len);
+        let mut magnitudes = vec![0.; bins];
+        for channel in channels {
+            for (magnitude, db) in magnitudes.iter_mut().zip(channel) {
+                *magnitude += 10_f32.powf(db / 20.);
+            }
+        }
+        let previous = std::mem::replace(&mut self.previous, magnitudes);
+        // nothing to compare the first spectrum or a resized one with
+        if previous.len() != bins {
+            return false;
+        }
+        let flux: f32 = self
+            .previous
+            .iter()
+            .zip(&previous)
+            .map(|(current, previous)| (current - previous).max(0.))
+            .sum();
+        let onset = flux > ONSET_MIN_FLUX && flux > self.average * ONSET_THRESHOLD;
+        self.average += (flux - self.average) * ONSET_AVERAGE_WEIGHT;
+        let started = onset && !self.triggered;
+        self.triggered = onset;
+        started
+    }
+}
+
+/// Length of the blocks loudness is measured over, momentary and short-term
+/// loudness average the last 4 and 30 of them.
+const LOUDNESS_BLOCK: Duration = Duration::from_millis(100);
+const MOMENTARY_BLOCKS: usize = 4;
+const SHORT_TERM_BLOCKS: usize = 30;
+/// Loudness below which a block is silence, left out of everything.
+pub const LOUDNESS_ABSOLUTE_GATE: f32 = -70.;
+/// Blocks this far below the loudness of the ungated ones don't count
+/// towards the integrated loudness.
+const LOUDNESS_RELATIVE_GATE: f32 = -10.;
+/// Width of the histogram bins the gated blocks are counted in, in LU.
+const LOUDNESS_HISTOGRAM_STEP: f32 = 0.1;
+/// Bins from the absolute gate up to +10 LUFS.
+const LOUDNESS_HISTOGRAM_BINS: usize = 800;
+
+/// Loudness in LUFS, `None` below `LOUDNESS_ABSOLUTE_GATE`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Loudness {
+    /// Over the last 400 ms.
+    pub momentary: Option<f32>,
+    /// Over the last 3 s.
+    pub short_term: Option<f32>,
+    /// Since the meter started or was reset, gated the way BS.1770 does.
+    pub integrated: Option<f32>,
+}
+
+/// A second order IIR section, in transposed direct form II.
+#[derive(Debug, Clone, Copy, Default)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.state[0];
+        self.state[0] = self.b[1] * x - self.a[0] * y + self.state[1];
+        self.state[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+/// The K-weighting of ITU-R BS.1770, a high shelf for the effect of the head
+/// followed by a high pass, worked out for `rate`.
+fn k_weighting(rate: u32) -> [Biquad; 2] {
+    let rate = rate.max(1) as f64;
+    let k = (std::f64::consts::PI * 1681.974450955533 / rate).tan();
+    let q = 0.7071752369554196;
+    let vh = 10_f64.powf(3.999843853973347 / 20.);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1. + k / q + k * k;
+    let shelf = Biquad {
+        b: [
+            (vh + vb * k / q + k * k) / a0,
+            2. * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        a: [2. * (k * k - 1.) / a0, (1. - k / q + k * k) / a0],
+        state: [0.; 2],
+    };
+    let k = (std::f64::consts::PI * 38.13547087602444 / rate).tan();
+    let q = 0.5003270373238773;
+    let a0 = 1. + k / q + k * k;
+    let high_pass = Biquad {
+        b: [1., -2., 1.],
+        a: [2. * (k * k - 1.) / a0, (1. - k / q + k * k) / a0],
+        state: [0.; 2],
+    };
+    [shelf, high_pass]
+}
+
+fn power_to_lufs(power: f64) -> f32 {
+    (-0.691 + 10. * power.log10()) as f32
+}
+
+/// Momentary, short-term and integrated loudness of the samples fed in, a
+/// simplified ITU-R BS.1770: every channel is weighted the same, surrounds
+/// aren't raised and the LFE isn't left out, and the integrated loudness is
+/// kept to a tenth of an LU.
+///
+/// ```
+/// use std::f32::consts::PI;
+/// use wav_viewer::backend::LoudnessMeter;
+///
+/// // a 1 kHz tone at -20 dBFS in one channel, 3 s of it
+/// let rate = 48000;
+/// let tone: Vec<f32> = (0..3 * rate)
+///     .map(|i| 0.1 * (2. * PI * 1000. * i as f32 / rate as f32).sin())
+///     .collect();
+/// let mut meter = LoudnessMeter::new(rate, 1);
+/// let loudness = tone
+///     .chunks(1024)
+///     .filter_map(|buffer| meter.process(&[buffer.to_vec()]))
+///     .last()
+///     .unwrap();
+/// // a full scale 1 kHz sine in one channel reads -3.01 LUFS
+/// for lufs in [loudness.momentary, loudness.short_term, loudness.integrated] {
+///     assert!((lufs.unwrap() + 23.01).abs() < 0.05, "{lufs:?}");
+/// }
+///
+/// let silence = vec![0.; rate as usize];
+/// let loudness = meter.process(&[silence]).unwrap();
+/// assert_eq!(loudness.momentary, None);
+/// // the fade into silence counts a little, the silence itself not at all
+/// let integrated = loudness.integrated.unwrap();
+/// assert!(integrated > -23.5 && integrated < -23.01);
+/// ```
+#[derive(Debug, Clone)]
+pub struct LoudnessMeter {
+    /// Frames per block.
+    block_len: usize,
+    filters: Vec<[Biquad; 2]>,
+    /// Weighted power summed over the channels, so far in this block.
+    power: f64,
+    frames: usize,
+    /// Mean power of the latest blocks, the newest last.
+    blocks: VecDeque<f64>,
+    /// Count and summed power of the momentary readings above the absolute
+    /// gate, by their loudness.
+    histogram: Vec<(u64, f64)>,
+}
+
+impl LoudnessMeter {
+    pub fn new(rate: u32, channels: usize) -> Self {
+        Self {
+            block_len: ((rate as f64 * LOUDNESS_BLOCK.as_secs_f64()) as usize).max(1),
+            filters: vec![k_weighting(rate); channels],
+            power: 0.,
+            frames: 0,
+            blocks: VecDeque::with_capacity(SHORT_TERM_BLOCKS + 1),
+            histogram: vec![(0, 0.); LOUDNESS_HISTOGRAM_BINS],
+        }
+    }
+
+    /// Starts the integrated loudness over.
+    pub fn reset_integrated(&mut self) {
+        self.histogram.fill((0, 0.));
+    }
+
+    /// Feeds the next samples of every channel, returning the loudness
+    /// when they complete at least one block.
+    pub fn process(&mut self, channels: &[Vec<f32>]) -> Option<Loudness> {
+        let frames = channels.iter().map(Vec::len).min().unwrap_or(0);
+        if channels.len() != self.filters.len() || frames == 0 {
+            return None;
+        }
+        let mut completed = false;
+        for frame in 0..frames {
+            for (filters, channel) in self.filters.iter_mut().zip(channels) {
+                let sample = filters
+                    .iter_mut()
+                    .fold(channel[frame] as f64, |sample, filter| {
+                        filter.process(sample)
+                    });
+                self.power += sample * sample;
+            }
+            self.frames += 1;
+            if self.frames == self.block_len {
+                self.push_block(self.power / self.block_len as f64);
+                self.power = 0.;
+                self.frames = 0;
+                completed = true;
+            }
+        }
+        completed.then(|| self.loudness())
+    }
+
+    fn push_block(&mut self, power: f64) {
+        if self.blocks.len() == SHORT_TERM_BLOCKS {
+            self.blocks.pop_front();
+        }
+        self.blocks.push_back(power);
+        // every block ends a momentary window, overlapping the last by 75%
+        if self.blocks.len() >= MOMENTARY_BLOCKS {
+            let power = self.mean_power(MOMENTARY_BLOCKS);
+            let lufs = power_to_lufs(power);
+            if lufs > LOUDNESS_ABSOLUTE_GATE {
+                let bin = ((lufs - LOUDNESS_ABSOLUTE_GATE) / LOUDNESS_HISTOGRAM_STEP) as usize;
+                let (count, sum) = &mut self.histogram[bin.min(LOUDNESS_HISTOGRAM_BINS - 1)];
+                *count += 1;
+                *sum += power;
+            }
+        }
+    }
+
+    /// Mean power of the latest `blocks` blocks, or of all there are.
+    fn mean_power(&self, blocks: usize) -> f64 {
+        let blocks = blocks.min(self.blocks.len()).max(1);
+        self.blocks.iter().rev().take(blocks).sum::<f64>() / blocks as f64
+    }
+
+    fn gated(lufs: f32) -> Option<f32> {
+        (lufs > LOUDNESS_ABSOLUTE_GATE).then_some(lufs)
+    }
+
+    /// Mean power of the momentary readings counted from histogram bin
+    /// `first` up, `None` without any.
+    fn integrated_power(&self, first: usize) -> Option<f64> {
+        let (count, sum) = self.histogram[first.min(LOUDNESS_HISTOGRAM_BINS)..]
+            .iter()
+            .fold((0, 0.), |(count, sum), bin| (count + bin.0, sum + bin.1));
+        (count > 0).then(|| sum / count as f64)
+    }
+
+    fn loudness(&self) -> Loudness {
+        let integrated = self.integrated_power(0).and_then(|ungated| {
+            let gate = power_to_lufs(ungated) + LOUDNESS_RELATIVE_GATE;
+            let first = ((gate - LOUDNESS_ABSOLUTE_GATE) / LOUDNESS_HISTOGRAM_STEP).ceil();
+            self.integrated_power(first.max(0.) as usize)
+        });
+        Loudness {
+            momentary: Self::gated(power_to_lufs(self.mean_power(MOMENTARY_BLOCKS))),
+            short_term: Self::gated(power_to_lufs(self.mean_power(SHORT_TERM_BLOCKS))),
+            integrated: integrated.map(power_to_lufs),
+        }
+    }
+}
+
+/// Frequency weighting applied to the displayed spectrum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Weighting {
+    #[default]
+    None,
+    /// IEC 61672 A-weighting, roughly how loud quiet sounds are perceived.
+    A,
+    /// IEC 61672 C-weighting, flatter, for loud sounds.
+    C,
+}
+
+impl Weighting {
+    pub const ALL: [Weighting; 3] = [Weighting::None, Weighting::A, Weighting::C];
+
+    /// Offset in dB to add at `freq` Hz, 0 at 1 kHz.
+    pub fn offset_db(&self, freq: f64) -> f64 {
+        let f2 = freq * freq;
+        let pole = |hz: f64| f2 + hz * hz;
+        match self {
+            Self::None => 0.,
+            Self::A => {
+                let response = 12194_f64.powi(2) * f2 * f2
+                    / (pole(20.6) * (pole(107.7) * pole(737.9)).sqrt() * pole(12194.));
+                20. * response.log10() + 2.0
+            }
+            Self::C => {
+                let response = 12194_f64.powi(2) * f2 / (pole(20.6) * pole(12194.));
+                20. * response.log10() + 0.06
+            }
+        }
+    }
+}
+
+impl Display for Weighting {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => f.write_str("no weighting"),
+            Self::A => f.write_str("A-weighting"),
+            Self::C => f.write_str("C-weighting"),
+        }
+    }
+}
+
+/// What the spectrum's decibels measure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SpectrumKind {
+    /// Peak amplitude of a sine in the bin, a full-scale one reads 0 dB.
+    #[default]
+    Magnitude,
+    /// Power in the bin, a full-scale sine reads -3 dB.
+    Power,
+    /// Power per Hz, the power divided by the bandwidth of a bin as widened by
+    /// the window. White noise reads the same whatever the FFT size or
+    /// window.
+    Psd,
+}
+
+impl SpectrumKind {
+    pub const ALL: [SpectrumKind; 3] = [
+        SpectrumKind::Magnitude,
+        SpectrumKind::Power,
+        SpectrumKind::Psd,
+    ];
+
+    pub fn unit(&self) -> &'static str {
+        match self {
+            Self::Magnitude | Self::Power => "dB",
+            Self::Psd => "dB/Hz",
+        }
+    }
+}
+
+impl Display for SpectrumKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Magnitude => f.write_str("magnitude"),
+            Self::Power => f.write_str("power"),
+            Self::Psd => f.write_str("power density"),
+        }
+    }
+}
+
+/// Mapping from FFT magnitudes to decibels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DbScale {
+    /// Magnitude that reads as 0 dB.
+    ///
+    /// Magnitudes are normalized by the window's coherent gain and made
+    /// single-sided, so 1 is the level of a full-scale sine whatever the FFT
+    /// size or window.
+    pub reference: f32,
+    /// Level silent bins are clamped to, instead of `-inf`.
+    pub floor_db: f32,
+    /// Top of the displayed range.
+    pub max_db: f32,
+}
+
+impl Default for DbScale {
+    fn default() -> Self {
+        Self {
+            // dBFS, a full-scale sine reads 0 dB
+            reference: 1.,
+            floor_db: -120.,
+            max_db: 0.,
+        }
+    }
+}
+
+impl DbScale {
+    pub fn magnitude_to_db(&self, magnitude: f32) -> f32 {
+        // `max` also maps NaN to the floor
+        (20. * (magnitude / self.reference).log10()).max(self.floor_db)
+    }
+}
+
+/// Per-channel spectra in dB, along with the range they should be displayed in.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SpectrumFrame {
+    channels: Vec<Vec<f32>>,
+    fft_size: usize,
+    min_db: f32,
+    max_db: f32,
+    noise_bandwidth: f32,
+    kind: SpectrumKind,
+    /// Width of a bin in Hz.
+    bin_width: f32,
+}
+
+impl SpectrumFrame {
+    /// Magnitude spectra in dB, as measured through a rectangular window,
+    /// displayed in the range of `db_scale`.
+    pub fn new(channels: Vec<Vec<f32>>, fft_size: usize, rate: u32, db_scale: DbScale) -> Self {
+        Self {
+            channels,
+            fft_size,
+            min_db: db_scale.floor_db,
+            max_db: db_scale.max_db,
+            noise_bandwidth: 1.,
+            kind: SpectrumKind::Magnitude,
+            bin_width: rate as f32 / fft_size.max(1) as f32,
+        }
+    }
+    /// One spectrum per channel, or a single one when downmixing to mono.
+    /// Each is single-sided, `spectrum_bins(fft_size)` bins from DC to
+    /// nyquist.
+    pub fn channels(&self) -> &[Vec<f32>] {
+        &self.channels
+    }
+    pub fn channels_mut(&mut self) -> &mut [Vec<f32>] {
+        &mut self.channels
+    }
+    /// Size of the FFT the bins came from, bin `i` sits at `i * rate / fft_size`.
+    pub fn fft_size(&self) -> usize {
+        self.fft_size
+    }
+    /// Level in dB of everything from `low` to `high` Hz in `channel`, its bins
+    /// summed by power and divided by the window's noise bandwidth so a sine
+    /// reads the same as its bin, densities integrated over the band. `None`
+    /// if no bin falls in the band.
+    pub fn band_level(&self, channel: usize, rate: u32, low: f64, high: f64) -> Option<f32> {
+        let bins_per_hz = self.fft_size as f64 / rate.max(1) as f64;
+        let first = (low * bins_per_hz).ceil() as usize;
+        let channel = self.channels.get(channel)?;
+        let last = ((high * bins_per_hz).floor() as usize).min(channel.len().checked_sub(1)?);
+        let bins = channel.get(first..=last).filter(|bins| !bins.is_empty())?;
+        let power: f32 = bins.iter().map(|db| 10_f32.powf(db / 10.)).sum();
+        let power = match self.kind {
+            SpectrumKind::Magnitude | SpectrumKind::Power => {
+                power / self.noise_bandwidth.max(f32::EPSILON)
+            }
+            // the density already accounts for the window
+            SpectrumKind::Psd => power * self.bin_width,
+        };
+        Some(10. * power.log10())
+    }
+    /// Spectral centroid in Hz across all channels, see
+    /// [`spectral_centroid`].
+    pub fn centroid(&self, rate: u32) -> Option<f64> {
+        let bin_hz = rate as f64 / self.fft_size.max(1) as f64;
+        spectral_centroid(&self.channels, bin_hz, self.min_db)
+    }
+    pub fn kind(&self) -> SpectrumKind {
+        self.kind
+    }
+    pub fn min_db(&self) -> f32 {
+        self.min_db
+    }
+    pub fn max_db(&self) -> f32 {
+        self.max_db
+    }
+}
+
+/// Commands sent from the GUI to the capture thread.
+///
+/// They go through the `std::sync::mpsc` channel behind [`BackendHandle`] and
+/// are drained by a timer on the capture thread's mainloop, which also plans
+/// the FFT and allocates whatever else a command needs. The realtime process
+/// callback then swaps the result in at the start of its next buffer, so it
+/// neither allocates for them nor takes a lock; commands sent while no
+/// buffers flow apply with the first one that does.
+/// Changing the device or capture mode restarts the subscription instead.
+#[derive(Debug, Clone, Copy)]
+pub enum BackendCommand {
+    Window(WindowType),
+    DbScale(DbScale),
+    Overlap(Overlap),
+    FftSize(usize),
+    /// Whether anything shows the spectrum, the FFT is skipped while not.
+    SpectrumVisible(bool),
+    /// Subtract the block mean before windowing, so a DC bias doesn't swamp
+    /// the low bins.
+    RemoveDc(bool),
+    SpectrumKind(SpectrumKind),
+    /// Sum all channels into a single spectrum.
+    Downmix(bool),
+    DownmixLaw(DownmixLaw),
+    /// Gain in dB applied to the samples before the waveform, the levels and
+    /// the spectrum see them, clamped to `INPUT_GAIN_RANGE`.
+    InputGain(f32),
+    /// One of `ZERO_PADDINGS`.
+    ZeroPadding(usize),
+    /// Level in dBFS below which the input goes idle after `SILENCE_HOLD`,
+    /// clamped to `SILENCE_THRESHOLD_RANGE`. No FFT runs while idle. `None`
+    /// never goes idle.
+    SilenceThreshold(Option<f32>),
+    /// Channels the spectrum is computed for, downmixed or not.
+    Channels(ChannelSelection),
+    /// Start the integrated loudness over.
+    ResetLoudness,
+}
+
+/// Handle used by the GUI to control the running backend.
 #[derive(Debug, Clone)]
+pub struct BackendHandle {
+    sender: StdSender<BackendCommand>,
+    shutdown: Arc<AtomicBool>,
+}
+
+impl BackendHandle {
+    pub fn send(&self, command: BackendCommand) {
+        let _ = self.sender.send(command);
+    }
+
+    /// Asks the capture thread to stop, it will not reconnect afterwards.
+    pub fn stop(&self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Stops the capture thread and waits for it when dropped.
+pub struct ShutdownGuard {
+    shutdown: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for ShutdownGuard {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Everything the capture thread reports. Serialized with the variant name in
+/// snake case as the key, the handle and the raw samples are left out.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum PwEvent {
+    #[serde(skip)]
+    Ready(BackendHandle),
+    #[serde(rename = "format")]
     FormatChange(AudioInfo),
+    #[serde(skip)]
     DataNew(Matrix<f32>),
-    Spectrum(Vec<f32>),
-    PwErr,
+    Spectrum(SpectrumFrame),
+    /// All audio sinks currently known, sent again whenever one comes or goes.
+    Devices(Vec<DeviceInfo>),
+    /// Frames per buffer, sent again whenever it changes.
+    Quantum(u32),
+    /// The requested capture target could not be linked.
+    TargetNotFound(String),
+    /// The connection was lost, the next attempt happens after the delay.
+    Reconnecting(Duration),
+    /// Note of the loudest tone with every spectrum, `None` in silence.
+    Pitch(Option<Pitch>),
+    /// How long computing the latest spectrum took.
+    SpectrumTime(Duration),
+    /// A transient such as a beat started with the latest spectrum.
+    Onset,
+    /// RMS and peak of every channel over the last buffer.
+    Levels {
+        rms: Vec<f32>,
+        peak: Vec<f32>,
+    },
+    /// Loudness of all channels, after every 100 ms of samples.
+    Loudness(Loudness),
+    /// Phase correlation of the first two channels over the last buffer, from
+    /// -1 (inverted) to 1 (identical).
+    Correlation(f32),
+    /// Samples of the last buffer at `CLIP_THRESHOLD` or above, only sent
+    /// when there are any.
+    Clipped(u32),
+    /// Buffers lost since the last report because the process callback fell
+    /// behind or the server marked them corrupted. Sent with the next good
+    /// buffer, or at most ten times a second while none come.
+    Xrun(u32),
+    /// The input went silent, or sounded again, per
+    /// `BackendCommand::SilenceThreshold`.
+    Idle(bool),
+    /// Connecting failed, or the capture thread ended.
+    Error(BackendError),
+}
+
+/// Why there is no connection to PipeWire.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackendError {
+    /// There is no server to connect to.
+    NotRunning,
+    /// The server refused the connection.
+    PermissionDenied,
+    /// Setting up the connection failed otherwise.
+    Connection(String),
+    /// The server reported an error, with its message.
+    Server(String),
+    /// The capture thread stopped.
+    Disconnected,
+}
+
+impl BackendError {
+    /// Reads why `pw_context_connect` failed from the errno it leaves.
+    fn from_connect_error(err: pw::Error) -> Self {
+        match std::io::Error::last_os_error().kind() {
+            std::io::ErrorKind::NotFound | std::io::ErrorKind::ConnectionRefused => {
+                Self::NotRunning
+            }
+            std::io::ErrorKind::PermissionDenied => Self::PermissionDenied,
+            _ => Self::Connection(err.to_string()),
+        }
+    }
+}
+
+impl From<pw::Error> for BackendError {
+    fn from(err: pw::Error) -> Self {
+        Self::Connection(err.to_string())
+    }
+}
+
+impl Display for BackendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotRunning => f.write_str("PipeWire is not running"),
+            Self::PermissionDenied => f.write_str("not allowed to connect to PipeWire"),
+            Self::Connection(err) => write!(f, "cannot connect to PipeWire: {err}"),
+            Self::Server(message) => write!(f, "pipewire error: {message}"),
+            Self::Disconnected => f.write_str("the capture thread stopped"),
+        }
+    }
+}
+
+impl std::error::Error for BackendError {}
+
+/// How often the mainloop picks up the queued `BackendCommand`s.
+const COMMAND_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How the captured samples are analysed, changed by `BackendCommand`s.
+#[derive(Debug, Clone, Copy)]
+struct AnalysisSettings {
+    window: WindowType,
+    db_scale: DbScale,
+    kind: SpectrumKind,
+    overlap: Overlap,
+    fft_size: usize,
+    zero_padding: usize,
+    spectrum_visible: bool,
+    remove_dc: bool,
+    downmix: bool,
+    downmix_law: DownmixLaw,
+    channel_selection: ChannelSelection,
+    /// Linear factor of the input gain.
+    input_gain: f32,
+    /// Linear RMS below which the input is silent.
+    silence_threshold: Option<f32>,
+}
+
+impl AnalysisSettings {
+    fn new(fft_size: usize) -> Self {
+        Self {
+            window: WindowType::default(),
+            db_scale: DbScale::default(),
+            kind: SpectrumKind::default(),
+            overlap: Overlap::default(),
+            fft_size,
+            zero_padding: 1,
+            spectrum_visible: true,
+            remove_dc: true,
+            downmix: false,
+            downmix_law: DownmixLaw::default(),
+            channel_selection: ChannelSelection::default(),
+            input_gain: 1.,
+            silence_threshold: None,
+        }
+    }
+    /// Samples analysed per FFT, the rest of it is zero padding.
+    fn block_len(&self) -> usize {
+        (self.fft_size / self.zero_padding).max(1)
+    }
+    /// Blocks of `block_len` zeros, one per spectrum computed from
+    /// `channels` decoded ones.
+    fn spectrum_blocks(&self, channels: usize) -> Vec<VecDeque<f32>> {
+        let spectra = if self.downmix {
+            1
+        } else {
+            self.channel_selection.shown(channels.max(1))
+        };
+        vec![VecDeque::from(vec![0.; self.block_len()]); spectra]
+    }
+}
+
+/// A planned FFT with its buffers, and what the spectrum needs to know about
+/// the window over the block.
+struct FftSetup {
+    fft: Arc<dyn RealToComplex<f32>>,
+    input: Vec<f32>,
+    output: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+    /// Sum of the window over the analysed block, its coherent gain.
+    window_sum: f32,
+    /// Equivalent noise bandwidth of the window, in bins of the padded FFT.
+    noise_bandwidth: f32,
+}
+
+impl FftSetup {
+    fn new(planner: &mut RealFftPlanner<f32>, settings: &AnalysisSettings) -> Self {
+        let fft = planner.plan_fft_forward(settings.fft_size);
+        let block_len = settings.block_len();
+        let (window_sum, power) = window_sums(settings.window, block_len);
+        Self {
+            input: fft.make_input_vec(),
+            output: fft.make_output_vec(),
+            scratch: fft.make_scratch_vec(),
+            fft,
+            window_sum,
+            // padding interpolates the spectrum, a tone spans that many more
+            // bins
+            noise_bandwidth: block_len as f32 * power / (window_sum * window_sum).max(f32::EPSILON)
+                * settings.zero_padding as f32,
+        }
+    }
+}
+
+/// New settings for the process callback to swap in, with whatever they
+/// needed planned or allocated done beforehand.
+struct Prepared {
+    settings: AnalysisSettings,
+    /// For a new FFT size, padding or window.
+    fft: Option<FftSetup>,
+    /// Fresh blocks to fill, whenever what the spectrum is computed from
+    /// changed.
+    spectrum_data: Option<Vec<VecDeque<f32>>>,
+    reset_loudness: bool,
+}
+
+/// What `Prepared` replaced, sent back to be freed on the mainloop instead of
+/// in the process callback.
+struct Retired {
+    fft: Option<FftSetup>,
+    spectrum_data: Option<Vec<VecDeque<f32>>>,
+}
+
+/// Services the `BackendCommand`s from the mainloop, so planning and
+/// allocating never happens in the realtime process callback.
+struct Controller {
+    commands: StdReceiver<BackendCommand>,
+    settings: AnalysisSettings,
+    // the planner keeps plans around so switching sizes back is cheap
+    planner: RealFftPlanner<f32>,
+    /// Channels decoded per frame, kept up to date on format changes.
+    channels: Rc<Cell<usize>>,
+    prepared: StdSender<Prepared>,
+    retired: StdReceiver<Retired>,
+}
+
+impl Controller {
+    /// Applies every command queued since the last poll, without blocking,
+    /// and hands the result to the process callback.
+    fn poll(&mut self) {
+        // dropped here, off the realtime thread
+        while self.retired.try_recv().is_ok() {}
+        let previous = self.settings;
+        let mut received = false;
+        let mut reset_spectrum = false;
+        let mut reset_loudness = false;
+        while let Ok(command) = self.commands.try_recv() {
+            received = true;
+            let settings = &mut self.settings;
+            match command {
+                BackendCommand::Window(window) => settings.window = window,
+                BackendCommand::DbScale(db_scale) => settings.db_scale = db_scale,
+                BackendCommand::SpectrumKind(kind) => settings.kind = kind,
+                BackendCommand::Overlap(overlap) => settings.overlap = overlap,
+                BackendCommand::FftSize(fft_size) => {
+                    if fft_size > 0 {
+                        settings.fft_size = fft_size;
+                    }
+                }
+                BackendCommand::SpectrumVisible(visible) => settings.spectrum_visible = visible,
+                BackendCommand::RemoveDc(remove_dc) => settings.remove_dc = remove_dc,
+                BackendCommand::Downmix(downmix) => {
+                    settings.downmix = downmix;
+                    reset_spectrum = true;
+                }
+                BackendCommand::DownmixLaw(downmix_law) => settings.downmix_law = downmix_law,
+                BackendCommand::InputGain(db) => {
+                    let db = db.clamp(*INPUT_GAIN_RANGE.start(), *INPUT_GAIN_RANGE.end());
+                    settings.input_gain = 10_f32.powf(db / 20.);
+                }
+                BackendCommand::ZeroPadding(zero_padding) => {
+                    settings.zero_padding = zero_padding.max(1);
+                }
+                BackendCommand::SilenceThreshold(db) => {
+                    settings.silence_threshold = db.map(|db| {
+                        let db = db.clamp(
+                            *SILENCE_THRESHOLD_RANGE.start(),
+                            *SILENCE_THRESHOLD_RANGE.end(),
+                        );
+                        10_f32.powf(db / 20.)
+                    });
+                }
+                BackendCommand::Channels(selection) => {
+                    settings.channel_selection = selection;
+                    reset_spectrum = true;
+                }
+                BackendCommand::ResetLoudness => reset_loudness = true,
+            }
+        }
+        if !received {
+            return;
+        }
+        let settings = self.settings;
+        let resized = settings.fft_size != previous.fft_size
+            || settings.zero_padding != previous.zero_padding;
+        let fft = (resized || settings.window != previous.window)
+            .then(|| FftSetup::new(&mut self.planner, &settings));
+        let spectrum_data =
+            (resized || reset_spectrum).then(|| settings.spectrum_blocks(self.channels.get()));
+        let _ = self.prepared.send(Prepared {
+            settings,
+            fft,
+            spectrum_data,
+            reset_loudness,
+        });
+    }
 }
 
 struct UserData {
     format: spa::param::audio::AudioInfoRaw,
+    target: Option<String>,
     sender: StdSender<PwEvent>,
-    spectrum_data: VecDeque<f32>,
+    /// Settings from the `Controller`, taken at the start of every buffer.
+    prepared: StdReceiver<Prepared>,
+    retired: StdSender<Retired>,
+    /// Channels decoded out of every frame, all of them without a mask or
+    /// when it keeps none the device has.
+    kept_channels: Vec<usize>,
+    channel_mask: Option<ChannelMask>,
+    /// Decoded samples of the current buffer, kept to reuse its allocation.
+    interleaved: Vec<f32>,
+    /// Frames in the last buffer, reported when it changes.
+    quantum: usize,
+    /// Buffers lost since the last `PwEvent::Xrun`, and when that was sent.
+    xruns: u32,
+    xruns_reported: Instant,
+    settings: AnalysisSettings,
+    fft: FftSetup,
+    spectrum_data: Vec<VecDeque<f32>>,
+    /// Samples in a row below `silence_threshold`.
+    silent_samples: usize,
+    idle: bool,
+    samples_since_fft: usize,
+    /// Samples still needed before the block holds only fresh ones, no FFT
+    /// runs until then.
+    filling: usize,
+    onsets: OnsetDetector,
+    loudness: LoudnessMeter,
+}
+
+/// The two ends of the analysis: the `Controller` for the mainloop and the
+/// `UserData` for the stream, set up as `config` asks.
+fn analysis(
+    config: &StreamConfig,
+    sender: StdSender<PwEvent>,
+    commands: StdReceiver<BackendCommand>,
+) -> (Controller, UserData) {
+    let settings = AnalysisSettings::new(config.fft_size);
+    let mut planner = RealFftPlanner::<f32>::new();
+    let fft = FftSetup::new(&mut planner, &settings);
+    let (prepared_sender, prepared) = channel();
+    let (retired_sender, retired) = channel();
+    let controller = Controller {
+        commands,
+        settings,
+        planner,
+        channels: Rc::default(),
+        prepared: prepared_sender,
+        retired,
+    };
+    let data = UserData {
+        format: Default::default(),
+        target: config.target.clone(),
+        sender,
+        prepared,
+        retired: retired_sender,
+        kept_channels: vec![],
+        channel_mask: config.channel_mask,
+        interleaved: vec![],
+        quantum: 0,
+        xruns: 0,
+        xruns_reported: Instant::now(),
+        spectrum_data: settings.spectrum_blocks(1),
+        settings,
+        fft,
+        silent_samples: 0,
+        idle: false,
+        samples_since_fft: 0,
+        filling: 0,
+        onsets: OnsetDetector::default(),
+        loudness: LoudnessMeter::new(0, 0),
+    };
+    (controller, data)
+}
+
+/// Mean frequency of the spectra in `channels`, every bin weighted by its
+/// magnitude, bin `i` being at `i * bin_hz`. Bins at `floor_db` or below
+/// don't count, `None` when that leaves nothing, as in silence.
+///
+/// ```
+/// use wav_viewer::backend::spectral_centroid;
+///
+/// // a tone at bin 10 and one at half its amplitude at bin 30
+/// let mut spectrum = vec![-120.; 64];
+/// spectrum[10] = 0.;
+/// spectrum[30] = 20. * 0.5_f32.log10();
+/// let centroid = spectral_centroid(&[spectrum], 10., -120.).unwrap();
+/// // (10 * 1 + 30 * 0.5) / 1.5 bins
+/// assert!((centroid - 166.67).abs() < 0.01);
+///
+/// assert_eq!(spectral_centroid(&[vec![-120.; 64]], 10., -120.), None);
+/// ```
+pub fn spectral_centroid(channels: &[Vec<f32>], bin_hz: f64, floor_db: f32) -> Option<f64> {
+    let (weighted, total) = channels
+        .iter()
+        .flat_map(|channel| channel.iter().enumerate())
+        .filter(|(_, db)| **db > floor_db)
+        .fold((0., 0.), |(weighted, total), (bin, db)| {
+            let magnitude = 10_f64.powf(*db as f64 / 20.);
+            (weighted + magnitude * bin as f64, total + magnitude)
+        });
+    (total > 0.).then(|| weighted / total * bin_hz)
+}
+
+/// Bins of the single-sided spectrum of a `fft_size` real FFT, DC up to and
+/// including nyquist. The upper half mirrors them and is never computed.
+pub const fn spectrum_bins(fft_size: usize) -> usize {
+    fft_size / 2 + 1
 }
 
-pub fn apply_blackman_harris(block: &mut [f32]) {
+/// Factor folding the negative frequencies of `bin` onto the positive ones.
+/// Interior bins double, DC and nyquist have no mirror image and stay.
+///
+/// A full-scale sine reads 1 at its bin once the magnitude is divided by the
+/// window's sum and multiplied by this:
+///
+/// ```
+/// use std::f32::consts::PI;
+///
+/// use realfft::RealFftPlanner;
+/// use wav_viewer::backend::{WindowType, apply_window, single_sided_gain, spectrum_bins};
+///
+/// let fft_size = 4096;
+/// let bin = 100;
+/// let mut block: Vec<f32> = (0..fft_size)
+///     .map(|i| (2. * PI * bin as f32 * i as f32 / fft_size as f32).sin())
+///     .collect();
+/// let mut window = vec![1.; fft_size];
+/// apply_window(&mut window, WindowType::Hann);
+/// apply_window(&mut block, WindowType::Hann);
+///
+/// let fft = RealFftPlanner::<f32>::new().plan_fft_forward(fft_size);
+/// let mut spectrum = fft.make_output_vec();
+/// fft.process(&mut block, &mut spectrum).unwrap();
+/// assert_eq!(spectrum.len(), spectrum_bins(fft_size));
+///
+/// let sum: f32 = window.iter().sum();
+/// let magnitude = spectrum[bin].norm() / sum * single_sided_gain(bin, fft_size);
+/// assert!((magnitude - 1.).abs() < 1e-2);
+/// assert_eq!(single_sided_gain(0, fft_size), 1.);
+/// assert_eq!(single_sided_gain(fft_size / 2, fft_size), 1.);
+/// ```
+pub fn single_sided_gain(bin: usize, fft_size: usize) -> f32 {
+    if bin == 0 || bin == fft_size / 2 {
+        1.
+    } else {
+        2.
+    }
+}
+
+/// Gain of the window with `coefficients` at sample `i` of a block `n + 1`
+/// long.
+fn window_gain(coefficients: &[f32], i: usize, n: f32) -> f32 {
+    let k = 2.0 * PI * i as f32 / n;
+    coefficients.iter().enumerate().fold(0., |acc, (term, a)| {
+        let sign = if term % 2 == 0 { 1. } else { -1. };
+        (sign * a).mul_add((term as f32 * k).cos(), acc)
+    })
+}
+
+pub fn apply_window(block: &mut [f32], window: WindowType) {
     let n = block.len().saturating_sub(1) as f32;
     if n <= 0.0 {
         return;
     }
 
+    let coefficients = window.coefficients();
     for (i, sample) in block.iter_mut().enumerate() {
-        let k = i as f32 / n;
-        let window = 0.01168f32.mul_add(
-            -(6.0 * PI * k).cos(),
-            0.14128f32.mul_add(
-                (4.0 * PI * k).cos(),
-                0.48829f32.mul_add(-(2.0 * PI * k).cos(), 0.35875),
-            ),
-        );
-        *sample *= window;
+        *sample *= window_gain(coefficients, i, n);
     }
 }
 
+/// Sum of `window` over a block of `len` samples and of its squares, what
+/// `apply_window` does to a block of ones, without allocating one.
+fn window_sums(window: WindowType, len: usize) -> (f32, f32) {
+    let n = len.saturating_sub(1) as f32;
+    // too short for a window, left as is
+    if n <= 0.0 {
+        return (len as f32, len as f32);
+    }
+    let coefficients = window.coefficients();
+    (0..len).fold((0., 0.), |(sum, power), i| {
+        let gain = window_gain(coefficients, i, n);
+        (sum + gain, gain.mul_add(gain, power))
+    })
+}
+
 impl UserData {
-    fn append_spectrum(&mut self, datas: &[f32]) {
-        for data in datas {
-            self.spectrum_data.push_back(*data);
-            self.spectrum_data.pop_front();
+    /// Swaps in what the `Controller` prepared since the last buffer,
+    /// without blocking.
+    fn apply_prepared(&mut self) {
+        while let Ok(prepared) = self.prepared.try_recv() {
+            self.settings = prepared.settings;
+            let retired = Retired {
+                fft: prepared
+                    .fft
+                    .map(|fft| std::mem::replace(&mut self.fft, fft)),
+                spectrum_data: prepared
+                    .spectrum_data
+                    .map(|spectrum_data| std::mem::replace(&mut self.spectrum_data, spectrum_data)),
+            };
+            if retired.spectrum_data.is_some() {
+                self.restart_spectrum();
+            }
+            if prepared.reset_loudness {
+                self.loudness.reset_integrated();
+            }
+            if retired.fft.is_some() || retired.spectrum_data.is_some() {
+                let _ = self.retired.send(retired);
+            }
         }
     }
-    fn send_spectrum(&self) {
-        let mut block: Vec<f32> = self.spectrum_data.iter().copied().collect();
-        let mut planner: RealFftPlanner<f32> = RealFftPlanner::new();
-        let fft = planner.plan_fft_forward(FFT_SIZE);
-        apply_blackman_harris(&mut block);
-        let mut spectrum = fft.make_output_vec();
-        if fft.process(&mut block, &mut spectrum).is_ok() {
-            let data: Vec<f32> = spectrum.iter().map(|v| v.norm()).collect();
-            let _ = self.sender.send(PwEvent::Spectrum(data));
+    /// Counts a lost buffer, only reporting once `XRUN_REPORT_INTERVAL` went
+    /// by since the last report so an overloaded graph doesn't flood the
+    /// channel.
+    fn lost_buffer(&mut self) {
+        self.xruns += 1;
+        if self.xruns_reported.elapsed() >= XRUN_REPORT_INTERVAL {
+            self.report_xruns();
         }
     }
-}
+    /// Sends the buffers lost since the last report, if any.
+    fn report_xruns(&mut self) {
+        if self.xruns > 0 {
+            let _ = self.sender.send(PwEvent::Xrun(self.xruns));
+            self.xruns = 0;
+            self.xruns_reported = Instant::now();
+        }
+    }
+    /// Decodes the first `chunk_size` bytes of `mapped` and passes them to
+    /// everything that measures or shows the samples.
+    fn process_samples(&mut self, mapped: &[u8], chunk_size: usize) {
+        let n_channels = self.format.channels() as usize;
+        if n_channels == 0 {
+            return;
+        }
+        // negotiated in param_changed, normalized to f32 here
+        let frames = decode_chunk(
+            mapped,
+            chunk_size,
+            self.format.format(),
+            n_channels,
+            &self.kept_channels,
+            &mut self.interleaved,
+        );
+        // valid but empty, there's nothing to measure or show and the meters
+        // would average over no samples
+        if frames == 0 {
+            return;
+        }
+        if frames != self.quantum {
+            self.quantum = frames;
+            let _ = self.sender.send(PwEvent::Quantum(frames as u32));
+        }
+        let clipped = self
+            .interleaved
+            .iter()
+            .filter(|sample| sample.abs() >= CLIP_THRESHOLD)
+            .count();
+        if clipped > 0 {
+            let _ = self.sender.send(PwEvent::Clipped(clipped as u32));
+        }
+        // after the clip check, which is about the source itself
+        if self.settings.input_gain != 1. {
+            let gain = self.settings.input_gain;
+            self.interleaved
+                .iter_mut()
+                .for_each(|sample| *sample *= gain);
+        }
+        let matrix = Matrix::from_interleaved_f32(&self.interleaved, self.kept_channels.len());
+        self.update_activity(matrix.channels());
+        self.append_spectrum(matrix.channels());
+        if let Some(loudness) = self.loudness.process(matrix.channels()) {
+            let _ = self.sender.send(PwEvent::Loudness(loudness));
+        }
+        let _ = self.sender.send(levels(matrix.channels()));
+        let _ = self
+            .sender
+            .send(PwEvent::Correlation(correlation(matrix.channels())));
+        let _ = self.sender.send(PwEvent::DataNew(matrix));
+    }
+    /// Starts the analysis over for the format just parsed into `format`,
+    /// telling the `Controller` how many channels are kept.
+    fn format_changed(&mut self, channels: &Cell<usize>) {
+        // settings prepared for the old channel count go in first, the
+        // blocks are sized for the new one right after
+        self.apply_prepared();
+        self.update_kept_channels();
+        channels.set(self.kept_channels.len());
+        self.reset_spectrum();
+        self.loudness = LoudnessMeter::new(self.format.rate(), self.kept_channels.len());
 
-pub fn listen_pw() -> iced::Subscription<PwEvent> {
-    iced::Subscription::run(|| {
-        iced::stream::channel(100, |mut output: Sender<PwEvent>| async move {
-            let (sync_sender, sync_receiver) = channel();
-            std::thread::spawn(move || {
-                connect(sync_sender);
-            });
-            loop {
-                let Ok(data) = sync_receiver.recv() else {
-                    let _ = output.send(PwEvent::PwErr).await;
-                    break;
-                };
-                let _ = output.send(data).await;
+        let _ = self.sender.send(PwEvent::FormatChange(AudioInfo {
+            rate: self.format.rate(),
+            channels: self.kept_channels.len() as u32,
+        }));
+    }
+    /// Works out which channels to decode for the negotiated format.
+    fn update_kept_channels(&mut self) {
+        let channels = self.format.channels() as usize;
+        self.kept_channels = self
+            .channel_mask
+            .map(|mask| mask.channels(channels))
+            .filter(|kept| !kept.is_empty())
+            .unwrap_or_else(|| (0..channels).collect());
+    }
+    /// Reallocates the analysis buffers for the current channel count.
+    fn reset_spectrum(&mut self) {
+        self.spectrum_data = self.settings.spectrum_blocks(self.kept_channels.len());
+        self.restart_spectrum();
+    }
+    /// Waits for the blocks to fill again before the next FFT.
+    fn restart_spectrum(&mut self) {
+        self.samples_since_fft = 0;
+        // the zeros, or samples at another rate, would show up as a bogus
+        // first spectrum
+        self.filling = self.settings.block_len();
+        self.onsets.reset();
+    }
+    /// Goes idle once the loudest channel stayed below the silence threshold
+    /// for `SILENCE_HOLD`, and back as soon as it isn't.
+    fn update_activity(&mut self, channels: &[Vec<f32>]) {
+        let loudest = channels
+            .iter()
+            .map(|channel| rms(channel))
+            .fold(0., f32::max);
+        if self
+            .settings
+            .silence_threshold
+            .is_some_and(|threshold| loudest < threshold)
+        {
+            self.silent_samples += channels.first().map_or(0, Vec::len);
+        } else {
+            self.silent_samples = 0;
+        }
+        let hold = (SILENCE_HOLD.as_secs_f32() * self.format.rate() as f32) as usize;
+        let idle = self.silent_samples > hold;
+        if idle != self.idle {
+            self.idle = idle;
+            let _ = self.sender.send(PwEvent::Idle(idle));
+        }
+    }
+    /// Pushes new samples, running the FFT every `hop` samples.
+    fn append_spectrum(&mut self, channels: &[Vec<f32>]) {
+        let channels = self.settings.channel_selection.apply(channels);
+        let frames = channels.iter().map(Vec::len).min().unwrap_or(0);
+        let downmix_gain = self.settings.downmix_law.gain(channels.len());
+        for frame in 0..frames {
+            if self.settings.downmix {
+                let sum: f32 = channels.iter().map(|channel| channel[frame]).sum();
+                if let Some(samples) = self.spectrum_data.first_mut() {
+                    samples.push_back(sum * downmix_gain);
+                    samples.pop_front();
+                }
+            } else {
+                for (samples, channel) in self.spectrum_data.iter_mut().zip(channels.iter()) {
+                    samples.push_back(channel[frame]);
+                    samples.pop_front();
+                }
             }
-        })
-    })
+            self.samples_since_fft += 1;
+            self.filling = self.filling.saturating_sub(1);
+            // while hidden or idle the count keeps growing, so the first
+            // buffer after that runs the FFT on fresh samples
+            if self.settings.spectrum_visible
+                && !self.idle
+                && self.filling == 0
+                && self.samples_since_fft >= self.settings.overlap.hop(self.settings.block_len())
+            {
+                self.samples_since_fft = 0;
+                let started = Instant::now();
+                self.send_spectrum();
+                let _ = self.sender.send(PwEvent::SpectrumTime(started.elapsed()));
+            }
+        }
+    }
+    fn send_spectrum(&mut self) {
+        let mut channels = Vec::with_capacity(self.spectrum_data.len());
+        // undo the window's gain and fold the negative frequencies onto the
+        // positive ones; the padding adds nothing to the sum, so the block's
+        // window gain still applies
+        let scale = 1. / self.fft.window_sum.max(f32::EPSILON);
+        let block_len = self.settings.block_len();
+        for samples in &self.spectrum_data {
+            let (block, padding) = self
+                .fft
+                .input
+                .split_at_mut(block_len.min(self.settings.fft_size));
+            for (input, data) in block.iter_mut().zip(samples) {
+                *input = *data;
+            }
+            padding.fill(0.);
+            if self.settings.remove_dc {
+                let mean = block.iter().sum::<f32>() / block.len() as f32;
+                for input in block.iter_mut() {
+                    *input -= mean;
+                }
+            }
+            apply_window(block, self.settings.window);
+            if self
+                .fft
+                .fft
+                .process_with_scratch(
+                    &mut self.fft.input,
+                    &mut self.fft.output,
+                    &mut self.fft.scratch,
+                )
+                .is_err()
+            {
+                return;
+            }
+            channels.push(
+                self.fft
+                    .output
+                    .iter()
+                    .enumerate()
+                    .map(|(bin, v)| {
+                        let sides = single_sided_gain(bin, self.settings.fft_size);
+                        self.settings
+                            .db_scale
+                            .magnitude_to_db(v.norm() * scale * sides)
+                    })
+                    .collect(),
+            );
+        }
+        // the pitch and onsets work on magnitudes whatever is shown
+        let pitch = detect_pitch(&channels, self.format.rate(), self.settings.fft_size);
+        let _ = self.sender.send(PwEvent::Pitch(pitch));
+        if self.onsets.process(&channels) {
+            let _ = self.sender.send(PwEvent::Onset);
+        }
+        let bin_width = self.format.rate() as f32 / self.settings.fft_size as f32;
+        // the kinds only differ by a constant; a sine's power is half its
+        // squared peak, except at DC and nyquist which have no phase
+        let (power_offset, density_offset) = match self.settings.kind {
+            SpectrumKind::Magnitude => (0., 0.),
+            SpectrumKind::Power => (-10. * 2_f32.log10(), 0.),
+            SpectrumKind::Psd => (
+                -10. * 2_f32.log10(),
+                -10. * (self.fft.noise_bandwidth * bin_width)
+                    .max(f32::EPSILON)
+                    .log10(),
+            ),
+        };
+        if self.settings.kind != SpectrumKind::Magnitude {
+            for channel in &mut channels {
+                for (bin, db) in channel.iter_mut().enumerate() {
+                    let power = if single_sided_gain(bin, self.settings.fft_size) == 1. {
+                        0.
+                    } else {
+                        power_offset
+                    };
+                    *db = (*db + power + density_offset).max(self.settings.db_scale.floor_db);
+                }
+            }
+        }
+        let _ = self.sender.send(PwEvent::Spectrum(SpectrumFrame {
+            channels,
+            fft_size: self.settings.fft_size,
+            min_db: self.settings.db_scale.floor_db,
+            max_db: self.settings.db_scale.max_db,
+            noise_bandwidth: self.fft.noise_bandwidth,
+            kind: self.settings.kind,
+            bin_width,
+        }));
+    }
+}
+
+/// Captures as set up by `config` on the calling thread, handing every event
+/// to `callback` until it breaks. Reconnects in between, like
+/// [`spawn_capture`].
+///
+/// ```no_run
+/// use std::ops::ControlFlow;
+///
+/// use wav_viewer::backend::{PwEvent, StreamConfig, capture};
+///
+/// capture(StreamConfig::new(), |event| match event {
+///     PwEvent::Levels { rms, .. } => {
+///         println!("{rms:?}");
+///         ControlFlow::Continue(())
+///     }
+///     PwEvent::Error(_) => ControlFlow::Break(()),
+///     _ => ControlFlow::Continue(()),
+/// });
+/// ```
+pub fn capture(config: StreamConfig, mut callback: impl FnMut(PwEvent) -> ControlFlow<()>) {
+    let (events, _guard) = spawn_capture(config);
+    for event in events {
+        if callback(event).is_break() {
+            break;
+        }
+    }
+}
+
+/// Runs the capture on its own thread. It reconnects until the guard is
+/// dropped, the receiver disconnects after that.
+pub fn spawn_capture(config: StreamConfig) -> (StdReceiver<PwEvent>, ShutdownGuard) {
+    let (sender, receiver) = channel();
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let thread = std::thread::spawn({
+        let shutdown = shutdown.clone();
+        move || connect(config, sender, shutdown)
+    });
+    (
+        receiver,
+        ShutdownGuard {
+            shutdown,
+            thread: Some(thread),
+        },
+    )
 }
 
+/// The newest `len` samples of every channel, oldest first.
+///
+/// ```
+/// use wav_viewer::backend::{Matrix, MatrixFixed};
+///
+/// let mut history = MatrixFixed::<f32>::new(3, 2);
+/// history
+///     .append(Matrix::from_channels(vec![vec![0.1, 0.2], vec![-0.1, -0.2]]))
+///     .unwrap();
+///
+/// // padded with silence until full
+/// assert_eq!(history[1], [0., -0.1, -0.2]);
+/// for (channel, samples) in history.iter_channels().enumerate() {
+///     let peak = samples.fold(0_f32, |peak, sample| peak.max(sample.abs()));
+///     assert_eq!(peak, [0.2, 0.2][channel]);
+/// }
+/// ```
 #[derive(Debug, Clone)]
 pub struct MatrixFixed<T = f32>
 where
     T: Clone + Copy + Default,
 {
-    inner: Vec<VecDeque<T>>,
+    /// `2 * len` samples per channel, every sample written twice, `len`
+    /// apart, so the newest `len` always sit next to each other.
+    inner: Vec<T>,
+    /// Where the oldest sample of each channel is.
+    heads: Vec<usize>,
     len: usize,
     channel: usize,
 }
@@ -119,7 +2094,8 @@ where
 {
     pub fn new(len: usize, channel: usize) -> Self {
         Self {
-            inner: vec![vec![Default::default(); len].into(); channel],
+            inner: vec![Default::default(); 2 * len * channel],
+            heads: vec![0; channel],
             len,
             channel,
         }
@@ -130,21 +2106,132 @@ where
     pub fn len(&self) -> usize {
         self.len
     }
-    pub fn data(&self) -> &[VecDeque<T>] {
-        self.inner.as_slice()
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
     }
-    pub fn append(&mut self, matrix: Matrix<T>) {
-        assert_eq!(matrix.channel(), self.channel());
-        let chunks = matrix.chunks(1);
-        for chunk in chunks {
-            for (data, channel_data) in chunk.iter().zip(&mut self.inner) {
-                channel_data.push_back(data[0]);
-                channel_data.pop_front();
-            }
+    /// The samples of every channel, oldest first.
+    pub fn iter_channels(&self) -> impl Iterator<Item = impl Iterator<Item = &T>> {
+        (0..self.channel).map(|channel| self[channel].iter())
+    }
+    /// Changes how many samples are kept, keeping the newest ones. Growing
+    /// pads the front with silence.
+    pub fn resize(&mut self, new_len: usize) {
+        let mut resized = Self::new(new_len, self.channel);
+        for channel in 0..self.channel {
+            resized
+                .push_samples(channel, &self[channel])
+                .expect("both have the same channels");
+        }
+        *self = resized;
+    }
+    /// Appends the samples, dropping as many of the oldest ones. Data with a
+    /// different channel count is rejected, it was captured before a format
+    /// change.
+    pub fn append(&mut self, matrix: Matrix<T>) -> Result<(), ChannelMismatch> {
+        if matrix.channel() != self.channel() {
+            return Err(ChannelMismatch {
+                expected: self.channel(),
+                found: matrix.channel(),
+            });
+        }
+        for (channel, data) in matrix.channels().iter().enumerate() {
+            self.push_samples(channel, data)
+                .expect("the channel count was checked above");
+        }
+        Ok(())
+    }
+    /// Appends samples to a single channel, dropping as many of its oldest
+    /// ones.
+    pub fn push_samples(&mut self, channel: usize, samples: &[T]) -> Result<(), ChannelOutOfRange> {
+        let channels = self.channel();
+        let len = self.len;
+        let head = self
+            .heads
+            .get_mut(channel)
+            .ok_or(ChannelOutOfRange { channel, channels })?;
+        if len == 0 {
+            return Ok(());
+        }
+        // only the newest `len` samples can stay
+        let samples = &samples[samples.len().saturating_sub(len)..];
+        // they replace the oldest ones, up to the end of the ring and then
+        // from its start
+        let (to_end, wrapped) = samples.split_at(samples.len().min(len - *head));
+        let channel_data = &mut self.inner[channel * 2 * len..(channel + 1) * 2 * len];
+        for start in [*head, *head + len] {
+            channel_data[start..start + to_end.len()].copy_from_slice(to_end);
         }
+        for start in [0, len] {
+            channel_data[start..start + wrapped.len()].copy_from_slice(wrapped);
+        }
+        *head = (*head + samples.len()) % len;
+        Ok(())
     }
 }
 
+impl<T> std::ops::Index<usize> for MatrixFixed<T>
+where
+    T: Clone + Copy + Default,
+{
+    type Output = [T];
+
+    /// The samples of `channel`, oldest first.
+    fn index(&self, channel: usize) -> &Self::Output {
+        let start = channel * 2 * self.len + self.heads[channel];
+        &self.inner[start..start + self.len]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelMismatch {
+    pub expected: usize,
+    pub found: usize,
+}
+
+impl Display for ChannelMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "expected {} channels, got {}", self.expected, self.found)
+    }
+}
+
+impl std::error::Error for ChannelMismatch {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelOutOfRange {
+    pub channel: usize,
+    pub channels: usize,
+}
+
+impl Display for ChannelOutOfRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "no channel {} in {} channels",
+            self.channel, self.channels
+        )
+    }
+}
+
+impl std::error::Error for ChannelOutOfRange {}
+
+/// Captured samples, one `Vec` per channel, as carried by
+/// [`PwEvent::DataNew`].
+///
+/// ```
+/// use wav_viewer::backend::Matrix;
+///
+/// let matrix = Matrix::<f32>::from_channels(vec![vec![0.1, 0.2, 0.3], vec![-0.1, -0.2, -0.3]]);
+/// assert_eq!(matrix.channel(), 2);
+/// assert_eq!(matrix.channels()[1], [-0.1, -0.2, -0.3]);
+///
+/// // whole frames, one sample per channel
+/// let frames: Vec<Vec<f32>> = matrix.frames().collect();
+/// assert_eq!(frames[0], [0.1, -0.1]);
+///
+/// // blocks of up to two samples per channel
+/// let chunks: Vec<_> = matrix.chunks(2).collect();
+/// assert_eq!(chunks[1], [&[0.3][..], &[-0.3][..]]);
+/// ```
 #[derive(Debug, Clone)]
 pub struct Matrix<T = f32>
 where
@@ -153,65 +2240,450 @@ where
     inner: Vec<Vec<T>>,
 }
 
-struct MatrixChunks<'a, T>
+/// Blocks of up to `chunk_size` samples of every channel, see
+/// [`Matrix::chunks`].
+pub struct MatrixChunks<'a, T>
 where
     T: Clone + Copy,
 {
     inner: Vec<Chunks<'a, T>>,
 }
 
-impl<'a, T> Iterator for MatrixChunks<'a, T>
-where
-    T: Clone + Copy,
-{
-    type Item = Vec<&'a [T]>;
-    fn next(&mut self) -> Option<Self::Item> {
-        let mut outputs = vec![];
-        for chunk in &mut self.inner {
-            let data = chunk.next()?;
-            outputs.push(data);
-        }
-        Some(outputs)
-    }
+/// Frames of one sample per channel, see [`Matrix::frames`].
+pub struct MatrixFrames<'a, T>
+where
+    T: Clone + Copy,
+{
+    inner: &'a [Vec<T>],
+    frame: usize,
+    len: usize,
+}
+
+impl<T> Iterator for MatrixFrames<'_, T>
+where
+    T: Clone + Copy,
+{
+    type Item = Vec<T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.frame >= self.len {
+            return None;
+        }
+        let frame = self.inner.iter().map(|data| data[self.frame]).collect();
+        self.frame += 1;
+        Some(frame)
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Matrix<T>
+where
+    T: Clone + Copy,
+{
+    type Item = Vec<T>;
+    type IntoIter = MatrixFrames<'a, T>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.frames()
+    }
+}
+
+impl<'a, T> Iterator for MatrixChunks<'a, T>
+where
+    T: Clone + Copy,
+{
+    type Item = Vec<&'a [T]>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut outputs = vec![];
+        for chunk in &mut self.inner {
+            let data = chunk.next()?;
+            outputs.push(data);
+        }
+        Some(outputs)
+    }
+}
+
+impl<T> Matrix<T>
+where
+    T: Clone + Copy,
+{
+    /// Builds a matrix from one `Vec` of samples per channel.
+    pub fn from_channels(inner: Vec<Vec<T>>) -> Self {
+        Self { inner }
+    }
+    /// Number of channels.
+    pub fn channel(&self) -> usize {
+        self.inner.len()
+    }
+    /// The samples of every channel.
+    pub fn channels(&self) -> &[Vec<T>] {
+        &self.inner
+    }
+    /// Number of whole frames, the length of the shortest channel.
+    pub fn frame_count(&self) -> usize {
+        self.inner.iter().map(Vec::len).min().unwrap_or(0)
+    }
+    /// Iterates frames, each holding one sample of every channel.
+    pub fn frames(&self) -> MatrixFrames<'_, T> {
+        MatrixFrames {
+            inner: &self.inner,
+            frame: 0,
+            len: self.frame_count(),
+        }
+    }
+    /// Samples frame by frame, every channel in turn.
+    pub fn interleaved(&self) -> impl Iterator<Item = T> + '_ {
+        (0..self.frame_count())
+            .flat_map(move |frame| self.inner.iter().map(move |data| data[frame]))
+    }
+    /// Splits every channel into blocks of `chunk_size` samples, the last one
+    /// possibly shorter, and yields the blocks of all channels together.
+    pub fn chunks<'a>(&'a self, chunk_size: usize) -> MatrixChunks<'a, T> {
+        let mut chunks = vec![];
+        for data in &self.inner {
+            chunks.push(data.chunks(chunk_size));
+        }
+        MatrixChunks { inner: chunks }
+    }
+}
+
+impl Matrix<f32> {
+    /// Splits frames of `channels` samples into one `Vec` per channel. A
+    /// trailing partial frame is dropped.
+    pub fn from_interleaved_f32(samples: &[f32], channels: usize) -> Self {
+        if channels == 0 {
+            return Self::from_channels(vec![]);
+        }
+        let mut inner = vec![Vec::with_capacity(samples.len() / channels); channels];
+        for frame in samples.chunks_exact(channels) {
+            for (data, sample) in inner.iter_mut().zip(frame) {
+                data.push(*sample);
+            }
+        }
+        Self { inner }
+    }
+    /// Largest absolute sample across all channels.
+    pub fn peak(&self) -> f32 {
+        self.channels()
+            .iter()
+            .flatten()
+            .fold(0., |peak: f32, sample| peak.max(sample.abs()))
+    }
+}
+
+/// Full scale of a 24-bit sample.
+const S24_MAX: f32 = ((1 << 23) - 1) as f32;
+
+/// Normalizes a little endian 24-bit sample. Placed in the top three bytes
+/// of an `i32`, the arithmetic shift back down extends the sign.
+fn s24_to_f32([low, mid, high]: [u8; 3]) -> f32 {
+    (i32::from_le_bytes([0, low, mid, high]) >> 8) as f32 / S24_MAX
+}
+
+/// Bytes per sample of `format`, one of the ones `SampleFormat` asks for.
+fn sample_size(format: AudioFormat) -> usize {
+    match format {
+        AudioFormat::S16LE => 2,
+        AudioFormat::S24LE => 3,
+        _ => 4,
+    }
+}
+
+/// Normalizes one sample of `format`, `sample_size` bytes long, to `-1..=1`.
+/// Anything short decodes as silence.
+fn decode_sample(bytes: &[u8], format: AudioFormat) -> f32 {
+    match format {
+        AudioFormat::S16LE => bytes.try_into().map_or(0., |bytes| {
+            i16::from_le_bytes(bytes) as f32 / i16::MAX as f32
+        }),
+        AudioFormat::S32LE => bytes.try_into().map_or(0., |bytes| {
+            i32::from_le_bytes(bytes) as f32 / i32::MAX as f32
+        }),
+        AudioFormat::S24LE => bytes.try_into().map_or(0., s24_to_f32),
+        // the low three bytes carry the sample
+        AudioFormat::S24_32LE => bytes
+            .get(..3)
+            .and_then(|bytes| bytes.try_into().ok())
+            .map_or(0., s24_to_f32),
+        _ => bytes.try_into().map_or(0., f32::from_le_bytes),
+    }
+}
+
+/// Decodes `channels` out of `bytes` holding frames of `frame_channels`
+/// samples of `format`, into `out` frame by frame, replacing what it held.
+/// A trailing partial frame is dropped.
+///
+/// ```
+/// use pipewire::spa::param::audio::AudioFormat;
+/// use wav_viewer::backend::{ChannelMask, Matrix, decode_frames};
+///
+/// // two frames of 5.1, every sample its frame * 10 + channel
+/// let bytes: Vec<u8> = (0..2)
+///     .flat_map(|frame| (0..6).map(move |channel| (frame * 10 + channel) as f32))
+///     .flat_map(f32::to_le_bytes)
+///     .collect();
+/// let kept = ChannelMask::new([2, 5]).channels(6);
+/// let mut samples = vec![];
+/// decode_frames(&bytes, AudioFormat::F32LE, 6, &kept, &mut samples);
+///
+/// let matrix = Matrix::from_interleaved_f32(&samples, kept.len());
+/// assert_eq!(matrix.channels(), [vec![2., 12.], vec![5., 15.]]);
+/// ```
+pub fn decode_frames(
+    bytes: &[u8],
+    format: AudioFormat,
+    frame_channels: usize,
+    channels: &[usize],
+    out: &mut Vec<f32>,
+) {
+    let sample_size = sample_size(format);
+    out.clear();
+    out.extend(
+        bytes
+            .chunks_exact(sample_size * frame_channels.max(1))
+            .flat_map(|frame| {
+                channels.iter().map(move |channel| {
+                    let start = channel * sample_size;
+                    frame
+                        .get(start..start + sample_size)
+                        .map_or(0., |bytes| decode_sample(bytes, format))
+                })
+            }),
+    );
+}
+
+/// Decodes the whole frames of a buffer whose chunk claims `chunk_size` of
+/// the `mapped` bytes, the way `decode_frames` does, and returns how many
+/// there were. A zero-size chunk, or one without a sample to keep, leaves
+/// `out` empty and gives 0.
+///
+/// ```
+/// use pipewire::spa::param::audio::AudioFormat;
+/// use wav_viewer::backend::decode_chunk;
+///
+/// let mapped: Vec<u8> = [0.5_f32, -0.5, 0.25, -0.25]
+///     .into_iter()
+///     .flat_map(f32::to_le_bytes)
+///     .collect();
+/// let mut samples = vec![1.];
+/// assert_eq!(decode_chunk(&mapped, 0, AudioFormat::F32LE, 2, &[0, 1], &mut samples), 0);
+/// assert!(samples.is_empty());
+/// assert_eq!(decode_chunk(&[], 4096, AudioFormat::F32LE, 2, &[0, 1], &mut samples), 0);
+/// assert_eq!(decode_chunk(&mapped, 16, AudioFormat::F32LE, 2, &[], &mut samples), 0);
+///
+/// // more than was mapped, and a partial frame at the end
+/// assert_eq!(decode_chunk(&mapped, 64, AudioFormat::F32LE, 2, &[0, 1], &mut samples), 2);
+/// assert_eq!(decode_chunk(&mapped, 12, AudioFormat::F32LE, 2, &[0, 1], &mut samples), 1);
+/// assert_eq!(samples, [0.5, -0.5]);
+/// ```
+pub fn decode_chunk(
+    mapped: &[u8],
+    chunk_size: usize,
+    format: AudioFormat,
+    frame_channels: usize,
+    channels: &[usize],
+    out: &mut Vec<f32>,
+) -> usize {
+    out.clear();
+    let frame_size = sample_size(format) * frame_channels;
+    if frame_size == 0 || channels.is_empty() {
+        return 0;
+    }
+    // odd quantum sizes can leave a partial frame at the end, and the chunk
+    // should never claim more than was mapped
+    let frames = chunk_size.min(mapped.len()) / frame_size;
+    decode_frames(
+        &mapped[..frames * frame_size],
+        format,
+        frame_channels,
+        channels,
+        out,
+    );
+    frames
+}
+
+fn rms(channel: &[f32]) -> f32 {
+    let power = channel.iter().map(|sample| sample * sample).sum::<f32>();
+    (power / channel.len().max(1) as f32).sqrt()
+}
+
+fn levels(channels: &[Vec<f32>]) -> PwEvent {
+    let rms = channels.iter().map(|channel| rms(channel)).collect();
+    let peak = channels
+        .iter()
+        .map(|channel| {
+            channel
+                .iter()
+                .fold(0., |peak: f32, sample| peak.max(sample.abs()))
+        })
+        .collect();
+    PwEvent::Levels { rms, peak }
 }
 
-impl<T> Matrix<T>
-where
-    T: Clone + Copy,
-{
-    fn init(inner: Vec<Vec<T>>) -> Self {
-        Self { inner }
+/// Normalized cross-correlation of the first two channels. A single channel
+/// is identical to itself, silence counts as uncorrelated.
+fn correlation(channels: &[Vec<f32>]) -> f32 {
+    let [left, right, ..] = channels else {
+        return 1.;
+    };
+    let (mut cross, mut left_power, mut right_power) = (0., 0., 0.);
+    for (l, r) in left.iter().zip(right) {
+        cross += l * r;
+        left_power += l * l;
+        right_power += r * r;
     }
-    fn channel(&self) -> usize {
-        self.inner.len()
+    let norm = (left_power * right_power).sqrt();
+    if norm > f32::EPSILON {
+        (cross / norm).clamp(-1., 1.)
+    } else {
+        0.
     }
-    fn chunks<'a>(&'a self, chunk_size: usize) -> MatrixChunks<'a, T> {
-        let mut chunks = vec![];
-        for data in &self.inner {
-            chunks.push(data.chunks(chunk_size));
-        }
-        MatrixChunks { inner: chunks }
+}
+
+fn device_info(global: &GlobalObject<&DictRef>) -> Option<DeviceInfo> {
+    if global.type_ != ObjectType::Node {
+        return None;
     }
+    let props = global.props?;
+    let mode = match props.get(*pw::keys::MEDIA_CLASS)? {
+        "Audio/Sink" => CaptureMode::SinkMonitor,
+        "Audio/Source" => CaptureMode::Source,
+        _ => return None,
+    };
+    let name = props.get(*pw::keys::NODE_NAME)?.to_owned();
+    let description = props
+        .get(*pw::keys::NODE_DESCRIPTION)
+        .map_or_else(|| name.clone(), ToOwned::to_owned);
+    Some(DeviceInfo {
+        id: global.id,
+        name,
+        description,
+        mode,
+    })
 }
 
-fn connect(sender: StdSender<PwEvent>) {
-    if connect_inner(sender.clone()).is_err() {
-        let _ = sender.send(PwEvent::PwErr);
+/// Keeps the capture running, rebuilding everything with exponential backoff
+/// whenever the connection fails or is lost, until it is asked to stop or the
+/// GUI goes away.
+fn connect(config: StreamConfig, sender: StdSender<PwEvent>, shutdown: Arc<AtomicBool>) {
+    let mut delay = RECONNECT_DELAY_MIN;
+    while !shutdown.load(Ordering::Relaxed) {
+        match connect_inner(&config, sender.clone(), shutdown.clone()) {
+            // a connection that got to stream resets the backoff
+            Ok(true) => delay = RECONNECT_DELAY_MIN,
+            Ok(false) => {}
+            Err(err) => {
+                let _ = sender.send(PwEvent::Error(err));
+            }
+        }
+        if shutdown.load(Ordering::Relaxed) || sender.send(PwEvent::Reconnecting(delay)).is_err() {
+            break;
+        }
+        // sleep in small steps so a stop request is not held up by the backoff
+        let mut waited = Duration::ZERO;
+        while waited < delay && !shutdown.load(Ordering::Relaxed) {
+            std::thread::sleep(SHUTDOWN_POLL_INTERVAL);
+            waited += SHUTDOWN_POLL_INTERVAL;
+        }
+        delay = (delay * 2).min(RECONNECT_DELAY_MAX);
     }
 }
 
-fn connect_inner(sender: StdSender<PwEvent>) -> Result<(), pw::Error> {
+/// Runs one connection until it fails or is asked to stop, returning whether
+/// it got to stream.
+fn connect_inner(
+    config: &StreamConfig,
+    sender: StdSender<PwEvent>,
+    shutdown: Arc<AtomicBool>,
+) -> Result<bool, BackendError> {
     pw::init();
 
     let mainloop = pw::main_loop::MainLoopRc::new(None)?;
     let context = pw::context::ContextRc::new(&mainloop, None)?;
-    let core = context.connect_rc(None)?;
+    let core = context
+        .connect_rc(None)
+        .map_err(BackendError::from_connect_error)?;
 
-    let data = UserData {
-        format: Default::default(),
-        sender,
-        spectrum_data: VecDeque::from_iter(vec![0_f32; FFT_SIZE].iter().copied()),
-    };
+    // leave the mainloop once a stop was requested
+    let shutdown_timer = mainloop.loop_().add_timer({
+        let mainloop = mainloop.downgrade();
+        let shutdown = shutdown.clone();
+        move |_| {
+            if shutdown.load(Ordering::Relaxed)
+                && let Some(mainloop) = mainloop.upgrade()
+            {
+                mainloop.quit();
+            }
+        }
+    });
+    shutdown_timer
+        .update_timer(Some(SHUTDOWN_POLL_INTERVAL), Some(SHUTDOWN_POLL_INTERVAL))
+        .into_sync_result()
+        .map_err(pw::Error::from)?;
+
+    // the server went away
+    let _core_listener = core
+        .add_listener_local()
+        .error({
+            let mainloop = mainloop.downgrade();
+            let sender = sender.clone();
+            move |id, _seq, _res, message| {
+                let _ = sender.send(PwEvent::Error(BackendError::Server(message.to_owned())));
+                if id == pw::core::PW_ID_CORE
+                    && let Some(mainloop) = mainloop.upgrade()
+                {
+                    mainloop.quit();
+                }
+            }
+        })
+        .register();
+
+    // the GUI sends its settings again on every new handle
+    let (command_sender, commands) = channel();
+    let _ = sender.send(PwEvent::Ready(BackendHandle {
+        sender: command_sender,
+        shutdown,
+    }));
+
+    let registry = core.get_registry_rc()?;
+    let devices: Rc<RefCell<Vec<DeviceInfo>>> = Rc::default();
+    let _registry_listener = registry
+        .add_listener_local()
+        .global({
+            let devices = devices.clone();
+            let sender = sender.clone();
+            move |global| {
+                let Some(device) = device_info(global) else {
+                    return;
+                };
+                let mut devices = devices.borrow_mut();
+                devices.push(device);
+                let _ = sender.send(PwEvent::Devices(devices.clone()));
+            }
+        })
+        .global_remove({
+            let devices = devices.clone();
+            let sender = sender.clone();
+            move |id| {
+                let mut devices = devices.borrow_mut();
+                let len = devices.len();
+                devices.retain(|device| device.id != id);
+                if devices.len() != len {
+                    let _ = sender.send(PwEvent::Devices(devices.clone()));
+                }
+            }
+        })
+        .register();
+
+    let (controller, data) = analysis(config, sender, commands);
+    let channels = controller.channels.clone();
+    // commands are worked out here, the process callback only swaps in the
+    // result
+    let controller = RefCell::new(controller);
+    let command_timer = mainloop
+        .loop_()
+        .add_timer(move |_| controller.borrow_mut().poll());
+    command_timer
+        .update_timer(Some(COMMAND_POLL_INTERVAL), Some(COMMAND_POLL_INTERVAL))
+        .into_sync_result()
+        .map_err(pw::Error::from)?;
 
     /* Create a simple stream, the simple stream manages the core and remote
      * objects for you if you don't need to deal with them.
@@ -224,21 +2696,48 @@ fn connect_inner(sender: StdSender<PwEvent>) -> Result<(), pw::Error> {
      * you need to listen to is the process event where you need to produce
      * the data.
      */
-    let props = properties! {
+    let mut props = properties! {
         *pw::keys::MEDIA_TYPE => "Audio",
         *pw::keys::MEDIA_CATEGORY => "Capture",
         *pw::keys::MEDIA_ROLE => "Music",
-        *pw::keys::STREAM_CAPTURE_SINK => "true",
     };
-
-    // uncomment if you want to capture from the sink monitor ports
-    // props.insert(*pw::keys::STREAM_CAPTURE_SINK, "true");
+    // without it the stream links to a source, the default one if untargeted
+    if config.mode == CaptureMode::SinkMonitor {
+        props.insert(*pw::keys::STREAM_CAPTURE_SINK, "true");
+    }
+    if let Some(node_latency) = config.latency.node_latency() {
+        props.insert(*pw::keys::NODE_LATENCY, node_latency);
+    }
+    if let Some(target) = &config.target {
+        props.insert(*pw::keys::TARGET_OBJECT, target.clone());
+        // error out instead of silently capturing the default sink
+        props.insert("node.dont-fallback", "true");
+    }
 
     let stream = pw::stream::StreamBox::new(&core, "audio-capture", props)?;
 
+    let streamed = Rc::new(Cell::new(false));
     let _listener = stream
         .add_local_listener_with_user_data(data)
-        .param_changed(|_, user_data, id, param| {
+        .state_changed({
+            let mainloop = mainloop.downgrade();
+            let streamed = streamed.clone();
+            move |_, user_data, _old, new| match new {
+                pw::stream::StreamState::Streaming => streamed.set(true),
+                pw::stream::StreamState::Error(_) => {
+                    if let Some(target) = &user_data.target {
+                        let _ = user_data
+                            .sender
+                            .send(PwEvent::TargetNotFound(target.clone()));
+                    }
+                    if let Some(mainloop) = mainloop.upgrade() {
+                        mainloop.quit();
+                    }
+                }
+                _ => {}
+            }
+        })
+        .param_changed(move |_, user_data, id, param| {
             // NULL means to clear the format
             let Some(param) = param else {
                 return;
@@ -262,80 +2761,65 @@ fn connect_inner(sender: StdSender<PwEvent>) -> Result<(), pw::Error> {
                 .format
                 .parse(param)
                 .expect("Failed to parse param changed to AudioInfoRaw");
-
-            let _ = user_data.sender.send(PwEvent::FormatChange(AudioInfo {
-                rate: user_data.format.rate(),
-                channels: user_data.format.channels(),
-            }));
-            println!(
-                "capturing rate:{} channels:{}",
-                user_data.format.rate(),
-                user_data.format.channels()
-            );
+            // reported as `PwEvent::FormatChange`, stdout is left to the binary
+            user_data.format_changed(&channels);
         })
-        .process(|stream, user_data| match stream.dequeue_buffer() {
-            None => println!("out of buffers"),
-            Some(mut buffer) => {
-                let datas = buffer.datas_mut();
-                if datas.is_empty() {
-                    return;
-                }
-
-                let data = &mut datas[0];
-                let n_channels = user_data.format.channels();
-                let n_samples = data.chunk().size() / (mem::size_of::<f32>() as u32);
+        .process(|stream, user_data| {
+            user_data.apply_prepared();
+            match stream.dequeue_buffer() {
+                // the server had nothing queued, we fell behind it
+                None => user_data.lost_buffer(),
+                Some(mut buffer) => {
+                    let datas = buffer.datas_mut();
+                    if datas.is_empty() {
+                        return;
+                    }
 
-                let Some(samples) = data.data() else {
-                    return;
-                };
-                let mut matrix_inner =
-                    vec![vec![0.; (n_samples / n_channels) as usize]; n_channels as usize];
-                for c in 0..n_channels {
-                    for (index, n) in (c..n_samples).step_by(n_channels as usize).enumerate() {
-                        let start = n as usize * mem::size_of::<f32>();
-                        let end = start + mem::size_of::<f32>();
-                        let chan = &samples[start..end];
-                        let f = f32::from_le_bytes(chan.try_into().unwrap());
-                        matrix_inner[c as usize][index] = f;
+                    let data = &mut datas[0];
+                    if data.chunk().flags().contains(ChunkFlags::CORRUPTED) {
+                        user_data.lost_buffer();
+                        return;
+                    }
+                    // caught up, whatever was lost on the way is reported now
+                    user_data.report_xruns();
+                    let chunk_size = data.chunk().size() as usize;
+                    if let Some(samples) = data.data() {
+                        user_data.process_samples(samples, chunk_size);
                     }
                 }
-                user_data.append_spectrum(&matrix_inner[0]);
-                let matrix = Matrix {
-                    inner: matrix_inner,
-                };
-                for data in matrix.chunks(80) {
-                    let data_new: Vec<Vec<f32>> = data
-                        .iter()
-                        .map(|data| data.to_vec())
-                        .collect();
-                    let data_chunk: Matrix<f32> = Matrix::init(data_new);
-                    let _ = user_data.sender.send(PwEvent::DataNew(data_chunk));
-                }
-                user_data.send_spectrum();
             }
         })
         .register()?;
 
-    /* Make one parameter with the supported formats. The SPA_PARAM_EnumFormat
-     * id means that this is a format enumeration (of 1 value).
+    /* Make one parameter per supported format, in order of preference. The
+     * SPA_PARAM_EnumFormat id means that this is a format enumeration.
      * We leave the channels and rate empty to accept the native graph
      * rate and channels. */
-    let mut audio_info = spa::param::audio::AudioInfoRaw::new();
-    audio_info.set_format(spa::param::audio::AudioFormat::F32LE);
-    let obj = pw::spa::pod::Object {
-        type_: pw::spa::utils::SpaTypes::ObjectParamFormat.as_raw(),
-        id: pw::spa::param::ParamType::EnumFormat.as_raw(),
-        properties: audio_info.into(),
-    };
-    let values: Vec<u8> = pw::spa::pod::serialize::PodSerializer::serialize(
-        std::io::Cursor::new(Vec::new()),
-        &pw::spa::pod::Value::Object(obj),
-    )
-    .unwrap()
-    .0
-    .into_inner();
+    let values: Vec<Vec<u8>> = config
+        .formats()
+        .iter()
+        .map(|format| {
+            let mut audio_info = spa::param::audio::AudioInfoRaw::new();
+            audio_info.set_format(format.audio_format());
+            let obj = pw::spa::pod::Object {
+                type_: pw::spa::utils::SpaTypes::ObjectParamFormat.as_raw(),
+                id: pw::spa::param::ParamType::EnumFormat.as_raw(),
+                properties: audio_info.into(),
+            };
+            pw::spa::pod::serialize::PodSerializer::serialize(
+                std::io::Cursor::new(Vec::new()),
+                &pw::spa::pod::Value::Object(obj),
+            )
+            .unwrap()
+            .0
+            .into_inner()
+        })
+        .collect();
 
-    let mut params = [Pod::from_bytes(&values).unwrap()];
+    let mut params: Vec<&Pod> = values
+        .iter()
+        .map(|values| Pod::from_bytes(values).unwrap())
+        .collect();
 
     /* Now connect this stream. We ask that our process function is
      * called in a realtime thread. */
@@ -350,5 +2834,1092 @@ fn connect_inner(sender: StdSender<PwEvent>) -> Result<(), pw::Error> {
 
     // and wait while we let things run
     mainloop.run();
-    Ok(())
+    Ok(streamed.get())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The two ends of the analysis of a stream of `channels` channels of
+    /// f32 at `rate`, with the commands going in and the events coming out.
+    fn stream(
+        rate: u32,
+        channels: u32,
+    ) -> (
+        Controller,
+        UserData,
+        StdSender<BackendCommand>,
+        StdReceiver<PwEvent>,
+    ) {
+        let (sender, events) = channel();
+        let (commands_sender, commands) = channel();
+        let (controller, mut data) = analysis(&StreamConfig::new(), sender, commands);
+        data.format.set_format(AudioFormat::F32LE);
+        data.format.set_rate(rate);
+        data.format.set_channels(channels);
+        data.format_changed(&controller.channels);
+        (controller, data, commands_sender, events)
+    }
+
+    /// Sends `commands` and lets them through to the process side.
+    fn apply(
+        controller: &mut Controller,
+        data: &mut UserData,
+        commands: &StdSender<BackendCommand>,
+        sent: impl IntoIterator<Item = BackendCommand>,
+    ) {
+        for command in sent {
+            commands.send(command).unwrap();
+        }
+        controller.poll();
+        data.apply_prepared();
+    }
+
+    /// The spectra sent since the last call.
+    fn spectra(events: &StdReceiver<PwEvent>) -> Vec<SpectrumFrame> {
+        events
+            .try_iter()
+            .filter_map(|event| match event {
+                PwEvent::Spectrum(frame) => Some(frame),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// `len` samples of a sine of `amplitude` at `frequency` Hz.
+    fn sine(frequency: f32, amplitude: f32, rate: u32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| amplitude * (2. * PI * frequency * i as f32 / rate as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn stopping_ends_the_capture_thread() {
+        // whether or not a server is running, the thread either streams or
+        // waits to reconnect, and both check the flag
+        let (_events, mut guard) = spawn_capture(StreamConfig::new());
+        let handle = BackendHandle {
+            sender: channel().0,
+            shutdown: guard.shutdown.clone(),
+        };
+        handle.stop();
+        let thread = guard.thread.take().expect("the guard owns the thread");
+        let (joined, finished) = channel();
+        std::thread::spawn(move || {
+            let _ = thread.join();
+            let _ = joined.send(());
+        });
+        assert!(finished.recv_timeout(Duration::from_secs(5)).is_ok());
+
+        // dropping the guard stops and joins
+        let (_events, guard) = spawn_capture(StreamConfig::new());
+        let started = Instant::now();
+        drop(guard);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn commands_apply_once_the_controller_prepared_them() {
+        let (mut controller, mut data, commands, _events) = stream(48000, 2);
+        commands
+            .send(BackendCommand::Window(WindowType::Hann))
+            .unwrap();
+        commands.send(BackendCommand::Downmix(true)).unwrap();
+        commands.send(BackendCommand::InputGain(6.)).unwrap();
+        // the process callback never reads the commands itself
+        data.apply_prepared();
+        assert_eq!(data.settings.window, WindowType::default());
+
+        controller.poll();
+        data.apply_prepared();
+        assert_eq!(data.settings.window, WindowType::Hann);
+        assert!((data.fft.window_sum / data.settings.block_len() as f32 - 0.5).abs() < 1e-3);
+        assert_eq!(data.spectrum_data.len(), 1);
+        assert!((data.settings.input_gain - 2.).abs() < 1e-2);
+    }
+
+    #[test]
+    fn silence_reads_the_floor() {
+        let scale = DbScale::default();
+        assert_eq!(scale.magnitude_to_db(0.), scale.floor_db);
+        assert_eq!(scale.magnitude_to_db(f32::NAN), scale.floor_db);
+        assert_eq!(scale.magnitude_to_db(1.), 0.);
+
+        let (mut controller, mut data, commands, events) = stream(48000, 1);
+        apply(
+            &mut controller,
+            &mut data,
+            &commands,
+            [BackendCommand::FftSize(1024)],
+        );
+        for kind in [
+            SpectrumKind::Magnitude,
+            SpectrumKind::Power,
+            SpectrumKind::Psd,
+        ] {
+            apply(
+                &mut controller,
+                &mut data,
+                &commands,
+                [BackendCommand::SpectrumKind(kind)],
+            );
+            data.append_spectrum(&[vec![0.; 1024]]);
+            let spectra = spectra(&events);
+            assert!(!spectra.is_empty());
+            for frame in spectra {
+                assert!(
+                    frame.channels()[0].iter().all(|db| *db == scale.floor_db),
+                    "{kind:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn a440_reads_as_a4() {
+        let pitch = Pitch::from_frequency(440.);
+        assert_eq!(pitch.note, "A4");
+        assert!(pitch.cents.abs() < 1e-3);
+        assert_eq!(Pitch::from_frequency(261.63).note, "C4");
+        // a quarter tone sharp of A4 is still A4
+        let sharp = Pitch::from_frequency(440. * 2_f32.powf(0.4 / 12.));
+        assert_eq!(sharp.note, "A4");
+        assert!((sharp.cents - 40.).abs() < 0.1);
+
+        let (mut controller, mut data, commands, events) = stream(48000, 1);
+        apply(
+            &mut controller,
+            &mut data,
+            &commands,
+            [BackendCommand::Window(WindowType::Hann)],
+        );
+        data.append_spectrum(&[sine(440., 0.5, 48000, DEFAULT_FFT_SIZE)]);
+        let pitches: Vec<Pitch> = events
+            .try_iter()
+            .filter_map(|event| match event {
+                PwEvent::Pitch(pitch) => pitch,
+                _ => None,
+            })
+            .collect();
+        let pitch = pitches.last().expect("a full block was analysed");
+        assert_eq!(pitch.note, "A4");
+        assert!((pitch.freq - 440.).abs() < 1., "{pitch}");
+        assert!(pitch.cents.abs() < 5., "{pitch}");
+
+        assert_eq!(detect_pitch(&[vec![-120.; 513]], 48000, 1024), None);
+    }
+
+    #[test]
+    fn sine_rms_is_its_amplitude_over_root_two() {
+        // whole periods of 100 Hz
+        let channels = [sine(100., 0.8, 48000, 4800), sine(100., 0.25, 48000, 4800)];
+        let PwEvent::Levels { rms, peak } = levels(&channels) else {
+            panic!("levels sends levels");
+        };
+        for ((rms, peak), amplitude) in rms.iter().zip(&peak).zip([0.8, 0.25]) {
+            assert!((rms - amplitude / 2_f32.sqrt()).abs() < 1e-4, "{rms}");
+            assert!((peak - amplitude).abs() < 1e-4, "{peak}");
+        }
+        assert_eq!(super::rms(&[]), 0.);
+        assert_eq!(super::rms(&[-0.5; 10]), 0.5);
+    }
+
+    #[test]
+    fn a_step_in_level_is_an_onset() {
+        let quiet = vec![vec![-60.; 64]];
+        let loud = vec![vec![-6.; 64]];
+        let mut onsets = OnsetDetector::default();
+        // nothing to compare the first spectrum with
+        assert!(!onsets.process(&quiet));
+        for _ in 0..20 {
+            assert!(!onsets.process(&quiet));
+        }
+        assert!(onsets.process(&loud));
+        // staying loud is no new onset, the flux drops back to zero
+        for _ in 0..20 {
+            assert!(!onsets.process(&loud));
+        }
+
+        // end to end, through the FFT
+        let (mut controller, mut data, commands, events) = stream(48000, 1);
+        apply(
+            &mut controller,
+            &mut data,
+            &commands,
+            [BackendCommand::FftSize(1024)],
+        );
+        let count_onsets = |events: &StdReceiver<PwEvent>| {
+            events
+                .try_iter()
+                .filter(|event| matches!(event, PwEvent::Onset))
+                .count()
+        };
+        let steady = sine(1000., 0.01, 48000, 9600);
+        // the first spectra start from silence
+        data.append_spectrum(&[steady[..4800].to_vec()]);
+        let _ = count_onsets(&events);
+        data.append_spectrum(&[steady[4800..].to_vec()]);
+        assert_eq!(count_onsets(&events), 0);
+        data.append_spectrum(&[sine(1000., 0.9, 48000, 2048)]);
+        assert_eq!(count_onsets(&events), 1);
+    }
+
+    #[test]
+    fn full_scale_sine_peaks_at_0_dbfs() {
+        let (mut controller, mut data, commands, events) = stream(48000, 1);
+        for fft_size in [1024, 4096] {
+            for window in WindowType::ALL {
+                apply(
+                    &mut controller,
+                    &mut data,
+                    &commands,
+                    [
+                        BackendCommand::FftSize(fft_size),
+                        BackendCommand::Window(window),
+                    ],
+                );
+                // centred on bin 64
+                let frequency = 64. * 48000. / fft_size as f32;
+                data.append_spectrum(&[sine(frequency, 1., 48000, fft_size)]);
+                let frame = spectra(&events).pop().expect("a full block was analysed");
+                let channel = &frame.channels()[0];
+                let (bin, peak) = channel
+                    .iter()
+                    .enumerate()
+                    .max_by(|a, b| a.1.total_cmp(b.1))
+                    .unwrap();
+                assert_eq!(bin, 64, "{window:?} {fft_size}");
+                assert!(peak.abs() < 0.05, "{window:?} {fft_size}: {peak} dB");
+            }
+        }
+    }
+
+    #[test]
+    fn dc_removal_clears_bin_0() {
+        let (mut controller, mut data, commands, events) = stream(48000, 1);
+        let offset: Vec<f32> = sine(3000., 0.25, 48000, 1024)
+            .into_iter()
+            .map(|sample| sample + 0.5)
+            .collect();
+        for remove_dc in [false, true] {
+            apply(
+                &mut controller,
+                &mut data,
+                &commands,
+                [
+                    BackendCommand::FftSize(1024),
+                    BackendCommand::RemoveDc(remove_dc),
+                ],
+            );
+            data.append_spectrum(std::slice::from_ref(&offset));
+            let frame = spectra(&events).pop().expect("a full block was analysed");
+            let channel = &frame.channels()[0];
+            if remove_dc {
+                assert!(channel[0] < -100., "{} dB", channel[0]);
+            } else {
+                assert!(
+                    (channel[0] - 20. * 0.5_f32.log10()).abs() < 0.1,
+                    "{} dB",
+                    channel[0]
+                );
+            }
+            // the tone is untouched
+            assert!((channel[64] - 20. * 0.25_f32.log10()).abs() < 0.1);
+        }
+    }
+
+    #[test]
+    fn weightings_are_flat_at_1_khz() {
+        for weighting in Weighting::ALL {
+            let offset = weighting.offset_db(1000.);
+            assert!(offset.abs() < 0.01, "{weighting}: {offset} dB");
+        }
+        // IEC 61672 table values
+        for (weighting, freq, expected) in [
+            (Weighting::A, 100., -19.1),
+            (Weighting::A, 10_000., -2.5),
+            (Weighting::C, 100., -0.3),
+            (Weighting::C, 10_000., -4.4),
+        ] {
+            let offset = weighting.offset_db(freq);
+            assert!(
+                (offset - expected).abs() < 0.1,
+                "{weighting} at {freq} Hz: {offset} dB"
+            );
+        }
+    }
+
+    #[test]
+    fn zero_padding_follows_the_padded_size() {
+        // 21.3 bins of a 1024 sample block, off its grid
+        let tone = 21.3 * 48000. / 1024.;
+        let mut errors = vec![];
+        for zero_padding in ZERO_PADDINGS {
+            let (mut controller, mut data, commands, events) = stream(48000, 1);
+            let fft_size = 1024 * zero_padding;
+            apply(
+                &mut controller,
+                &mut data,
+                &commands,
+                [
+                    BackendCommand::FftSize(fft_size),
+                    BackendCommand::ZeroPadding(zero_padding),
+                    BackendCommand::Window(WindowType::Hann),
+                ],
+            );
+            assert_eq!(data.settings.block_len(), 1024);
+            data.append_spectrum(&[sine(tone, 1., 48000, 1024)]);
+            let (mut frames, mut pitch) = (vec![], None);
+            for event in events.try_iter() {
+                match event {
+                    PwEvent::Spectrum(frame) => frames.push(frame),
+                    PwEvent::Pitch(found) => pitch = found,
+                    _ => {}
+                }
+            }
+            assert_eq!(frames.len(), 1, "padded {zero_padding}x");
+            let frame = &frames[0];
+            assert_eq!(frame.fft_size(), fft_size);
+            let channel = &frame.channels()[0];
+            assert_eq!(channel.len(), spectrum_bins(fft_size));
+            let (peak, level) = channel
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.total_cmp(b.1))
+                .unwrap();
+            // the nearest bin of the padded grid
+            let bin_width = 48000. / fft_size as f32;
+            assert!(
+                (peak as f32 * bin_width - tone).abs() <= bin_width / 2.,
+                "padded {zero_padding}x"
+            );
+            // the window gain of the block still scales it, give or take
+            // Hann's scalloping loss
+            assert!(*level < 0.01 && *level > -1.5, "{level} dB");
+            let pitch = pitch.expect("the tone is loud enough for a pitch");
+            errors.push((pitch.freq - tone).abs());
+        }
+        // finer bins, a better interpolated peak
+        assert!(errors[0] > errors[1] && errors[1] > errors[2], "{errors:?}");
+        assert!(errors[3] <= errors[2], "{errors:?}");
+    }
+
+    #[test]
+    fn rate_change_refills_the_blocks() {
+        let (mut controller, mut data, commands, events) = stream(48000, 2);
+        apply(
+            &mut controller,
+            &mut data,
+            &commands,
+            [BackendCommand::FftSize(1024)],
+        );
+        let tone = sine(1000., 0.5, 48000, 1500);
+        data.append_spectrum(&[tone.clone(), tone]);
+        assert!(!spectra(&events).is_empty());
+        assert!(data.spectrum_data[0].iter().any(|sample| *sample != 0.));
+
+        // renegotiated mid-stream
+        data.format.set_rate(44100);
+        data.format_changed(&controller.channels);
+        let formats: Vec<AudioInfo> = events
+            .try_iter()
+            .filter_map(|event| match event {
+                PwEvent::FormatChange(format) => Some(format),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(formats.len(), 1);
+        assert_eq!((formats[0].rate(), formats[0].channels()), (44100, 2));
+        // nothing of the old rate is left to be analysed
+        assert_eq!(data.spectrum_data.len(), 2);
+        for samples in &data.spectrum_data {
+            assert_eq!(samples.len(), 1024);
+            assert!(samples.iter().all(|sample| *sample == 0.));
+        }
+        assert_eq!(data.filling, 1024);
+
+        let tone = sine(1000., 0.5, 44100, 1023);
+        data.append_spectrum(&[tone.clone(), tone]);
+        assert!(spectra(&events).is_empty());
+        data.append_spectrum(&[vec![0.], vec![0.]]);
+        let frames = spectra(&events);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].fft_size(), 1024);
+        // 1 kHz lands in bin 23 at the new rate, 21 at the old one
+        let peak = frames[0].channels()[0]
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(bin, _)| bin);
+        assert_eq!(peak, Some(23));
+    }
+
+    #[test]
+    fn zero_size_buffers_send_nothing() {
+        let (_controller, mut data, _commands, events) = stream(48000, 2);
+        // the format change
+        events.try_iter().for_each(drop);
+        let mapped = vec![0_u8; 64];
+        data.process_samples(&mapped, 0);
+        data.process_samples(&[], 64);
+        // less than a frame
+        data.process_samples(&mapped, 7);
+        assert!(events.try_iter().next().is_none());
+        assert_eq!(data.quantum, 0);
+    }
+
+    #[test]
+    fn lost_buffers_are_reported_together() {
+        let (_controller, mut data, _commands, events) = stream(48000, 2);
+        let xruns = |events: &StdReceiver<PwEvent>| {
+            events
+                .try_iter()
+                .filter_map(|event| match event {
+                    PwEvent::Xrun(count) => Some(count),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        data.xruns_reported = Instant::now();
+        for _ in 0..5 {
+            data.lost_buffer();
+        }
+        assert_eq!(xruns(&events), []);
+        // the next good buffer
+        data.report_xruns();
+        assert_eq!(xruns(&events), [5]);
+        data.report_xruns();
+        assert_eq!(xruns(&events), []);
+
+        // still falling behind an interval later
+        data.lost_buffer();
+        data.xruns_reported = Instant::now() - XRUN_REPORT_INTERVAL;
+        data.lost_buffer();
+        data.lost_buffer();
+        assert_eq!(xruns(&events), [2]);
+    }
+
+    #[test]
+    fn full_scale_samples_are_clipped() {
+        let (mut controller, mut data, commands, events) = stream(48000, 2);
+        let mapped: Vec<u8> = [0.5, -0.5, 1., -0.2, 0.1, -1., 1.5, 0.9989]
+            .iter()
+            .flat_map(|sample: &f32| sample.to_le_bytes())
+            .collect();
+        let clipped = |events: &StdReceiver<PwEvent>| {
+            events
+                .try_iter()
+                .filter_map(|event| match event {
+                    PwEvent::Clipped(clipped) => Some(clipped),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        data.process_samples(&mapped, mapped.len());
+        assert_eq!(clipped(&events), [3]);
+        // only the first frame, under full scale
+        data.process_samples(&mapped, 8);
+        assert_eq!(clipped(&events), []);
+
+        // the gain doesn't make the source clip
+        apply(
+            &mut controller,
+            &mut data,
+            &commands,
+            [BackendCommand::InputGain(12.)],
+        );
+        data.process_samples(&mapped, 8);
+        assert_eq!(clipped(&events), []);
+    }
+
+    #[test]
+    fn input_gain_scales_the_decoded_samples() {
+        let (mut controller, mut data, commands, events) = stream(48000, 2);
+        let mapped: Vec<u8> = [0.25_f32, -0.125, 0.5, 0.]
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect();
+        let decoded = |events: &StdReceiver<PwEvent>| {
+            events
+                .try_iter()
+                .find_map(|event| match event {
+                    PwEvent::DataNew(matrix) => Some(matrix.channels().to_vec()),
+                    _ => None,
+                })
+                .expect("every buffer is sent on")
+        };
+        data.process_samples(&mapped, mapped.len());
+        assert_eq!(decoded(&events), [[0.25, 0.5], [-0.125, 0.]]);
+
+        for (db, gain) in [(-6.0206, 0.5), (12.0412, 4.)] {
+            apply(
+                &mut controller,
+                &mut data,
+                &commands,
+                [BackendCommand::InputGain(db)],
+            );
+            data.process_samples(&mapped, mapped.len());
+            let channels = decoded(&events);
+            for (channel, expected) in channels.iter().flatten().zip([0.25, 0.5, -0.125, 0.]) {
+                assert!((channel - expected * gain).abs() < 1e-4, "{db} dB");
+            }
+        }
+
+        // clamped to the range
+        apply(
+            &mut controller,
+            &mut data,
+            &commands,
+            [BackendCommand::InputGain(100.)],
+        );
+        let top = 10_f32.powf(INPUT_GAIN_RANGE.end() / 20.);
+        assert!((data.settings.input_gain - top).abs() / top < 1e-4);
+    }
+
+    #[test]
+    fn white_noise_has_a_flat_psd() {
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let noise: Vec<f32> = (0..96000).map(|_| rng.random_range(-1_f32..1.)).collect();
+        // uniform noise has a variance of 1/3, spread over 0 to nyquist
+        let expected = 10. * (2. / 3. / 48000_f32).log10();
+        for fft_size in [1024, 4096] {
+            let (mut controller, mut data, commands, events) = stream(48000, 1);
+            apply(
+                &mut controller,
+                &mut data,
+                &commands,
+                [
+                    BackendCommand::FftSize(fft_size),
+                    BackendCommand::SpectrumKind(SpectrumKind::Psd),
+                    BackendCommand::Window(WindowType::Hann),
+                    BackendCommand::Overlap(Overlap::Half),
+                ],
+            );
+            data.append_spectrum(std::slice::from_ref(&noise));
+            let frames = spectra(&events);
+            // averaged by power over every spectrum, leaving out DC and
+            // nyquist
+            let bins = spectrum_bins(fft_size);
+            let mut power = vec![0_f32; bins];
+            for frame in &frames {
+                for (power, db) in power.iter_mut().zip(&frame.channels()[0]) {
+                    *power += 10_f32.powf(db / 10.) / frames.len() as f32;
+                }
+            }
+            let bands: Vec<f32> = power[1..bins - 1]
+                .chunks(bins / 8)
+                .map(|band| 10. * (band.iter().sum::<f32>() / band.len() as f32).log10())
+                .collect();
+            for level in bands {
+                assert!(
+                    (level - expected).abs() < 0.5,
+                    "{fft_size}: {level} dB, not {expected}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn silence_runs_no_ffts() {
+        let (mut controller, mut data, commands, events) = stream(48000, 1);
+        apply(
+            &mut controller,
+            &mut data,
+            &commands,
+            [
+                BackendCommand::FftSize(1024),
+                BackendCommand::SilenceThreshold(Some(-60.)),
+            ],
+        );
+        // 10 ms of f32 zeros
+        let silence = vec![0_u8; 480 * 4];
+        let analysed = |events: &StdReceiver<PwEvent>| {
+            let (mut spectra, mut idle) = (0, None);
+            for event in events.try_iter() {
+                match event {
+                    PwEvent::Spectrum(_) | PwEvent::SpectrumTime(_) => spectra += 1,
+                    PwEvent::Idle(now) => idle = Some(now),
+                    _ => {}
+                }
+            }
+            (spectra, idle)
+        };
+        // analysed as usual until the hold runs out
+        for _ in 0..100 {
+            data.process_samples(&silence, silence.len());
+        }
+        let (spectra, idle) = analysed(&events);
+        assert!(spectra > 0);
+        assert_eq!(idle, None);
+        data.process_samples(&silence, silence.len());
+        assert_eq!(analysed(&events), (0, Some(true)));
+        for _ in 0..100 {
+            data.process_samples(&silence, silence.len());
+        }
+        assert_eq!(analysed(&events), (0, None));
+        assert!(data.idle);
+
+        // a quiet tone above the threshold wakes it up
+        let tone: Vec<u8> = sine(1000., 0.01, 48000, 1024)
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect();
+        data.process_samples(&tone, tone.len());
+        let (spectra, idle) = analysed(&events);
+        assert!(spectra > 0);
+        assert_eq!(idle, Some(false));
+    }
+
+    #[test]
+    fn a_tone_lands_in_its_band() {
+        let (mut controller, mut data, commands, events) = stream(48000, 1);
+        for window in [WindowType::Hann, WindowType::BlackmanHarris] {
+            apply(
+                &mut controller,
+                &mut data,
+                &commands,
+                [
+                    BackendCommand::FftSize(4096),
+                    BackendCommand::Window(window),
+                ],
+            );
+            // between bins
+            data.append_spectrum(&[sine(1000., 0.5, 48000, 4096)]);
+            let frame = spectra(&events).pop().expect("a full block was analysed");
+            // the octave bands around 500 Hz, 1 kHz and 2 kHz
+            let octave = |center: f64| {
+                frame
+                    .band_level(0, 48000, center / 2_f64.sqrt(), center * 2_f64.sqrt())
+                    .unwrap()
+            };
+            let level = octave(1000.);
+            // the sine's own level, wherever its leakage falls
+            assert!(
+                (level - 20. * 0.5_f32.log10()).abs() < 0.2,
+                "{window:?}: {level} dB"
+            );
+            assert!(octave(500.) < level - 40., "{window:?}");
+            assert!(octave(2000.) < level - 40., "{window:?}");
+        }
+        // narrower than a bin
+        let frame = SpectrumFrame::new(vec![vec![0.; 2049]], 4096, 48000, DbScale::default());
+        assert_eq!(frame.band_level(0, 48000, 1000., 1001.), None);
+        assert_eq!(frame.band_level(1, 48000, 0., 24000.), None);
+    }
+
+    #[test]
+    fn right_only_tone_survives_the_downmix() {
+        // bin 64, full scale, in the right channel only
+        let tone = sine(64. * 48000. / 1024., 1., 48000, 1024);
+        let mapped: Vec<u8> = tone
+            .iter()
+            .flat_map(|right| [0., *right])
+            .flat_map(f32::to_le_bytes)
+            .collect();
+        for (law, expected) in [
+            (DownmixLaw::Average, -6.02),
+            (DownmixLaw::EqualPower, -3.01),
+        ] {
+            let (mut controller, mut data, commands, events) = stream(48000, 2);
+            apply(
+                &mut controller,
+                &mut data,
+                &commands,
+                [
+                    BackendCommand::FftSize(1024),
+                    BackendCommand::Window(WindowType::Hann),
+                    BackendCommand::Downmix(true),
+                    BackendCommand::DownmixLaw(law),
+                ],
+            );
+            data.process_samples(&mapped, mapped.len());
+            let frame = spectra(&events).pop().expect("a full block was analysed");
+            assert_eq!(frame.channels().len(), 1);
+            let level = frame.channels()[0][64];
+            assert!((level - expected).abs() < 0.1, "{law}: {level} dB");
+        }
+    }
+
+    #[test]
+    fn correlation_of_matching_and_inverted_channels() {
+        let left: Vec<f32> = (0..480).map(|i| (i as f32 * 0.1).sin()).collect();
+        let inverted: Vec<f32> = left.iter().map(|sample| -sample).collect();
+        let halved: Vec<f32> = left.iter().map(|sample| sample * 0.5).collect();
+        assert!((correlation(&[left.clone(), left.clone()]) - 1.).abs() < 1e-5);
+        // the level doesn't matter, only the shape
+        assert!((correlation(&[left.clone(), halved]) - 1.).abs() < 1e-5);
+        assert!((correlation(&[left.clone(), inverted]) + 1.).abs() < 1e-5);
+        assert_eq!(correlation(&[vec![0.; 480], vec![0.; 480]]), 0.);
+        assert_eq!(correlation(&[left.clone(), vec![0.; 480]]), 0.);
+        assert_eq!(correlation(&[left]), 1.);
+    }
+
+    #[test]
+    fn one_spectrum_per_hop_once_the_block_is_full() {
+        let frames = 10_000;
+        for overlap in Overlap::ALL {
+            let (mut controller, mut data, commands, events) = stream(48000, 2);
+            apply(
+                &mut controller,
+                &mut data,
+                &commands,
+                [
+                    BackendCommand::FftSize(1024),
+                    BackendCommand::Overlap(overlap),
+                ],
+            );
+            let hop = overlap.hop(1024);
+            // buffers that don't line up with the hop
+            for start in (0..frames).step_by(333) {
+                let len = 333.min(frames - start);
+                data.append_spectrum(&[vec![0.1; len], vec![-0.1; len]]);
+            }
+            let expected = 1 + (frames - 1024) / hop;
+            assert_eq!(spectra(&events).len(), expected, "{overlap}");
+        }
+    }
+
+    #[test]
+    fn s16_samples_span_full_scale() {
+        let decode = |sample: i16| decode_sample(&sample.to_le_bytes(), AudioFormat::S16LE);
+        assert!((decode(i16::MIN) + 1.).abs() < 1e-4);
+        assert_eq!(decode(0), 0.);
+        assert_eq!(decode(i16::MAX), 1.);
+    }
+
+    #[test]
+    fn every_format_decodes_to_the_same_scale() {
+        let cases: [(AudioFormat, &[u8], f32); 8] = [
+            (AudioFormat::F32LE, &0.25_f32.to_le_bytes(), 0.25),
+            (AudioFormat::F32LE, &(-1_f32).to_le_bytes(), -1.),
+            (
+                AudioFormat::S16LE,
+                &[0x00, 0x40],
+                0x4000 as f32 / i16::MAX as f32,
+            ),
+            (
+                AudioFormat::S16LE,
+                &[0x00, 0xc0],
+                -0x4000 as f32 / i16::MAX as f32,
+            ),
+            (AudioFormat::S32LE, &[0x00, 0x00, 0x00, 0x40], 0.5),
+            (AudioFormat::S32LE, &[0x00, 0x00, 0x00, 0xc0], -0.5),
+            (AudioFormat::S24LE, &[0x00, 0x00, 0x40], 0.5),
+            (AudioFormat::S24_32LE, &[0x00, 0x00, 0xc0, 0xff], -0.5),
+        ];
+        for (format, bytes, expected) in cases {
+            assert_eq!(bytes.len(), sample_size(format));
+            let decoded = decode_sample(bytes, format);
+            assert!(
+                (decoded - expected).abs() < 1e-6,
+                "{format:?} {bytes:?}: {decoded}"
+            );
+        }
+        // too short for the format
+        assert_eq!(decode_sample(&[0xff], AudioFormat::S16LE), 0.);
+    }
+
+    #[test]
+    fn s24_samples_decode_with_their_sign() {
+        for format in [AudioFormat::S24LE, AudioFormat::S24_32LE] {
+            let decode = |bytes: [u8; 3]| {
+                // whatever sits in the padding byte is ignored
+                let padded = [bytes[0], bytes[1], bytes[2], 0xa5];
+                decode_sample(&padded[..sample_size(format)], format)
+            };
+            assert_eq!(decode([0, 0, 0]), 0.);
+            // 0x400000, half scale
+            assert!((decode([0x00, 0x00, 0x40]) - 0.5).abs() < 1e-6);
+            assert_eq!(decode([0x01, 0x00, 0x00]), 1. / S24_MAX);
+            // 0xc00000 is -0x400000 once the sign is extended
+            assert!((decode([0x00, 0x00, 0xc0]) + 0.5).abs() < 1e-6);
+            assert_eq!(decode([0xff, 0xff, 0xff]), -1. / S24_MAX);
+            assert_eq!(decode([0xff, 0xff, 0x7f]), 1.);
+            assert!((decode([0x00, 0x00, 0x80]) + 1.).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn stream_config_builder_sets_each_field() {
+        let defaults = StreamConfig::new();
+        assert_eq!(defaults, StreamConfig::default());
+        assert_eq!(defaults.mode, CaptureMode::SinkMonitor);
+        assert_eq!(defaults.target, None);
+        assert_eq!(defaults.latency, Latency::Default);
+        assert_eq!(defaults.fft_size, DEFAULT_FFT_SIZE);
+        assert_eq!(defaults.channel_mask, None);
+        assert_eq!(defaults.formats(), SampleFormat::ALL);
+
+        let config = StreamConfig::new()
+            .capture_mode(CaptureMode::Source)
+            .target("alsa_input.usb-mic")
+            .latency(Latency::Frames(256))
+            .format(SampleFormat::S16)
+            .fft_size(4096)
+            .channel_mask(ChannelMask::new([1]));
+        assert_eq!(config.mode, CaptureMode::Source);
+        assert_eq!(config.target.as_deref(), Some("alsa_input.usb-mic"));
+        assert_eq!(config.latency.node_latency().as_deref(), Some("256/48000"));
+        assert_eq!(config.formats(), [SampleFormat::S16]);
+        assert_eq!(config.fft_size, 4096);
+        // only the offered sizes
+        assert_eq!(config.clone().fft_size(0).fft_size, 4096);
+        assert_eq!(config.clone().fft_size(3000).fft_size, 4096);
+        assert_eq!(config.channel_mask, Some(ChannelMask::new([1])));
+        assert_ne!(config, defaults);
+    }
+
+    #[test]
+    fn matrix_accessors_follow_the_channels() {
+        let matrix = Matrix::from_channels(vec![vec![1, 2, 3], vec![-1, -2, -3, -4]]);
+        assert_eq!(matrix.channel(), 2);
+        assert_eq!(matrix.channels()[1], [-1, -2, -3, -4]);
+        // the shortest channel bounds the frames
+        assert_eq!(matrix.frame_count(), 3);
+        let frames: Vec<Vec<i32>> = matrix.frames().collect();
+        assert_eq!(frames, [[1, -1], [2, -2], [3, -3]]);
+        assert_eq!((&matrix).into_iter().count(), 3);
+        assert_eq!(
+            matrix.interleaved().collect::<Vec<_>>(),
+            [1, -1, 2, -2, 3, -3]
+        );
+        let chunks: Vec<_> = matrix.chunks(2).collect();
+        assert_eq!(
+            chunks,
+            [[&[1, 2][..], &[-1, -2][..]], [&[3][..], &[-3, -4][..]]]
+        );
+
+        let empty = Matrix::<f32>::from_channels(vec![]);
+        assert_eq!(empty.frame_count(), 0);
+        assert_eq!(empty.frames().count(), 0);
+    }
+
+    #[test]
+    fn interleaved_samples_split_into_channels() {
+        let samples = [0.1, -0.1, 0.2, -0.2, 0.3];
+        let matrix = Matrix::from_interleaved_f32(&samples, 2);
+        // the lone 0.3 is half a frame
+        assert_eq!(matrix.channels(), [vec![0.1, 0.2], vec![-0.1, -0.2]]);
+        assert_eq!(matrix.interleaved().collect::<Vec<_>>(), samples[..4]);
+        assert_eq!(matrix.peak(), 0.2);
+
+        let mono = Matrix::from_interleaved_f32(&samples, 1);
+        assert_eq!(mono.channels(), [samples]);
+        assert_eq!(Matrix::from_interleaved_f32(&samples, 0).channel(), 0);
+        assert_eq!(
+            Matrix::from_interleaved_f32(&[], 2).channels(),
+            [vec![], vec![]]
+        );
+    }
+
+    #[test]
+    fn matrix_fixed_pads_until_full() {
+        let mut history = MatrixFixed::<f32>::new(3, 2);
+        assert_eq!(history.channel(), 2);
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[0], [0.; 3]);
+        history
+            .append(Matrix::from_channels(vec![
+                vec![0.1, 0.2],
+                vec![-0.1, -0.2],
+            ]))
+            .unwrap();
+        assert_eq!(history[0], [0., 0.1, 0.2]);
+        assert_eq!(history[1], [0., -0.1, -0.2]);
+        history
+            .append(Matrix::from_channels(vec![
+                vec![0.3, 0.4],
+                vec![-0.3, -0.4],
+            ]))
+            .unwrap();
+        assert_eq!(history[0], [0.2, 0.3, 0.4]);
+        assert_eq!(history[1], [-0.2, -0.3, -0.4]);
+        assert!(MatrixFixed::<f32>::new(0, 1).is_empty());
+    }
+
+    #[test]
+    fn matrix_fixed_channels_iterate_like_their_index() {
+        let mut history = MatrixFixed::<i32>::new(4, 3);
+        // wraps the ring of every channel at least once
+        for start in (0..10).step_by(3) {
+            let chunk = (0..3)
+                .map(|channel| (start..start + 3).map(|i| i * 10 + channel).collect())
+                .collect();
+            history.append(Matrix::from_channels(chunk)).unwrap();
+        }
+        let channels: Vec<Vec<i32>> = history
+            .iter_channels()
+            .map(|samples| samples.copied().collect())
+            .collect();
+        assert_eq!(channels.len(), 3);
+        for (channel, samples) in channels.iter().enumerate() {
+            assert_eq!(*samples, history[channel]);
+            let expected: Vec<i32> = (8..12).map(|i| i * 10 + channel as i32).collect();
+            assert_eq!(*samples, expected);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn matrix_fixed_index_past_the_channels_panics() {
+        let history = MatrixFixed::<f32>::new(4, 2);
+        let _ = &history[2];
+    }
+
+    #[test]
+    fn matrix_fixed_rejects_other_channel_counts() {
+        let mut history = MatrixFixed::<f32>::new(4, 2);
+        history
+            .append(Matrix::from_channels(vec![vec![0.5], vec![-0.5]]))
+            .unwrap();
+        let error = history
+            .append(Matrix::from_channels(vec![vec![1.; 3]; 3]))
+            .unwrap_err();
+        assert_eq!(
+            error,
+            ChannelMismatch {
+                expected: 2,
+                found: 3
+            }
+        );
+        assert_eq!(error.to_string(), "expected 2 channels, got 3");
+        // nothing of the rejected data went in
+        assert_eq!(history[0], [0., 0., 0., 0.5]);
+        assert_eq!(history[1], [0., 0., 0., -0.5]);
+        assert!(history.append(Matrix::from_channels(vec![])).is_err());
+    }
+
+    #[test]
+    fn matrix_fixed_resizes_keeping_the_newest() {
+        let mut history = MatrixFixed::<i32>::new(5, 2);
+        // the ring has wrapped before resizing
+        history
+            .append(Matrix::from_channels(vec![
+                (1..=7).collect(),
+                (-7..=-1).rev().collect(),
+            ]))
+            .unwrap();
+        assert_eq!(history[0], [3, 4, 5, 6, 7]);
+
+        history.resize(3);
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[0], [5, 6, 7]);
+        assert_eq!(history[1], [-5, -6, -7]);
+
+        history.resize(6);
+        assert_eq!(history.len(), 6);
+        assert_eq!(history[0], [0, 0, 0, 5, 6, 7]);
+        history
+            .append(Matrix::from_channels(vec![vec![8], vec![-8]]))
+            .unwrap();
+        assert_eq!(history[0], [0, 0, 5, 6, 7, 8]);
+        assert_eq!(history[1], [0, 0, -5, -6, -7, -8]);
+
+        history.resize(0);
+        assert!(history.is_empty());
+        assert_eq!(history[0], []);
+    }
+
+    #[test]
+    fn pushing_past_the_length_keeps_the_last_samples() {
+        let mut history = MatrixFixed::<i32>::new(4, 2);
+        history.push_samples(1, &[1, 2]).unwrap();
+        history
+            .push_samples(1, &(3..=13).collect::<Vec<_>>())
+            .unwrap();
+        assert_eq!(history[1], [10, 11, 12, 13]);
+        // the other channel is untouched
+        assert_eq!(history[0], [0; 4]);
+        history.push_samples(1, &[14]).unwrap();
+        assert_eq!(history[1], [11, 12, 13, 14]);
+        assert_eq!(
+            history.push_samples(2, &[1]),
+            Err(ChannelOutOfRange {
+                channel: 2,
+                channels: 2
+            })
+        );
+    }
+
+    #[test]
+    fn chunked_appends_match_a_whole_buffer() {
+        let channels: Vec<Vec<f32>> = (0..3)
+            .map(|channel| (0..1000).map(|i| (i * 3 + channel) as f32).collect())
+            .collect();
+        let matrix = Matrix::from_channels(channels);
+
+        let mut whole = MatrixFixed::new(300, 3);
+        whole.append(matrix.clone()).unwrap();
+        for chunk_size in [1, 80, 299, 300, 301, 1000] {
+            let mut chunked = MatrixFixed::new(300, 3);
+            for chunk in matrix.chunks(chunk_size) {
+                let chunk = chunk.into_iter().map(<[f32]>::to_vec).collect();
+                chunked.append(Matrix::from_channels(chunk)).unwrap();
+            }
+            for channel in 0..3 {
+                assert_eq!(chunked[channel], whole[channel], "chunks of {chunk_size}");
+            }
+        }
+        assert_eq!(whole[2][0], (700 * 3 + 2) as f32);
+    }
+
+    #[test]
+    fn fft_size_change_resizes_the_blocks() {
+        let (mut controller, mut data, commands, _events) = stream(48000, 2);
+        data.append_spectrum(&[vec![0.5; 1000], vec![-0.5; 1000]]);
+        for fft_size in [2048, 16384, 1024] {
+            commands.send(BackendCommand::FftSize(fft_size)).unwrap();
+            controller.poll();
+            data.apply_prepared();
+            assert_eq!(data.settings.fft_size, fft_size);
+            assert_eq!(data.fft.input.len(), fft_size);
+            assert_eq!(data.fft.output.len(), spectrum_bins(fft_size));
+            assert_eq!(data.spectrum_data.len(), 2);
+            for samples in &data.spectrum_data {
+                // prefilled with silence, nothing left of the old size
+                assert_eq!(samples.len(), fft_size);
+                assert!(samples.iter().all(|sample| *sample == 0.));
+            }
+            assert_eq!(data.filling, fft_size);
+        }
+        // the replaced buffers went back to be freed
+        controller.poll();
+        assert!(controller.retired.try_recv().is_err());
+
+        // half of the block is padding
+        commands.send(BackendCommand::ZeroPadding(2)).unwrap();
+        controller.poll();
+        data.apply_prepared();
+        assert_eq!(data.fft.input.len(), 1024);
+        assert_eq!(data.spectrum_data[0].len(), 512);
+    }
+
+    #[test]
+    fn windows_sum_to_their_coefficients_over_dc() {
+        // odd, so a sample sits right in the middle
+        let len = 4097;
+        for window in WindowType::ALL {
+            let mut block = vec![1.; len];
+            apply_window(&mut block, window);
+            // every term peaks in the middle, together they make the window
+            // reach 1 there
+            let total: f32 = window.coefficients().iter().sum();
+            assert!((total - 1.).abs() < 1e-6, "{window}");
+            assert!((block[len / 2] - total).abs() < 1e-5, "{window}");
+            // the cosines cancel over the block, leaving a0 on average
+            let sum: f32 = block.iter().sum();
+            assert!(
+                (sum / len as f32 - window.coherent_gain()).abs() < 1e-3,
+                "{window}"
+            );
+
+            let power: f32 = block.iter().map(|gain| gain * gain).sum();
+            let (window_sum, window_power) = window_sums(window, len);
+            assert!((window_sum - sum).abs() < 1e-2, "{window}");
+            assert!((window_power - power).abs() < 1e-2, "{window}");
+        }
+    }
 }
@@ -0,0 +1,79 @@
+use std::io::{self, Write};
+
+use crate::args::Args;
+use wav_viewer::backend::{self, BackendCommand, PwEvent};
+
+/// Everything `--events` accepts, by the key the event is printed under.
+pub const EVENTS: &[&str] = &[
+    "format",
+    "spectrum",
+    "levels",
+    "pitch",
+    "onset",
+    "correlation",
+    "loudness",
+    "clipped",
+    "idle",
+    "xrun",
+    "quantum",
+    "devices",
+    "error",
+];
+/// Printed when `--events` is not given.
+const DEFAULT_EVENTS: &[&str] = &["format", "spectrum", "levels"];
+
+/// Key of `event` in the JSON, `None` for the ones never printed.
+fn event_name(event: &PwEvent) -> Option<&'static str> {
+    Some(match event {
+        PwEvent::FormatChange(_) => "format",
+        PwEvent::Spectrum(_) => "spectrum",
+        PwEvent::Levels { .. } => "levels",
+        PwEvent::Pitch(_) => "pitch",
+        PwEvent::Onset => "onset",
+        PwEvent::Correlation(_) => "correlation",
+        PwEvent::Loudness(_) => "loudness",
+        PwEvent::Clipped(_) => "clipped",
+        PwEvent::Idle(_) => "idle",
+        PwEvent::Xrun(_) => "xrun",
+        PwEvent::Quantum(_) => "quantum",
+        PwEvent::Devices(_) => "devices",
+        PwEvent::Error(_) => "error",
+        _ => return None,
+    })
+}
+
+/// Captures without a window, printing the chosen events to stdout as
+/// newline-delimited JSON until the reader goes away.
+pub fn run(args: &Args) -> io::Result<()> {
+    let wanted = args.events.as_deref().unwrap_or(DEFAULT_EVENTS);
+    // only these need the FFT to run
+    let spectrum = ["spectrum", "pitch", "onset"]
+        .iter()
+        .any(|name| wanted.contains(name));
+    let (events, _guard) = backend::spawn_capture(args.stream_config());
+    let mut stdout = io::stdout().lock();
+    for event in events {
+        if let PwEvent::Ready(backend) = &event {
+            if let Some(window) = args.window {
+                backend.send(BackendCommand::Window(window));
+            }
+            backend.send(BackendCommand::SpectrumVisible(spectrum));
+            backend.send(BackendCommand::SilenceThreshold(args.idle_below));
+            continue;
+        }
+        if !event_name(&event).is_some_and(|name| wanted.contains(&name)) {
+            continue;
+        }
+        let written = serde_json::to_writer(&mut stdout, &event)
+            .map_err(io::Error::from)
+            .and_then(|()| writeln!(stdout))
+            .and_then(|()| stdout.flush());
+        match written {
+            Ok(()) => {}
+            // the consumer quit, that's the normal way to stop
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
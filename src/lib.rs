@@ -0,0 +1,6 @@
+//! Capture and analysis of PipeWire audio, free of any UI toolkit.
+//!
+//! [`backend::spawn_capture`] runs a capture on its own thread and hands its
+//! [`backend::PwEvent`]s out over a channel, [`backend::capture`] passes them
+//! to a closure instead. The `wav_viewer` binary draws them with iced.
+pub mod backend;
@@ -6,29 +6,107 @@
 //! Inspired by the example found in the MDN docs[1].
 //!
 //! [1]: https://developer.mozilla.org/en-US/docs/Web/API/Canvas_API/Tutorial/Basic_animations#An_animated_solar_system
-mod backend;
+mod args;
+mod headless;
+mod mel;
+mod settings;
+mod subscription;
+mod tui;
+mod wav;
 
+use std::collections::VecDeque;
 use std::fmt::Display;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
 
+use iced::keyboard;
 use iced::mouse;
-use iced::widget::canvas::{Geometry, Path, Stroke, stroke};
-use iced::widget::{canvas, column, pick_list};
+use iced::widget::canvas::{Geometry, Path, Stroke, gradient, stroke};
+use iced::widget::{
+    Row, button, canvas, checkbox, column, image, pick_list, row, slider, text, text_input, toggler,
+};
 use iced::window;
-use iced::{Color, Element, Fill, Point, Rectangle, Renderer, Subscription, Theme};
+use iced::{Color, Element, Fill, Point, Rectangle, Renderer, Subscription, Theme, Vector};
+use serde::{Deserialize, Serialize};
 
-use crate::backend::{FFT_SIZE, MIN_FREQ, Matrix, MatrixFixed, POINTS_PER_OCTAVE, PwEvent};
+use crate::args::{Args, ArgsError};
+use crate::mel::MelFilterbank;
+use crate::settings::Settings;
+use crate::wav::WavWriter;
+use wav_viewer::backend::{
+    AudioInfo, BackendCommand, BackendError, BackendHandle, CLIP_THRESHOLD, CaptureMode,
+    ChannelMask, ChannelSelection, DbScale, DeviceInfo, DownmixLaw, FFT_SIZES, HiddenChannels,
+    INPUT_GAIN_RANGE, Latency, Loudness, MIN_FREQ, MIN_POINTS, Matrix, MatrixFixed, Overlap,
+    POINTS_PER_OCTAVE, Pitch, PwEvent, SILENCE_THRESHOLD_RANGE, SampleFormat, SpectrumFrame,
+    SpectrumKind, StreamConfig, Weighting, WindowType, ZERO_PADDINGS, log_points, octaves,
+    spectrum_bins,
+};
 
 pub fn main() -> iced::Result {
-    iced::application(SolarSystem::new, SolarSystem::update, SolarSystem::view)
-        .subscription(SolarSystem::subscription)
-        .theme(SolarSystem::theme)
-        .run()
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(ArgsError::Help) => {
+            println!("{}", args::USAGE);
+            return Ok(());
+        }
+        Err(ArgsError::Invalid(message)) => {
+            eprintln!("{message}\n\n{}", args::USAGE);
+            std::process::exit(2);
+        }
+    };
+    if args.tui {
+        if let Err(err) = tui::run(&args) {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if args.headless {
+        if let Err(err) = headless::run(&args) {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    let settings = Settings::load();
+    let antialiasing = settings.antialiasing;
+    iced::application(
+        move || SolarSystem::new(&args, settings.clone()),
+        SolarSystem::update,
+        SolarSystem::view,
+    )
+    .subscription(SolarSystem::subscription)
+    .theme(SolarSystem::theme)
+    .antialiasing(antialiasing)
+    .run()
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 enum ShowType {
     Raw,
     Spectrum,
+    Spectrogram,
+    /// Left channel against right channel.
+    XYScope,
+    /// Mid upwards against side across, the XY scope turned by 45°.
+    Goniometer,
+}
+
+impl ShowType {
+    const ALL: [ShowType; 5] = [
+        ShowType::Raw,
+        ShowType::Spectrum,
+        ShowType::Spectrogram,
+        ShowType::XYScope,
+        ShowType::Goniometer,
+    ];
+
+    fn shows_spectrum(&self) -> bool {
+        matches!(self, Self::Spectrum | Self::Spectrogram)
+    }
 }
 
 impl Display for ShowType {
@@ -36,75 +114,1476 @@ impl Display for ShowType {
         match self {
             Self::Raw => f.write_str("raw"),
             Self::Spectrum => f.write_str("spectrum"),
+            Self::Spectrogram => f.write_str("spectrogram"),
+            Self::XYScope => f.write_str("xy scope"),
+            Self::Goniometer => f.write_str("goniometer"),
         }
     }
 }
 
 struct SolarSystem {
     state: State,
+    meter: LevelMeter,
+    stereo: StereoMeter,
+    loudness: LoudnessBars,
+    theme: Theme,
     show_type: ShowType,
+    window: WindowType,
+    db_scale: DbScale,
+    overlap: Overlap,
+    spectrum_kind: SpectrumKind,
+    fft_size: usize,
+    zero_padding: usize,
+    downmix: bool,
+    downmix_law: DownmixLaw,
+    /// Gain in dB applied in the backend before any analysis.
+    input_gain: f32,
+    remove_dc: bool,
+    /// Stop the FFT and the redraws while the input stays below
+    /// `idle_threshold` dBFS.
+    idle_gate: bool,
+    idle_threshold: f32,
+    /// The backend found the input silent.
+    idle: bool,
+    target: Option<String>,
+    target_input: String,
+    devices: Vec<DeviceInfo>,
+    capture_mode: CaptureMode,
+    latency: Latency,
+    /// The only sample format to ask for, from the command line.
+    sample_format: Option<SampleFormat>,
+    /// The only channels to capture, from the command line.
+    channel_mask: Option<ChannelMask>,
+    /// Frames per buffer the server actually uses.
+    quantum: Option<u32>,
+    error: Option<String>,
+    /// Why the last connection attempt failed, shown until the retry.
+    backend_error: Option<BackendError>,
+    format: Option<AudioInfo>,
+    pitch: Option<Pitch>,
+    last_onset: Option<Instant>,
+    /// Clipped samples since the start or the last reset.
+    clipped: u64,
+    /// Buffers lost since the start or the last reset, and when the last
+    /// one was.
+    xruns: u64,
+    last_xrun: Option<Instant>,
+    recording: Option<Recording>,
+    /// Keeps the last frame on screen, dropping whatever arrives meanwhile.
+    paused: bool,
+    show_help: bool,
+    /// Shown only while `show_performance` is on, but always measured.
+    performance: Performance,
+    show_performance: bool,
+    frame_rate: FrameRate,
+    last_redraw: Option<Instant>,
+    capturing: bool,
+    backend: Option<BackendHandle>,
+    /// Hex colors as typed for the first channels, applied once they parse.
+    channel_colors: Vec<String>,
+    spectrum_color: String,
+    show_colors: bool,
+    /// A second device shown next to the first one.
+    compare: Option<Comparison>,
+    /// Last saved, to only write when something changed.
+    settings: Settings,
+}
+
+/// A second capture drawn beside the main one, e.g. a processed sink next
+/// to the unprocessed one. It shares the analysis settings and the view.
+struct Comparison {
+    /// Node name of the device.
+    target: String,
+    state: State,
+    backend: Option<BackendHandle>,
 }
 
 #[derive(Debug, Clone)]
 enum Message {
     Tick,
     Pw(PwEvent),
+    /// From the stream of the compared device.
+    ComparePw(PwEvent),
+    CompareSelected(DeviceInfo),
+    CompareStopped,
+    ToggleColors,
+    ChannelColorInput(usize, String),
+    SpectrumColorInput(String),
     ShowTypeChanged(ShowType),
+    WindowChanged(WindowType),
+    OverlapChanged(Overlap),
+    SpectrumKindChanged(SpectrumKind),
+    FftSizeChanged(usize),
+    ZeroPaddingChanged(usize),
+    DownmixToggled(bool),
+    DownmixLawChanged(DownmixLaw),
+    ChannelCapChanged(ChannelCap),
+    HiddenChannelsChanged(HiddenChannels),
+    NextChannels,
+    InputGainChanged(f32),
+    RemoveDcToggled(bool),
+    IdleGateToggled(bool),
+    IdleThresholdChanged(f32),
+    GainChanged(f32),
+    TimeWindowChanged(usize),
+    TriggerToggled(bool),
+    TriggerEdgeChanged(TriggerEdge),
+    TriggerLevelChanged(f32),
+    TriggerSourceChanged(TriggerSource),
+    RawScrolled(usize),
+    AutoGainToggled(bool),
+    ResetClipped,
+    ResetXruns,
+    LanesToggled(bool),
+    LogAmplitudeToggled(bool),
+    LineWidthChanged(f32),
+    AutoThinToggled(bool),
+    AntialiasingToggled(bool),
+    LogFactorChanged(f32),
+    SmoothingChanged(f32),
+    PeakHoldToggled(bool),
+    CaptureNoiseFloor,
+    NoiseFloorToggled(bool),
+    SnapshotSpectrum,
+    ClearSnapshot,
+    AxisScaleChanged(AxisScale),
+    BinMappingChanged(BinMapping),
+    WeightingChanged(Weighting),
+    AutoRangeToggled(bool),
+    MelBandsChanged(usize),
+    GradientToggled(bool),
+    OrientationChanged(SpectrumOrientation),
+    HarmonicsToggled(bool),
+    CentroidToggled(bool),
+    BarsToggled(bool),
+    BarCountChanged(usize),
+    BarBandsChanged(BarBands),
+    BarGapChanged(f32),
+    TargetInput(String),
+    TargetSubmit,
+    DeviceSelected(DeviceInfo),
+    CaptureModeChanged(CaptureMode),
+    LatencyChanged(Latency),
+    Start,
+    Stop,
+    StartRecording(PathBuf),
+    StopRecording,
+    ExportSpectrum(PathBuf),
+    TogglePause,
+    /// Clears what was captured so far, the stream keeps running.
+    Reset,
+    KeyPressed(keyboard::Key),
+    ThemeChanged(Theme),
+    TogglePerformance,
+    FrameRateChanged(FrameRate),
+}
+
+/// Most channels shown at once, as offered in the UI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ChannelCap(Option<usize>);
+
+const CHANNEL_CAPS: [ChannelCap; 4] = [
+    ChannelCap(None),
+    ChannelCap(Some(1)),
+    ChannelCap(Some(2)),
+    ChannelCap(Some(4)),
+];
+
+impl Display for ChannelCap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            None => f.write_str("all channels"),
+            Some(1) => f.write_str("1 channel"),
+            Some(max) => write!(f, "{max} channels"),
+        }
+    }
+}
+
+/// Most redraws per second, the views rarely change faster than audio
+/// buffers arrive and a high refresh rate only burns power.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum FrameRate {
+    Fps30,
+    #[default]
+    Fps60,
+    /// As fast as the compositor asks.
+    Unlimited,
+}
+
+impl FrameRate {
+    const ALL: [FrameRate; 3] = [FrameRate::Fps30, FrameRate::Fps60, FrameRate::Unlimited];
+
+    /// Shortest time between two redraws.
+    fn interval(&self) -> Duration {
+        match self {
+            Self::Fps30 => Duration::from_secs(1) / 30,
+            Self::Fps60 => Duration::from_secs(1) / 60,
+            Self::Unlimited => Duration::ZERO,
+        }
+    }
+}
+
+impl Display for FrameRate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Fps30 => f.write_str("30 fps"),
+            Self::Fps60 => f.write_str("60 fps"),
+            Self::Unlimited => f.write_str("unlimited fps"),
+        }
+    }
+}
+
+/// Which way the spectrum grows from its baseline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum SpectrumOrientation {
+    #[default]
+    BottomUp,
+    /// Hanging from the top edge.
+    TopDown,
+    /// Growing both ways out of a line across the middle.
+    Mirror,
+}
+
+impl SpectrumOrientation {
+    const ALL: [SpectrumOrientation; 3] = [
+        SpectrumOrientation::BottomUp,
+        SpectrumOrientation::TopDown,
+        SpectrumOrientation::Mirror,
+    ];
+
+    /// Where `y` of a spectrum laid out bottom up lands in a frame `height`
+    /// tall, twice when mirrored.
+    fn place(&self, y: f32, height: f32) -> Vec<f32> {
+        match self {
+            Self::BottomUp => vec![y],
+            Self::TopDown => vec![height - y],
+            Self::Mirror => vec![y / 2., height - y / 2.],
+        }
+    }
+}
+
+impl Display for SpectrumOrientation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BottomUp => f.write_str("bottom up"),
+            Self::TopDown => f.write_str("top down"),
+            Self::Mirror => f.write_str("mirror"),
+        }
+    }
+}
+
+/// How long the xrun counter stays highlighted after a lost buffer.
+const XRUN_WARNING: Duration = Duration::from_secs(5);
+
+/// Level in dBFS the idle gate starts at.
+const DEFAULT_IDLE_THRESHOLD: f32 = -70.;
+
+/// Shown by the `?` help line, keep in sync with `SolarSystem::shortcut`.
+const SHORTCUTS: &str = "r raw · s spectrum · g spectrogram · x xy scope · o goniometer · space pause · p peak hold · n next channels · f performance · ? help";
+
+/// Weight of the newest sample in the performance averages.
+const PERFORMANCE_SMOOTHING: f32 = 0.05;
+
+/// Rolling averages of the frame rate and the backend's spectrum time.
+#[derive(Debug, Default)]
+struct Performance {
+    last_frame: Option<Instant>,
+    frame_secs: f32,
+    spectrum_secs: f32,
+}
+
+impl Performance {
+    fn frame(&mut self) {
+        let now = Instant::now();
+        if let Some(last_frame) = self.last_frame.replace(now) {
+            let secs = (now - last_frame).as_secs_f32();
+            self.frame_secs += (secs - self.frame_secs) * PERFORMANCE_SMOOTHING;
+        }
+    }
+
+    fn spectrum_time(&mut self, time: Duration) {
+        self.spectrum_secs += (time.as_secs_f32() - self.spectrum_secs) * PERFORMANCE_SMOOTHING;
+    }
+}
+
+impl Display for Performance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fps = if self.frame_secs > 0. {
+            1. / self.frame_secs
+        } else {
+            0.
+        };
+        write!(
+            f,
+            "{fps:.0} fps · spectrum {:.2} ms",
+            self.spectrum_secs * 1000.
+        )
+    }
+}
+
+/// A WAV recording in progress, split into numbered parts on format changes.
+#[derive(Debug)]
+struct Recording {
+    path: PathBuf,
+    part: u32,
+    writer: WavWriter,
+}
+
+/// Default file name for recordings and exports, in the working directory.
+fn timestamped_path(prefix: &str, extension: &str) -> PathBuf {
+    let secs = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    PathBuf::from(format!("{prefix}-{secs}.{extension}"))
 }
 
 impl SolarSystem {
-    fn new() -> Self {
+    fn new(args: &Args, settings: Settings) -> Self {
+        let theme = settings.theme();
+        let show_type = args.show_type.unwrap_or(settings.show_type);
+        let mut state = State::new();
+        state.show_type = show_type;
+        state.set_theme(&theme);
+        state.set_smoothing(
+            settings
+                .smoothing
+                .clamp(*SMOOTHING_RANGE.start(), *SMOOTHING_RANGE.end()),
+        );
+        let mut channel_colors = settings.channel_colors.clone();
+        channel_colors.resize(CUSTOM_LINE_COLORS, String::new());
+        let spectrum_color = settings.spectrum_color.clone();
+        state.set_custom_colors(CustomColors::parse(&channel_colors, &spectrum_color));
+        state.set_channel_selection(settings.channels);
+        state.set_line_width(
+            settings
+                .line_width
+                .clamp(*LINE_WIDTH_RANGE.start(), *LINE_WIDTH_RANGE.end()),
+        );
+        state.set_auto_thin(settings.auto_thin);
+        state.set_spectrum_orientation(settings.spectrum_orientation);
         Self {
-            state: State::new(),
-            show_type: ShowType::Raw,
+            state,
+            meter: LevelMeter::default(),
+            stereo: StereoMeter::default(),
+            loudness: LoudnessBars::default(),
+            theme,
+            show_type,
+            window: args.window.unwrap_or(settings.window),
+            db_scale: DbScale::default(),
+            overlap: Overlap::default(),
+            spectrum_kind: SpectrumKind::default(),
+            fft_size: args.fft_size.unwrap_or(settings.fft_size),
+            zero_padding: 1,
+            downmix: false,
+            downmix_law: DownmixLaw::default(),
+            input_gain: 0.,
+            remove_dc: true,
+            idle_gate: args.idle_below.is_some(),
+            idle_threshold: args.idle_below.unwrap_or(DEFAULT_IDLE_THRESHOLD),
+            idle: false,
+            target: args.device.clone(),
+            target_input: args.device.clone().unwrap_or_default(),
+            devices: vec![],
+            capture_mode: CaptureMode::default(),
+            latency: args.latency.unwrap_or_default(),
+            sample_format: args.format,
+            channel_mask: args.channels,
+            quantum: None,
+            error: None,
+            backend_error: None,
+            format: None,
+            pitch: None,
+            last_onset: None,
+            clipped: 0,
+            xruns: 0,
+            last_xrun: None,
+            recording: None,
+            paused: false,
+            show_help: false,
+            performance: Performance::default(),
+            show_performance: false,
+            frame_rate: settings.frame_rate,
+            last_redraw: None,
+            capturing: true,
+            backend: None,
+            channel_colors,
+            spectrum_color,
+            show_colors: false,
+            compare: None,
+            settings,
+        }
+    }
+
+    /// Writes the settings out whenever one of them changed.
+    fn save_settings(&mut self) {
+        let mut settings = Settings {
+            show_type: self.show_type,
+            theme: self.theme.to_string(),
+            window: self.window,
+            fft_size: self.fft_size,
+            smoothing: self.state.data.smoothing,
+            channel_colors: self.channel_colors.clone(),
+            spectrum_color: self.spectrum_color.clone(),
+            frame_rate: self.frame_rate,
+            channels: self.state.data.channel_selection,
+            line_width: self.state.data.line_width,
+            auto_thin: self.state.data.auto_thin,
+            antialiasing: self.settings.antialiasing,
+            spectrum_orientation: self.state.spectrum_orientation,
+        };
+        while settings.channel_colors.last().is_some_and(String::is_empty) {
+            settings.channel_colors.pop();
+        }
+        if settings == self.settings {
+            return;
+        }
+        if let Err(err) = settings.save() {
+            self.error = Some(format!("cannot save settings: {err}"));
+        }
+        self.settings = settings;
+    }
+
+    fn start_recording(&mut self, path: PathBuf, part: u32) {
+        let Some(format) = &self.format else {
+            self.error = Some("nothing to record before a format is negotiated".to_owned());
+            return;
+        };
+        let file = if part == 0 {
+            path.clone()
+        } else {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            path.with_file_name(format!("{stem}-{part}.wav"))
+        };
+        match WavWriter::create(&file, format) {
+            Ok(writer) => self.recording = Some(Recording { path, part, writer }),
+            Err(err) => self.error = Some(format!("cannot record to {}: {err}", file.display())),
+        }
+    }
+
+    /// Finishes the current file, returning the base path and its part.
+    fn stop_recording(&mut self) -> Option<(PathBuf, u32)> {
+        let Recording { path, part, writer } = self.recording.take()?;
+        let file = writer.path().to_owned();
+        if let Err(err) = writer.finish() {
+            self.error = Some(format!("cannot finish {}: {err}", file.display()));
+        }
+        Some((path, part))
+    }
+
+    /// What is being captured, e.g. "48000 Hz · 2 ch · Speakers".
+    fn status(&self) -> Option<String> {
+        let format = self.format.as_ref().filter(|_| self.capturing)?;
+        let device = self.device_name(self.target.as_deref());
+        let mut status = format!("{} Hz · {} ch · {device}", format.rate(), format.channels());
+        if let Some(quantum) = self.quantum {
+            let millis = quantum as f32 * 1000. / format.rate().max(1) as f32;
+            status.push_str(&format!(" · {quantum} frames ({millis:.1} ms)"));
+        }
+        Some(status)
+    }
+
+    /// Lights up on an onset and fades back over `ONSET_FLASH`.
+    fn onset_color(&self) -> Color {
+        let flash = self.last_onset.map_or(0., |onset| {
+            1. - onset.elapsed().as_secs_f32() / ONSET_FLASH.as_secs_f32()
+        });
+        Color {
+            a: flash.clamp(0.15, 1.),
+            ..ONSET_COLOR
+        }
+    }
+
+    fn shortcut(&self, key: keyboard::Key) -> Option<Message> {
+        use keyboard::Key;
+        use keyboard::key::Named;
+
+        Some(match key.as_ref() {
+            Key::Character("r") => Message::ShowTypeChanged(ShowType::Raw),
+            Key::Character("s") => Message::ShowTypeChanged(ShowType::Spectrum),
+            Key::Character("g") => Message::ShowTypeChanged(ShowType::Spectrogram),
+            Key::Character("x") => Message::ShowTypeChanged(ShowType::XYScope),
+            Key::Character("o") => Message::ShowTypeChanged(ShowType::Goniometer),
+            Key::Character("p") => Message::PeakHoldToggled(!self.state.show_peak_hold),
+            Key::Character("n") => Message::NextChannels,
+            Key::Character("f") => Message::TogglePerformance,
+            Key::Named(Named::Space) => Message::TogglePause,
+            _ => return None,
+        })
+    }
+
+    fn send_command(&self, command: BackendCommand) {
+        if let Some(backend) = &self.backend {
+            backend.send(command);
+        }
+        if let Some(backend) = self
+            .compare
+            .as_ref()
+            .and_then(|compare| compare.backend.as_ref())
+        {
+            backend.send(command);
+        }
+    }
+
+    /// Shows the colors typed so far, the ones that don't parse yet keep
+    /// the theme's.
+    fn apply_colors(&mut self) {
+        self.state.set_custom_colors(CustomColors::parse(
+            &self.channel_colors,
+            &self.spectrum_color,
+        ));
+    }
+
+    /// Brings a freshly connected backend up to the current settings.
+    fn configure_backend(&self, backend: &BackendHandle) {
+        backend.send(BackendCommand::Window(self.window));
+        backend.send(BackendCommand::DbScale(self.db_scale));
+        backend.send(BackendCommand::Overlap(self.overlap));
+        backend.send(BackendCommand::SpectrumKind(self.spectrum_kind));
+        backend.send(BackendCommand::FftSize(self.fft_size));
+        backend.send(BackendCommand::SpectrumVisible(
+            self.show_type.shows_spectrum(),
+        ));
+        backend.send(BackendCommand::Downmix(self.downmix));
+        backend.send(BackendCommand::DownmixLaw(self.downmix_law));
+        backend.send(BackendCommand::InputGain(self.input_gain));
+        backend.send(BackendCommand::RemoveDc(self.remove_dc));
+        backend.send(BackendCommand::ZeroPadding(self.zero_padding));
+        backend.send(BackendCommand::SilenceThreshold(self.silence_threshold()));
+        backend.send(BackendCommand::Channels(self.state.data.channel_selection));
+    }
+
+    fn set_channel_selection(&mut self, selection: ChannelSelection) {
+        self.state.set_channel_selection(selection);
+        self.send_command(BackendCommand::Channels(selection));
+    }
+
+    fn silence_threshold(&self) -> Option<f32> {
+        self.idle_gate.then_some(self.idle_threshold)
+    }
+
+    /// Hands what the compared stream reports to its own view, the rest
+    /// only concerns the main one.
+    fn compare_event(&mut self, event: PwEvent) {
+        if let PwEvent::Ready(backend) = &event {
+            self.configure_backend(backend);
+        }
+        let paused = self.paused;
+        let Some(compare) = &mut self.compare else {
+            return;
+        };
+        match event {
+            PwEvent::Ready(backend) => {
+                compare.backend = Some(backend);
+            }
+            PwEvent::Reconnecting(_) => {
+                compare.backend = None;
+            }
+            PwEvent::FormatChange(format) => {
+                let len = compare.state.data.raw_matrix.len();
+                compare.state.reset_matrix(len, format.channels() as usize);
+                compare.state.set_rate(format.rate());
+            }
+            PwEvent::Spectrum(spectrum) if !paused => {
+                compare.state.set_spectrum(spectrum);
+            }
+            PwEvent::Pitch(pitch) if !paused => {
+                compare
+                    .state
+                    .set_pitch(pitch.map(|pitch| pitch.freq as f64));
+            }
+            PwEvent::DataNew(data) if !paused => {
+                compare.state.append_data(data);
+            }
+            _ => {}
+        }
+    }
+
+    /// Name of `target` as listed by the server, or of the default device.
+    fn device_name<'a>(&'a self, target: Option<&'a str>) -> &'a str {
+        match target {
+            Some(target) => self
+                .devices
+                .iter()
+                .find(|device| device.name == target)
+                .map_or(target, |device| device.description.as_str()),
+            None => match self.capture_mode {
+                CaptureMode::SinkMonitor => "default sink",
+                CaptureMode::Source => "default source",
+            },
+        }
+    }
+
+    /// Whether enough time passed since the last redraw for another one.
+    fn redraw_due(&mut self) -> bool {
+        let now = Instant::now();
+        if self
+            .last_redraw
+            .is_some_and(|last| now - last < self.frame_rate.interval())
+        {
+            return false;
         }
+        self.last_redraw = Some(now);
+        true
     }
 
     fn update(&mut self, message: Message) {
+        let view_changed = !matches!(
+            message,
+            Message::Tick | Message::Pw(_) | Message::ComparePw(_)
+        );
         match message {
-            Message::Tick => {
-                self.state.update_canvas();
+            // while paused the last frame stays and new data is dropped, the
+            // data keeps coming in between redraws
+            Message::Tick if !self.paused && self.redraw_due() => {
+                self.performance.frame();
+                // a silent input looks the same frame after frame
+                if !self.idle {
+                    self.state.update_canvas();
+                }
+                if let Some(compare) = &mut self.compare {
+                    compare.state.update_canvas();
+                }
+            }
+            Message::Pw(PwEvent::Ready(backend)) => {
+                self.configure_backend(&backend);
+                self.backend = Some(backend);
+            }
+            Message::ComparePw(event) => {
+                self.compare_event(event);
+            }
+            Message::CompareSelected(device) => {
+                let mut state = State::new();
+                state.copy_view(&self.state);
+                self.compare = Some(Comparison {
+                    target: device.name,
+                    state,
+                    backend: None,
+                });
+            }
+            Message::ToggleColors => {
+                self.show_colors = !self.show_colors;
+            }
+            Message::ChannelColorInput(channel, input) => {
+                if let Some(color) = self.channel_colors.get_mut(channel) {
+                    *color = input;
+                }
+                self.apply_colors();
+            }
+            Message::SpectrumColorInput(input) => {
+                self.spectrum_color = input;
+                self.apply_colors();
+            }
+            Message::CompareStopped => {
+                // dropping the subscription stops its thread
+                self.compare = None;
+            }
+            Message::Pw(PwEvent::Devices(devices)) => {
+                self.devices = devices;
+            }
+            Message::Pw(PwEvent::TargetNotFound(target)) => {
+                self.error = Some(format!("capture target \"{target}\" not found"));
+            }
+            Message::Pw(PwEvent::Quantum(quantum)) => {
+                self.quantum = Some(quantum);
+            }
+            Message::Pw(PwEvent::Error(err)) => {
+                // the server's messages are kept in the terminal as well,
+                // the status line only shows the latest error
+                if let BackendError::Server(_) = &err {
+                    eprintln!("{err}");
+                }
+                self.error = Some(err.to_string());
+                self.backend_error = Some(err);
+            }
+            Message::Pw(PwEvent::Idle(idle)) => {
+                self.idle = idle;
+            }
+            Message::Pw(PwEvent::Reconnecting(delay)) => {
+                self.backend = None;
+                self.idle = false;
+                let reason = self
+                    .backend_error
+                    .take()
+                    .map_or_else(|| "disconnected".to_owned(), |err| err.to_string());
+                self.error = Some(format!(
+                    "{reason}, reconnecting in {:.1}s",
+                    delay.as_secs_f32()
+                ));
             }
             Message::Pw(PwEvent::FormatChange(format)) => {
+                self.error = None;
+                // a new stream starts out active
+                self.idle = false;
                 let channel = format.channels();
-                self.state.reset_matrix(500, channel as usize);
+                let len = self.state.data.raw_matrix.len();
+                self.state.reset_matrix(len, channel as usize);
                 self.state.set_rate(format.rate());
+                self.format = Some(format);
+                // a WAV file has a single format, carry on in a new one
+                if let Some((path, part)) = self.stop_recording() {
+                    self.start_recording(path, part + 1);
+                }
             }
-            Message::Pw(PwEvent::Spectrum(spectrum)) => {
+            Message::Pw(PwEvent::Spectrum(spectrum)) if !self.paused => {
                 self.state.set_spectrum(spectrum);
             }
+            Message::Pw(PwEvent::Levels { rms, peak }) if !self.paused => {
+                self.stereo.update_balance(&rms);
+                self.meter.update(rms, peak);
+            }
+            Message::Pw(PwEvent::Correlation(correlation)) if !self.paused => {
+                self.stereo.update_correlation(correlation);
+            }
+            Message::Pw(PwEvent::Loudness(loudness)) if !self.paused => {
+                self.loudness.loudness = loudness;
+            }
+            Message::Pw(PwEvent::Pitch(pitch)) if !self.paused => {
+                self.state
+                    .set_pitch(pitch.as_ref().map(|pitch| pitch.freq as f64));
+                self.pitch = pitch;
+            }
+            Message::Pw(PwEvent::SpectrumTime(time)) => {
+                self.performance.spectrum_time(time);
+            }
+            Message::TogglePerformance => {
+                self.show_performance = !self.show_performance;
+            }
+            Message::FrameRateChanged(frame_rate) => {
+                self.frame_rate = frame_rate;
+            }
+            Message::Pw(PwEvent::Onset) if !self.paused => {
+                self.last_onset = Some(Instant::now());
+            }
+            Message::Pw(PwEvent::Clipped(count)) => {
+                self.clipped += count as u64;
+            }
+            Message::Pw(PwEvent::Xrun(count)) => {
+                self.xruns += count as u64;
+                self.last_xrun = Some(Instant::now());
+            }
             Message::Pw(PwEvent::DataNew(data)) => {
-                self.state.append_data(data);
+                if let Some(recording) = &mut self.recording
+                    && let Err(err) = recording.writer.write(&data)
+                {
+                    self.error = Some(format!("recording stopped: {err}"));
+                    self.stop_recording();
+                }
+                // the recording keeps going, only the display is frozen
+                if !self.paused {
+                    self.state.append_data(data);
+                }
             }
             Message::ShowTypeChanged(ty) => {
+                // whatever was computed before hiding it is long outdated
+                if ty.shows_spectrum() && !self.show_type.shows_spectrum() {
+                    self.state.clear_spectrum();
+                }
                 self.show_type = ty;
                 self.state.show_type = ty;
+                self.send_command(BackendCommand::SpectrumVisible(ty.shows_spectrum()));
+            }
+            Message::WindowChanged(window) => {
+                self.window = window;
+                self.send_command(BackendCommand::Window(window));
+            }
+            Message::OverlapChanged(overlap) => {
+                self.overlap = overlap;
+                self.send_command(BackendCommand::Overlap(overlap));
+            }
+            Message::SpectrumKindChanged(kind) => {
+                self.spectrum_kind = kind;
+                self.send_command(BackendCommand::SpectrumKind(kind));
+            }
+            Message::ZeroPaddingChanged(zero_padding) => {
+                self.zero_padding = zero_padding;
+                self.send_command(BackendCommand::ZeroPadding(zero_padding));
+            }
+            Message::FftSizeChanged(fft_size) => {
+                self.fft_size = fft_size;
+                self.send_command(BackendCommand::FftSize(fft_size));
+            }
+            Message::DownmixToggled(downmix) => {
+                self.downmix = downmix;
+                self.send_command(BackendCommand::Downmix(downmix));
+            }
+            Message::DownmixLawChanged(downmix_law) => {
+                self.downmix_law = downmix_law;
+                self.send_command(BackendCommand::DownmixLaw(downmix_law));
+            }
+            Message::ChannelCapChanged(ChannelCap(max)) => {
+                self.set_channel_selection(ChannelSelection {
+                    max,
+                    first: 0,
+                    ..self.state.data.channel_selection
+                });
+            }
+            Message::HiddenChannelsChanged(hidden) => {
+                self.set_channel_selection(ChannelSelection {
+                    hidden,
+                    ..self.state.data.channel_selection
+                });
+            }
+            Message::NextChannels => {
+                let channels = self.state.data.raw_matrix.channel();
+                self.set_channel_selection(self.state.data.channel_selection.next(channels));
+            }
+            Message::InputGainChanged(input_gain) => {
+                self.input_gain = input_gain;
+                self.send_command(BackendCommand::InputGain(input_gain));
+            }
+            Message::RemoveDcToggled(remove_dc) => {
+                self.remove_dc = remove_dc;
+                self.send_command(BackendCommand::RemoveDc(remove_dc));
+            }
+            Message::IdleGateToggled(idle_gate) => {
+                self.idle_gate = idle_gate;
+                self.send_command(BackendCommand::SilenceThreshold(self.silence_threshold()));
+            }
+            Message::IdleThresholdChanged(idle_threshold) => {
+                self.idle_threshold = idle_threshold;
+                self.send_command(BackendCommand::SilenceThreshold(self.silence_threshold()));
+            }
+            Message::GainChanged(gain) => {
+                self.state.set_gain(gain);
+            }
+            Message::TimeWindowChanged(len) => {
+                self.state.set_time_window(len);
+            }
+            Message::RawScrolled(offset) => {
+                self.state.set_view_offset(offset);
+            }
+            Message::TriggerToggled(enabled) => {
+                self.state.set_trigger(Trigger {
+                    enabled,
+                    ..self.state.data.trigger
+                });
+            }
+            Message::TriggerEdgeChanged(edge) => {
+                self.state.set_trigger(Trigger {
+                    edge,
+                    ..self.state.data.trigger
+                });
+            }
+            Message::TriggerLevelChanged(level) => {
+                self.state.set_trigger(Trigger {
+                    level,
+                    ..self.state.data.trigger
+                });
+            }
+            Message::TriggerSourceChanged(source) => {
+                self.state.set_trigger(Trigger {
+                    channel: source.channel,
+                    ..self.state.data.trigger
+                });
+            }
+            Message::ResetClipped => {
+                self.clipped = 0;
+            }
+            Message::ResetXruns => {
+                self.xruns = 0;
+                self.last_xrun = None;
+            }
+            Message::AutoGainToggled(auto_gain) => {
+                self.state.set_auto_gain(auto_gain);
+            }
+            Message::LanesToggled(lanes) => {
+                self.state.set_lanes(lanes);
+            }
+            Message::LogAmplitudeToggled(log_amplitude) => {
+                self.state.set_log_amplitude(log_amplitude);
+            }
+            Message::LogFactorChanged(log_factor) => {
+                self.state.set_log_factor(log_factor);
+            }
+            Message::LineWidthChanged(line_width) => {
+                self.state.set_line_width(line_width);
+            }
+            Message::AutoThinToggled(auto_thin) => {
+                self.state.set_auto_thin(auto_thin);
+            }
+            Message::AntialiasingToggled(antialiasing) => {
+                // takes effect when the window is next opened
+                self.settings.antialiasing = antialiasing;
+                if let Err(err) = self.settings.save() {
+                    self.error = Some(format!("cannot save settings: {err}"));
+                }
+            }
+            Message::SmoothingChanged(smoothing) => {
+                self.state.set_smoothing(smoothing);
+            }
+            Message::AutoRangeToggled(auto_range) => {
+                self.state.set_auto_range(auto_range);
+            }
+            Message::MelBandsChanged(mel_bands) => {
+                self.state.set_mel_bands(mel_bands);
+            }
+            Message::GradientToggled(gradient) => {
+                self.state.set_spectrum_gradient(gradient);
+            }
+            Message::OrientationChanged(orientation) => {
+                self.state.set_spectrum_orientation(orientation);
+            }
+            Message::HarmonicsToggled(show_harmonics) => {
+                self.state.set_show_harmonics(show_harmonics);
+            }
+            Message::CentroidToggled(show_centroid) => {
+                self.state.show_centroid = show_centroid;
+            }
+            Message::BarsToggled(bars) => {
+                self.state.set_bars(bars);
+            }
+            Message::BarBandsChanged(bar_bands) => {
+                self.state.set_bar_bands(bar_bands);
+            }
+            Message::BarCountChanged(bar_count) => {
+                self.state.set_bar_count(bar_count);
+            }
+            Message::BarGapChanged(bar_gap) => {
+                self.state.set_bar_gap(bar_gap);
+            }
+            Message::WeightingChanged(weighting) => {
+                self.state.set_weighting(weighting);
+            }
+            Message::BinMappingChanged(bin_mapping) => {
+                self.state.set_bin_mapping(bin_mapping);
+            }
+            Message::AxisScaleChanged(axis_scale) => {
+                self.state.set_axis_scale(axis_scale);
+            }
+            Message::PeakHoldToggled(show_peak_hold) => {
+                self.state.set_show_peak_hold(show_peak_hold);
+            }
+            Message::CaptureNoiseFloor => {
+                self.state.capture_noise_floor();
+            }
+            Message::NoiseFloorToggled(subtract) => {
+                self.state.subtract_noise_floor = subtract;
+            }
+            Message::SnapshotSpectrum => {
+                self.state.snapshot_spectrum();
+            }
+            Message::ClearSnapshot => {
+                self.state.clear_snapshot();
+            }
+            Message::TargetInput(input) => {
+                self.target_input = input;
+            }
+            Message::TargetSubmit => {
+                let target = self.target_input.trim();
+                // the subscription is keyed on the target, changing it reconnects
+                self.target = (!target.is_empty()).then(|| target.to_owned());
+                self.error = None;
+            }
+            Message::CaptureModeChanged(mode) => {
+                // a target of the other kind would not link
+                self.capture_mode = mode;
+                self.target = None;
+                self.target_input.clear();
+                self.error = None;
+            }
+            Message::LatencyChanged(latency) => {
+                // takes a new connection, the subscription is keyed on it
+                self.latency = latency;
+                self.quantum = None;
+            }
+            Message::DeviceSelected(device) => {
+                self.target_input = device.name.clone();
+                self.target = Some(device.name.clone());
+                self.error = None;
+            }
+            Message::Start => {
+                self.capturing = true;
+            }
+            Message::StartRecording(path) => {
+                self.stop_recording();
+                self.start_recording(path, 0);
+            }
+            Message::StopRecording => {
+                self.stop_recording();
+            }
+            Message::ThemeChanged(theme) => {
+                self.state.set_theme(&theme);
+                self.theme = theme;
+            }
+            Message::Reset => {
+                self.state.reset();
+                if let Some(compare) = &mut self.compare {
+                    compare.state.reset();
+                }
+                self.meter = LevelMeter::default();
+                self.stereo = StereoMeter::default();
+                self.loudness = LoudnessBars::default();
+                self.send_command(BackendCommand::ResetLoudness);
+                self.pitch = None;
+            }
+            Message::TogglePause => {
+                self.paused = !self.paused;
+            }
+            Message::KeyPressed(keyboard::Key::Character(key)) if key == "?" => {
+                self.show_help = !self.show_help;
+            }
+            Message::KeyPressed(key) => {
+                if let Some(message) = self.shortcut(key) {
+                    self.update(message);
+                }
+            }
+            Message::ExportSpectrum(path) => {
+                if let Err(err) = self.state.data.write_spectrum_csv(&path) {
+                    self.error = Some(format!("cannot export to {}: {err}", path.display()));
+                }
+            }
+            Message::Stop => {
+                // dropping the subscription stops the thread as well, this
+                // just lets it wind down right away
+                let compared = self
+                    .compare
+                    .as_mut()
+                    .and_then(|compare| compare.backend.take());
+                for backend in self.backend.take().into_iter().chain(compared) {
+                    backend.stop();
+                }
+                self.capturing = false;
+                self.error = None;
             }
             _ => {}
         }
+        if view_changed && let Some(compare) = &mut self.compare {
+            compare.state.copy_view(&self.state);
+        }
+        self.save_settings();
     }
 
     fn view(&self) -> Element<'_, Message> {
+        let default_device = self.device_name(None);
+        let devices: Vec<_> = self
+            .devices
+            .iter()
+            .filter(|device| device.mode == self.capture_mode)
+            .cloned()
+            .collect();
+        let plots: Element<'_, Message> = match &self.compare {
+            Some(compare) => row![
+                column![
+                    text(self.device_name(self.target.as_deref())),
+                    canvas(&self.state).width(Fill).height(Fill),
+                ],
+                column![
+                    text(self.device_name(Some(&compare.target))),
+                    canvas(&compare.state).width(Fill).height(Fill),
+                ],
+            ]
+            .spacing(10)
+            .into(),
+            None => canvas(&self.state).width(Fill).height(Fill).into(),
+        };
         column![
-            pick_list(
-                [ShowType::Raw, ShowType::Spectrum],
-                Some(&self.show_type),
-                Message::ShowTypeChanged
-            ),
-            canvas(&self.state).width(Fill).height(Fill)
+            row![
+                pick_list(
+                    ShowType::ALL,
+                    Some(&self.show_type),
+                    Message::ShowTypeChanged
+                ),
+                pick_list(WindowType::ALL, Some(&self.window), Message::WindowChanged),
+                pick_list(Overlap::ALL, Some(&self.overlap), Message::OverlapChanged),
+                pick_list(FFT_SIZES, Some(self.fft_size), Message::FftSizeChanged),
+                text("padding ×"),
+                pick_list(
+                    ZERO_PADDINGS,
+                    Some(self.zero_padding),
+                    Message::ZeroPaddingChanged
+                ),
+                checkbox(self.downmix)
+                    .label("downmix")
+                    .on_toggle(Message::DownmixToggled),
+                self.downmix.then(|| pick_list(
+                    DownmixLaw::ALL,
+                    Some(self.downmix_law),
+                    Message::DownmixLawChanged
+                )),
+                pick_list(
+                    CHANNEL_CAPS,
+                    Some(ChannelCap(self.state.data.channel_selection.max)),
+                    Message::ChannelCapChanged
+                ),
+                self.state
+                    .data
+                    .channel_selection
+                    .max
+                    .is_some()
+                    .then(|| row![
+                        pick_list(
+                            HiddenChannels::ALL,
+                            Some(self.state.data.channel_selection.hidden),
+                            Message::HiddenChannelsChanged
+                        ),
+                        button("next channels").on_press(Message::NextChannels),
+                    ]
+                    .spacing(10)),
+                text(format!("input {:+.0} dB", self.input_gain)),
+                slider(INPUT_GAIN_RANGE, self.input_gain, Message::InputGainChanged).step(1.),
+                checkbox(self.idle_gate)
+                    .label("idle gate")
+                    .on_toggle(Message::IdleGateToggled),
+                self.idle_gate.then(|| row![
+                    text(format!("below {:.0} dBFS", self.idle_threshold)),
+                    slider(
+                        SILENCE_THRESHOLD_RANGE,
+                        self.idle_threshold,
+                        Message::IdleThresholdChanged
+                    )
+                    .step(1.),
+                ]),
+                pick_list(
+                    CaptureMode::ALL,
+                    Some(self.capture_mode),
+                    Message::CaptureModeChanged
+                ),
+                pick_list(Latency::ALL, Some(self.latency), Message::LatencyChanged),
+                pick_list(
+                    devices.clone(),
+                    self.devices
+                        .iter()
+                        .find(|device| self.target.as_deref() == Some(device.name.as_str())),
+                    Message::DeviceSelected
+                )
+                .placeholder(default_device),
+                pick_list(
+                    devices,
+                    self.compare.as_ref().and_then(|compare| {
+                        self.devices
+                            .iter()
+                            .find(|device| device.name == compare.target)
+                    }),
+                    Message::CompareSelected
+                )
+                .placeholder("compare with"),
+                self.compare
+                    .is_some()
+                    .then(|| button("stop comparing").on_press(Message::CompareStopped)),
+                text_input("capture target", &self.target_input)
+                    .on_input(Message::TargetInput)
+                    .on_submit(Message::TargetSubmit),
+                if self.capturing {
+                    button("stop").on_press(Message::Stop)
+                } else {
+                    button("start").on_press(Message::Start)
+                },
+                if self.recording.is_some() {
+                    button("stop recording").on_press(Message::StopRecording)
+                } else {
+                    button("record")
+                        .on_press(Message::StartRecording(timestamped_path("capture", "wav")))
+                },
+                button(if self.paused { "resume" } else { "pause" }).on_press(Message::TogglePause),
+                button("export spectrum")
+                    .on_press(Message::ExportSpectrum(timestamped_path("spectrum", "csv"))),
+                button("clear").on_press(Message::Reset),
+                pick_list(Theme::ALL, Some(&self.theme), Message::ThemeChanged),
+                pick_list(
+                    FrameRate::ALL,
+                    Some(self.frame_rate),
+                    Message::FrameRateChanged
+                ),
+                button("colors").on_press(Message::ToggleColors),
+            ],
+            self.show_colors.then(|| {
+                let channels = self
+                    .state
+                    .data
+                    .raw_matrix
+                    .channel()
+                    .clamp(1, CUSTOM_LINE_COLORS);
+                let inputs =
+                    self.channel_colors[..channels]
+                        .iter()
+                        .enumerate()
+                        .map(|(channel, color)| {
+                            text_input(&channel_label(channel, channels), color)
+                                .on_input(move |input| Message::ChannelColorInput(channel, input))
+                                .width(90)
+                                .into()
+                        });
+                Row::with_children(inputs)
+                    .push(
+                        text_input("spectrum", &self.spectrum_color)
+                            .on_input(Message::SpectrumColorInput)
+                            .width(90),
+                    )
+                    .push(text("#rrggbb, empty follows the theme"))
+                    .spacing(4)
+            }),
+            matches!(
+                self.show_type,
+                ShowType::Raw | ShowType::XYScope | ShowType::Goniometer
+            )
+            .then(|| row![
+                text("gain"),
+                slider(GAIN_RANGE, self.state.data.gain, Message::GainChanged).step(0.1),
+                toggler(self.state.data.auto_gain)
+                    .label("auto")
+                    .on_toggle(Message::AutoGainToggled),
+                checkbox(self.state.data.lanes)
+                    .label("lanes")
+                    .on_toggle(Message::LanesToggled),
+                matches!(self.show_type, ShowType::Raw).then(|| checkbox(
+                    self.state.data.log_amplitude
+                )
+                .label("log")
+                .on_toggle(Message::LogAmplitudeToggled)),
+                (matches!(self.show_type, ShowType::Raw) && self.state.data.log_amplitude).then(
+                    || row![
+                        text(format!("k {:.0}", self.state.data.log_factor)),
+                        slider(
+                            LOG_FACTOR_RANGE,
+                            self.state.data.log_factor,
+                            Message::LogFactorChanged
+                        )
+                        .step(1.),
+                    ]
+                ),
+                text(format!("{} samples", self.state.data.view_len)),
+                slider(
+                    MIN_TIME_WINDOW as f32..=self.state.data.raw_matrix.len() as f32,
+                    self.state.data.view_len as f32,
+                    |len| Message::TimeWindowChanged(len as usize)
+                )
+                .step(100.),
+                (self.state.data.view_offset > 0)
+                    .then(|| button("live").on_press(Message::RawScrolled(0))),
+                button(
+                    text(format!("{} clipped", self.clipped))
+                        .color_maybe((self.clipped > 0).then_some(CLIP_COLOR))
+                )
+                .on_press(Message::ResetClipped),
+            ]),
+            matches!(self.show_type, ShowType::Raw).then(|| {
+                let trigger = self.state.data.trigger;
+                let channels = self.state.data.raw_matrix.channel();
+                row![
+                    checkbox(trigger.enabled)
+                        .label("trigger")
+                        .on_toggle(Message::TriggerToggled),
+                    text(format!("line {:.1}", self.state.data.line_width)),
+                    slider(
+                        LINE_WIDTH_RANGE,
+                        self.state.data.line_width,
+                        Message::LineWidthChanged
+                    )
+                    .step(0.5),
+                    checkbox(self.state.data.auto_thin)
+                        .label("thin")
+                        .on_toggle(Message::AutoThinToggled),
+                    checkbox(self.settings.antialiasing)
+                        .label("antialiasing (on restart)")
+                        .on_toggle(Message::AntialiasingToggled),
+                    pick_list(
+                        TriggerEdge::ALL,
+                        Some(trigger.edge),
+                        Message::TriggerEdgeChanged
+                    ),
+                    pick_list(
+                        (0..channels)
+                            .map(|channel| TriggerSource { channel, channels })
+                            .collect::<Vec<_>>(),
+                        Some(TriggerSource {
+                            channel: trigger.channel,
+                            channels,
+                        }),
+                        Message::TriggerSourceChanged
+                    ),
+                    text(format!("level {:+.2}", trigger.level)),
+                    slider(-1.0..=1., trigger.level, Message::TriggerLevelChanged).step(0.01),
+                ]
+            }),
+            self.show_type.shows_spectrum().then(|| row![
+                pick_list(
+                    AxisScale::ALL,
+                    Some(self.state.data.axis_scale),
+                    Message::AxisScaleChanged
+                ),
+                (self.state.data.axis_scale != AxisScale::Mel).then(|| pick_list(
+                    BinMapping::ALL,
+                    Some(self.state.data.bin_mapping),
+                    Message::BinMappingChanged
+                )),
+                (self.state.data.axis_scale == AxisScale::Mel).then(|| row![
+                    text(format!("{} bands", self.state.data.mel_bands)),
+                    slider(MEL_BANDS_RANGE, self.state.data.mel_bands as f32, |bands| {
+                        Message::MelBandsChanged(bands as usize)
+                    }),
+                ]),
+                pick_list(
+                    SpectrumKind::ALL,
+                    Some(self.spectrum_kind),
+                    Message::SpectrumKindChanged
+                ),
+                pick_list(
+                    Weighting::ALL,
+                    Some(self.state.data.weighting),
+                    Message::WeightingChanged
+                ),
+                text("smoothing"),
+                slider(
+                    SMOOTHING_RANGE,
+                    self.state.data.smoothing,
+                    Message::SmoothingChanged
+                )
+                .step(0.05),
+                checkbox(self.state.data.auto_range)
+                    .label("auto range")
+                    .on_toggle(Message::AutoRangeToggled),
+                checkbox(self.state.show_peak_hold)
+                    .label("peak hold")
+                    .on_toggle(Message::PeakHoldToggled),
+                checkbox(self.remove_dc)
+                    .label("remove DC")
+                    .on_toggle(Message::RemoveDcToggled),
+                text(
+                    self.pitch
+                        .as_ref()
+                        .map_or_else(|| "-".to_owned(), Pitch::to_string)
+                ),
+                text("beat").color(self.onset_color()),
+            ]),
+            matches!(self.show_type, ShowType::Spectrum).then(|| row![
+                checkbox(self.state.spectrum_gradient)
+                    .label("gradient")
+                    .on_toggle(Message::GradientToggled),
+                pick_list(
+                    SpectrumOrientation::ALL,
+                    Some(self.state.spectrum_orientation),
+                    Message::OrientationChanged
+                ),
+                checkbox(self.state.show_harmonics)
+                    .label("harmonics")
+                    .on_toggle(Message::HarmonicsToggled),
+                self.state
+                    .show_harmonics
+                    .then(|| text("right click a peak to pick the fundamental")),
+                checkbox(self.state.show_centroid)
+                    .label("centroid")
+                    .on_toggle(Message::CentroidToggled),
+                self.state.show_centroid.then(|| text(
+                    self.state
+                        .centroid
+                        .map_or_else(|| "-".to_owned(), readout_frequency)
+                )),
+                button(if self.state.capturing_noise_floor() {
+                    "capturing floor…"
+                } else {
+                    "capture floor"
+                })
+                .on_press(Message::CaptureNoiseFloor),
+                checkbox(self.state.subtract_noise_floor)
+                    .label("subtract floor")
+                    .on_toggle(Message::NoiseFloorToggled),
+                button("snapshot").on_press(Message::SnapshotSpectrum),
+                (!self.state.snapshot.is_empty())
+                    .then(|| button("clear snapshot").on_press(Message::ClearSnapshot)),
+                checkbox(self.state.data.bars)
+                    .label("bars")
+                    .on_toggle(Message::BarsToggled),
+                self.state.data.bars.then(|| row![
+                    pick_list(
+                        BarBands::ALL,
+                        Some(self.state.data.bar_bands),
+                        Message::BarBandsChanged
+                    ),
+                    (self.state.data.bar_bands == BarBands::Even).then(|| row![
+                        text(format!("{} bars", self.state.data.bar_count)),
+                        slider(BAR_COUNT_RANGE, self.state.data.bar_count as f32, |count| {
+                            Message::BarCountChanged(count as usize)
+                        }),
+                    ]),
+                    text("gap"),
+                    slider(
+                        BAR_GAP_RANGE,
+                        self.state.data.bar_gap,
+                        Message::BarGapChanged
+                    )
+                    .step(0.05),
+                ]),
+            ]),
+            self.show_help.then(|| text(SHORTCUTS)),
+            self.show_performance
+                .then(|| text(self.performance.to_string())),
+            self.paused
+                .then(|| text("PAUSED").color(Color::from_rgb(1., 0.8, 0.))),
+            (self.idle && self.capturing).then(|| text("idle, no signal").color(Color {
+                a: 0.5,
+                ..self.theme.palette().text
+            })),
+            self.error.as_deref().map(text),
+            row![
+                self.status().map(text),
+                (self.xruns > 0).then(|| button(
+                    text(format!("{} xruns", self.xruns)).color_maybe(
+                        self.last_xrun
+                            .filter(|last| last.elapsed() < XRUN_WARNING)
+                            .map(|_| CLIP_COLOR)
+                    )
+                )
+                .on_press(Message::ResetXruns)),
+            ]
+            .spacing(10),
+            canvas(&self.meter).width(Fill).height(self.meter.height()),
+            canvas(&self.stereo).width(Fill).height(2. * METER_LANE),
+            canvas(&self.loudness).width(Fill).height(2. * METER_LANE),
+            plots,
         ]
         .into()
     }
 
     fn theme(&self) -> Theme {
-        Theme::Moonfly
+        self.theme.clone()
+    }
+
+    fn stream_config(&self, target: Option<&str>) -> StreamConfig {
+        let mut config = StreamConfig::new()
+            .capture_mode(self.capture_mode)
+            .latency(self.latency);
+        if let Some(target) = target {
+            config = config.target(target);
+        }
+        if let Some(format) = self.sample_format {
+            config = config.format(format);
+        }
+        if let Some(channels) = self.channel_mask {
+            config = config.channel_mask(channels);
+        }
+        config
     }
 
     fn subscription(&self) -> Subscription<Message> {
-        iced::Subscription::batch(vec![
+        let mut subscriptions = vec![
             window::frames().map(|_| Message::Tick),
-            backend::listen_pw().map(Message::Pw),
-        ])
+            // only keys no widget took, typing a target doesn't switch views
+            keyboard::listen().filter_map(|event| match event {
+                keyboard::Event::KeyPressed { modified_key, .. } => {
+                    Some(Message::KeyPressed(modified_key))
+                }
+                _ => None,
+            }),
+        ];
+        if self.capturing {
+            subscriptions.push(
+                subscription::listen_pw(self.stream_config(self.target.as_deref()))
+                    .map(Message::Pw),
+            );
+        }
+        if self.capturing
+            && let Some(compare) = &self.compare
+        {
+            // tagged so it stays a stream of its own even on the same device
+            subscriptions.push(
+                subscription::listen_pw(self.stream_config(Some(&compare.target)))
+                    .with("compare")
+                    .map(|(_, event)| Message::ComparePw(event)),
+            );
+        }
+        iced::Subscription::batch(subscriptions)
     }
 }
 
@@ -114,151 +1593,2255 @@ struct LineData {
     color: iced::Color,
 }
 
+#[derive(Debug, Default, Clone)]
+struct BarData {
+    bars: Vec<Rectangle>,
+    color: iced::Color,
+}
+
 #[derive(Debug)]
 struct LineDatas {
+    /// The last `RAW_HISTORY_SECONDS` of samples.
     raw_matrix: MatrixFixed,
-    spectrum: Vec<f32>,
+    /// Samples per channel shown by the raw and xy views.
+    view_len: usize,
+    /// Samples between the newest one and the right edge of the view, 0
+    /// follows the capture.
+    view_offset: usize,
+    trigger: Trigger,
+    spectrum: SpectrumFrame,
     rate: u32,
+    /// Full scale reaches half the height times this.
+    gain: f32,
+    auto_gain: bool,
+    /// Give every channel its own horizontal lane instead of overlapping.
+    lanes: bool,
+    /// Channels drawn when there are too many, the spectrum is computed for
+    /// the same ones.
+    channel_selection: ChannelSelection,
+    axis_scale: AxisScale,
+    /// Number of bands on the mel scale.
+    mel_bands: usize,
+    mel: MelFilterbank,
+    weighting: Weighting,
+    bin_mapping: BinMapping,
+    mapping: SpectrumMapping,
+    /// Draw the spectrum as bands instead of a curve.
+    bars: bool,
+    bar_bands: BarBands,
+    bar_count: usize,
+    bar_gap: f32,
+    colors: PlotColors,
+    /// Follow the level of the content instead of the backend's fixed range.
+    auto_range: bool,
+    /// dB range mapped to the canvas height while `auto_range` is on.
+    auto_db: (f32, f32),
+    /// Weight of the previous spectrum when averaging in a new one, 0 shows
+    /// every spectrum as is.
+    smoothing: f32,
+    /// Decaying peak of the incoming samples, followed by the auto gain.
+    peak: f32,
+    /// Gain applied while `auto_gain` is on, easing towards the one fitting
+    /// `peak`.
+    smoothed_gain: f32,
+    /// Compress the raw waveform's amplitude logarithmically, by
+    /// `log_factor`, so quiet detail shows next to loud parts.
+    log_amplitude: bool,
+    log_factor: f32,
+    /// Width of the raw waveform's lines.
+    line_width: f32,
+    /// Cap `line_width` at a pixel while there are more points than pixels.
+    auto_thin: bool,
 }
 
-const COLOR_ALL: &[iced::Color] = &[
-    iced::Color::WHITE,
-    iced::Color::from_rgb(0.4, 0.4, 1.),
-    iced::Color::from_rgb(0., 0.5, 1.),
-    iced::Color::from_rgb(0.5, 0.5, 0.5),
-];
+/// Layout of the spectrum's frequency axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AxisScale {
+    Linear,
+    Log,
+    /// Bands of a mel filterbank, spaced the way pitch is perceived.
+    Mel,
+}
+
+impl AxisScale {
+    const ALL: [AxisScale; 3] = [AxisScale::Linear, AxisScale::Log, AxisScale::Mel];
+}
+
+impl Display for AxisScale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Linear => f.write_str("linear"),
+            Self::Log => f.write_str("log"),
+            Self::Mel => f.write_str("mel"),
+        }
+    }
+}
+
+/// How display points covering several bins summarize them. Points between
+/// bins interpolate either way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinMapping {
+    /// Their average power.
+    Average,
+    /// The loudest of them, narrow peaks keep their height.
+    Peak,
+}
+
+impl BinMapping {
+    const ALL: [BinMapping; 2] = [BinMapping::Average, BinMapping::Peak];
+}
+
+impl Display for BinMapping {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Average => f.write_str("average bins"),
+            Self::Peak => f.write_str("peak bins"),
+        }
+    }
+}
+
+/// Bins one display point of the linear or log spectrum is read from.
+#[derive(Debug, Clone, Copy)]
+struct PointBins {
+    /// Bins up to the next point, summarized when there are several.
+    first: usize,
+    last: usize,
+    /// Bin below the point's frequency and how far past it the frequency
+    /// is, for points narrower than a bin.
+    low: usize,
+    fraction: f32,
+}
+
+/// Which bins and weighting every display point uses, worked out once per
+/// rate, FFT size and axis instead of on every frame.
+#[derive(Debug, Clone, Default)]
+struct SpectrumMapping {
+    rate: u32,
+    fft_size: usize,
+    axis_scale: Option<AxisScale>,
+    mel_bands: usize,
+    weighting: Weighting,
+    /// Display points across the width, including any above nyquist.
+    points: usize,
+    /// One per point up to nyquist, empty on the mel scale where the
+    /// filterbank reads the bins.
+    bins: Vec<PointBins>,
+    /// Weighting offset in dB of every point up to nyquist.
+    offsets: Vec<f32>,
+}
+
+/// How the bar view splits the spectrum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum BarBands {
+    /// `bar_count` bands spaced evenly in octaves.
+    #[default]
+    Even,
+    /// The ISO 266 octave bands, 31.5 Hz to 16 kHz.
+    Octave,
+    /// The ISO 266 third-octave bands, 25 Hz to 20 kHz.
+    ThirdOctave,
+}
+
+impl BarBands {
+    const ALL: [BarBands; 3] = [BarBands::Even, BarBands::Octave, BarBands::ThirdOctave];
+}
+
+impl Display for BarBands {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Even => f.write_str("even bands"),
+            Self::Octave => f.write_str("1/1 octave"),
+            Self::ThirdOctave => f.write_str("1/3 octave"),
+        }
+    }
+}
+
+/// Nominal centers in Hz of the ISO 266 third-octave bands, every third one
+/// from 31.5 Hz is an octave band. The exact center of the band at `index`
+/// is `1000 * 2^((index - 16) / 3)`.
+const THIRD_OCTAVE_CENTERS: [f64; 30] = [
+    25., 31.5, 40., 50., 63., 80., 100., 125., 160., 200., 250., 315., 400., 500., 630., 800.,
+    1000., 1250., 1600., 2000., 2500., 3150., 4000., 5000., 6300., 8000., 10000., 12500., 16000.,
+    20000.,
+];
+/// Index of 1 kHz in `THIRD_OCTAVE_CENTERS`.
+const THIRD_OCTAVE_REFERENCE: usize = 16;
+
+/// Direction the trigger source has to cross the level in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum TriggerEdge {
+    #[default]
+    Rising,
+    Falling,
+}
+
+impl TriggerEdge {
+    const ALL: [TriggerEdge; 2] = [TriggerEdge::Rising, TriggerEdge::Falling];
+}
+
+impl Display for TriggerEdge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Rising => f.write_str("rising edge"),
+            Self::Falling => f.write_str("falling edge"),
+        }
+    }
+}
+
+/// Oscilloscope trigger of the raw view: while following the capture, the
+/// view starts where `channel` last crossed `level`, so periodic signals
+/// stand still. Without a crossing it runs free.
+#[derive(Debug, Clone, Copy, Default)]
+struct Trigger {
+    enabled: bool,
+    edge: TriggerEdge,
+    level: f32,
+    channel: usize,
+}
+
+impl Trigger {
+    /// Whether going from `previous` to `sample` crosses the level.
+    fn fires(&self, previous: f32, sample: f32) -> bool {
+        match self.edge {
+            TriggerEdge::Rising => previous < self.level && sample >= self.level,
+            TriggerEdge::Falling => previous > self.level && sample <= self.level,
+        }
+    }
+}
+
+/// A channel to trigger on, named like the lanes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TriggerSource {
+    channel: usize,
+    channels: usize,
+}
+
+impl Display for TriggerSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&channel_label(self.channel, self.channels))
+    }
+}
+
+/// Room left above the loudest bin by the auto range, in dB.
+const AUTO_RANGE_HEADROOM: f32 = 6.;
+/// Most the auto range bounds move per spectrum, in dB.
+const AUTO_RANGE_SLEW: f32 = 1.;
+/// Smallest span the auto range shrinks to, in dB.
+const AUTO_RANGE_MIN_SPAN: f32 = 20.;
+
+/// Range of the spectrum smoothing, 1 would freeze it.
+const SMOOTHING_RANGE: std::ops::RangeInclusive<f32> = 0.0..=0.95;
+/// Samples per channel shown by the raw and xy views.
+const DEFAULT_TIME_WINDOW: usize = 500;
+const MIN_TIME_WINDOW: usize = 100;
+/// How far back the raw view can be scrolled.
+const RAW_HISTORY_SECONDS: usize = 5;
+/// Factor the raw view zooms by per wheel notch.
+const RAW_ZOOM_STEP: f32 = 1.25;
+/// Smooth scrolling touchpads report pixels, this many make one notch.
+const PIXELS_PER_LINE: f32 = 40.;
+/// Range of the manual waveform gain.
+const GAIN_RANGE: std::ops::RangeInclusive<f32> = 0.1..=20.;
+/// Range of the log amplitude's factor, 255 is the μ-law one.
+const LOG_FACTOR_RANGE: std::ops::RangeInclusive<f32> = 1.0..=1000.;
+const DEFAULT_LOG_FACTOR: f32 = 255.;
+const LINE_WIDTH_RANGE: std::ops::RangeInclusive<f32> = 0.5..=5.;
+/// Share of the half-height the auto gain fills with the recent peak.
+const AUTO_GAIN_FILL: f32 = 0.9;
+/// Per sample, about half a second to halve at 48 kHz.
+const PEAK_DECAY: f32 = 0.999_971;
+/// Per sample share of the old auto gain kept when a louder peak pulls it
+/// down, settling in about 10 ms at 48 kHz.
+const AUTO_GAIN_ATTACK: f32 = 0.995;
+/// Same when it rises again, over about half a second so it doesn't pump.
+const AUTO_GAIN_RELEASE: f32 = 0.999_8;
+
+/// Number of bands on the mel scale.
+const DEFAULT_MEL_BANDS: usize = 64;
+const MEL_BANDS_RANGE: std::ops::RangeInclusive<f32> = 16.0..=128.;
+
+/// Number of log-spaced bands in the bar view.
+const DEFAULT_BAR_COUNT: usize = 32;
+const BAR_COUNT_RANGE: std::ops::RangeInclusive<f32> = 8.0..=128.;
+/// Share of each band left empty between bars.
+const DEFAULT_BAR_GAP: f32 = 0.2;
+const BAR_GAP_RANGE: std::ops::RangeInclusive<f32> = 0.0..=0.8;
+
+/// How long the beat indicator stays lit after an onset.
+const ONSET_FLASH: Duration = Duration::from_millis(150);
+const ONSET_COLOR: Color = Color::from_rgb(1., 0.8, 0.);
+
+/// Number of past spectra kept for the spectrogram.
+const SPECTROGRAM_HISTORY: usize = 256;
+
+/// Palette for the spectrogram, from silence to full level.
+const HEAT_COLORS: &[[f32; 3]] = &[
+    [0., 0., 0.],
+    [0.3, 0., 0.5],
+    [0.8, 0.1, 0.3],
+    [1., 0.6, 0.],
+    [1., 1., 0.8],
+];
+
+fn heat_color(level: f32) -> [u8; 4] {
+    let position = level.clamp(0., 1.) * (HEAT_COLORS.len() - 1) as f32;
+    let index = (position as usize).min(HEAT_COLORS.len() - 2);
+    let t = position - index as f32;
+    let (low, high) = (HEAT_COLORS[index], HEAT_COLORS[index + 1]);
+    let channel = |c: usize| ((low[c] + (high[c] - low[c]) * t) * 255.) as u8;
+    [channel(0), channel(1), channel(2), 255]
+}
+
+/// Spacing of the horizontal dB gridlines in the spectrum view.
+const DB_GRID_STEP: f32 = 20.;
+/// Levels in dBFS of the gridlines either side of a raw waveform, the ones
+/// off its lane or crowding the louder ones left out.
+const AMPLITUDE_GRID_DB: [f32; 9] = [0., -6., -12., -18., -24., -30., -40., -50., -60.];
+/// Least pixels between two amplitude gridlines.
+const AMPLITUDE_GRID_SPACING: f32 = 16.;
+
+fn frequency_label(freq: f64) -> String {
+    if freq >= 1000. {
+        format!("{}k", freq / 1000.)
+    } else {
+        format!("{freq}")
+    }
+}
+
+/// Frequency as shown by the readouts, to the Hz or the 10 Hz.
+fn readout_frequency(freq: f64) -> String {
+    if freq >= 1000. {
+        format!("{:.2} kHz", freq / 1000.)
+    } else {
+        format!("{freq:.0} Hz")
+    }
+}
+
+/// Colors of the plots, taken from the theme so they stay legible on light
+/// themes too.
+#[derive(Debug, Clone, Copy)]
+struct PlotColors {
+    background: Color,
+    grid: Color,
+    label: Color,
+    /// For the first channels, further ones get generated hues.
+    lines: [Color; 4],
+    custom: CustomColors,
+}
+
+/// How many channels can be given a color of their own.
+const CUSTOM_LINE_COLORS: usize = 8;
+
+/// Colors picked by hand, taking over from the theme's.
+#[derive(Debug, Clone, Copy, Default)]
+struct CustomColors {
+    lines: [Option<Color>; CUSTOM_LINE_COLORS],
+    spectrum: Option<Color>,
+}
+
+impl CustomColors {
+    /// From the hex strings of the settings, leaving out the invalid ones.
+    fn parse(lines: &[String], spectrum: &str) -> Self {
+        let mut custom = Self {
+            spectrum: parse_color(spectrum),
+            ..Self::default()
+        };
+        for (color, input) in custom.lines.iter_mut().zip(lines) {
+            *color = parse_color(input);
+        }
+        custom
+    }
+}
+
+/// `#rrggbb` or one of the other hex forms iced reads.
+fn parse_color(input: &str) -> Option<Color> {
+    let input = input.trim();
+    // iced slices the digits by bytes
+    if !input
+        .trim_start_matches('#')
+        .chars()
+        .all(|digit| digit.is_ascii_hexdigit())
+    {
+        return None;
+    }
+    input.parse().ok()
+}
+
+impl PlotColors {
+    fn from_theme(theme: &Theme) -> Self {
+        let palette = theme.palette();
+        Self {
+            background: palette.background,
+            grid: Color {
+                a: 0.15,
+                ..palette.text
+            },
+            label: Color {
+                a: 0.6,
+                ..palette.text
+            },
+            lines: [
+                palette.text,
+                palette.primary,
+                palette.success,
+                palette.warning,
+            ],
+            custom: CustomColors::default(),
+        }
+    }
+
+    /// The palette first, then hues spaced by the golden angle so every
+    /// further channel stays distinct from the others.
+    fn line(&self, index: usize) -> Color {
+        self.custom
+            .lines
+            .get(index)
+            .copied()
+            .flatten()
+            .or_else(|| self.lines.get(index).copied())
+            .unwrap_or_else(|| {
+                let hue = (index as f32 * 0.618_034).fract();
+                hsv_color(hue, 0.65, 0.95)
+            })
+    }
+
+    /// Of the spectrum of `channel`, the same for all once one is picked.
+    fn spectrum(&self, channel: usize) -> Color {
+        self.custom
+            .spectrum
+            .unwrap_or_else(|| self.line(channel + 1))
+    }
+}
+
+/// `hue` in `0..1`, going around the color wheel from red.
+fn hsv_color(hue: f32, saturation: f32, value: f32) -> Color {
+    let sector = hue * 6.;
+    let fraction = sector.fract();
+    let (p, q, t) = (
+        value * (1. - saturation),
+        value * (1. - saturation * fraction),
+        value * (1. - saturation * (1. - fraction)),
+    );
+    let (r, g, b) = match sector as u32 % 6 {
+        0 => (value, t, p),
+        1 => (q, value, p),
+        2 => (p, value, t),
+        3 => (p, q, value),
+        4 => (t, p, value),
+        _ => (value, p, q),
+    };
+    Color::from_rgb(r, g, b)
+}
+
+/// Name of `channel` in PipeWire's default layout for `channels` channels.
+fn channel_label(channel: usize, channels: usize) -> String {
+    let layout: &[&str] = match channels {
+        1 => &["MONO"],
+        2 => &["FL", "FR"],
+        3 => &["FL", "FR", "LFE"],
+        4 => &["FL", "FR", "RL", "RR"],
+        5 => &["FL", "FR", "FC", "RL", "RR"],
+        6 => &["FL", "FR", "FC", "LFE", "RL", "RR"],
+        8 => &["FL", "FR", "FC", "LFE", "RL", "RR", "SL", "SR"],
+        _ => &[],
+    };
+    layout
+        .get(channel)
+        .map_or_else(|| format!("ch{}", channel + 1), |label| (*label).to_owned())
+}
+
+impl LineDatas {
+    fn new() -> Self {
+        Self {
+            raw_matrix: MatrixFixed::new(RAW_HISTORY_SECONDS * 50000, 2),
+            view_len: DEFAULT_TIME_WINDOW,
+            view_offset: 0,
+            trigger: Trigger::default(),
+            spectrum: SpectrumFrame::default(),
+            rate: 50000,
+            gain: 1.,
+            auto_gain: false,
+            lanes: false,
+            channel_selection: ChannelSelection::default(),
+            smoothing: 0.,
+            bars: false,
+            bar_bands: BarBands::default(),
+            bar_count: DEFAULT_BAR_COUNT,
+            bar_gap: DEFAULT_BAR_GAP,
+            colors: PlotColors::from_theme(&Theme::Moonfly),
+            auto_range: false,
+            auto_db: (-120., 0.),
+            axis_scale: AxisScale::Log,
+            mel_bands: DEFAULT_MEL_BANDS,
+            mel: MelFilterbank::default(),
+            weighting: Weighting::None,
+            bin_mapping: BinMapping::Average,
+            mapping: SpectrumMapping::default(),
+            peak: 0.,
+            smoothed_gain: 1.,
+            log_amplitude: false,
+            log_factor: DEFAULT_LOG_FACTOR,
+            line_width: 3.,
+            auto_thin: false,
+        }
+    }
+    pub fn set_rate(&mut self, rate: u32) {
+        self.rate = rate;
+        self.update_mel();
+        // the history covers the same time at any rate
+        self.raw_matrix.resize(RAW_HISTORY_SECONDS * rate as usize);
+        self.set_view_len(self.view_len);
+    }
+
+    fn set_mel_bands(&mut self, mel_bands: usize) {
+        self.mel_bands = mel_bands;
+        self.update_mel();
+    }
+
+    /// Recomputes the mel filters once the rate, FFT size or band count
+    /// changed.
+    fn update_mel(&mut self) {
+        let fft_size = self.spectrum.fft_size();
+        if !self.mel.matches(self.rate, fft_size, self.mel_bands) {
+            self.mel = MelFilterbank::new(self.rate, fft_size, self.mel_bands);
+        }
+        self.update_mapping();
+    }
+
+    /// Recomputes which bins every display point reads once anything it
+    /// depends on changed, the mel filters have to be up to date.
+    fn update_mapping(&mut self) {
+        let fft_size = self.spectrum.fft_size();
+        let mapping = &self.mapping;
+        if mapping.rate == self.rate
+            && mapping.fft_size == fft_size
+            && mapping.axis_scale == Some(self.axis_scale)
+            && mapping.mel_bands == self.mel_bands
+            && mapping.weighting == self.weighting
+        {
+            return;
+        }
+        let nyquist = self.rate as f64 / 2.;
+        // bin `i` sits at `i * rate / fft_size`
+        let bins_per_hz = fft_size as f64 / self.rate.max(1) as f64;
+        let points = self.num_points();
+        let bins: Vec<PointBins> = match self.axis_scale {
+            AxisScale::Mel => vec![],
+            AxisScale::Linear | AxisScale::Log => (0..points)
+                .map(|point| (self.point_frequency(point), self.point_frequency(point + 1)))
+                .take_while(|(freq, _)| *freq <= nyquist)
+                .map(|(freq, next)| {
+                    let bin = freq * bins_per_hz;
+                    PointBins {
+                        // bins up to the next point would be skipped otherwise
+                        first: bin.ceil() as usize,
+                        last: (next * bins_per_hz).ceil() as usize,
+                        low: bin.floor() as usize,
+                        fraction: bin.fract() as f32,
+                    }
+                })
+                .collect(),
+        };
+        let shown = match self.axis_scale {
+            AxisScale::Mel => points,
+            AxisScale::Linear | AxisScale::Log => bins.len(),
+        };
+        let offsets = (0..shown)
+            .map(|point| self.weighting.offset_db(self.point_frequency(point)) as f32)
+            .collect();
+        self.mapping = SpectrumMapping {
+            rate: self.rate,
+            fft_size,
+            axis_scale: Some(self.axis_scale),
+            mel_bands: self.mel_bands,
+            weighting: self.weighting,
+            points,
+            bins,
+            offsets,
+        };
+    }
+
+    fn set_axis_scale(&mut self, axis_scale: AxisScale) {
+        self.axis_scale = axis_scale;
+        self.update_mapping();
+    }
+
+    fn set_weighting(&mut self, weighting: Weighting) {
+        self.weighting = weighting;
+        self.update_mapping();
+    }
+
+    fn append_data(&mut self, matrix: Matrix) {
+        // buffers vary in size, decay by how much time they cover
+        let decay = PEAK_DECAY.powi(matrix.frame_count() as i32);
+        self.peak = (self.peak * decay).max(matrix.peak());
+        // the top of the manual range keeps silence from being blown up to
+        // full height
+        let target = (AUTO_GAIN_FILL / self.peak).clamp(*GAIN_RANGE.start(), *GAIN_RANGE.end());
+        let keep = if target < self.smoothed_gain {
+            AUTO_GAIN_ATTACK
+        } else {
+            AUTO_GAIN_RELEASE
+        }
+        .powi(matrix.frame_count() as i32);
+        self.smoothed_gain = target + (self.smoothed_gain - target) * keep;
+        // scrolled back, the view stays on the same samples
+        if self.view_offset > 0 {
+            self.set_view_offset(self.view_offset + matrix.frame_count());
+        }
+        // buffers still in flight from before a format change are dropped,
+        // the matrix was already reset for the new channel count
+        let _ = self.raw_matrix.append(matrix);
+    }
+
+    /// Indices of the samples in view.
+    fn view_range(&self) -> std::ops::Range<usize> {
+        let end = self.raw_matrix.len().saturating_sub(self.view_offset);
+        let start = end.saturating_sub(self.view_len);
+        if self.view_offset == 0
+            && let Some(start) = self.trigger_start(start)
+        {
+            return start..start + self.view_len;
+        }
+        start..end
+    }
+
+    /// Latest trigger crossing at or before `latest`, looking back at most
+    /// one view.
+    fn trigger_start(&self, latest: usize) -> Option<usize> {
+        if !self.trigger.enabled || self.trigger.channel >= self.raw_matrix.channel() {
+            return None;
+        }
+        let samples = &self.raw_matrix[self.trigger.channel];
+        let earliest = latest.saturating_sub(self.view_len).max(1);
+        (earliest..=latest)
+            .rev()
+            .find(|index| self.trigger.fires(samples[index - 1], samples[*index]))
+    }
+
+    fn set_view_len(&mut self, len: usize) {
+        self.view_len = len.clamp(MIN_TIME_WINDOW, self.raw_matrix.len().max(MIN_TIME_WINDOW));
+        self.set_view_offset(self.view_offset);
+    }
+
+    /// Scrolls back by `offset` samples, no further than the oldest one kept.
+    fn set_view_offset(&mut self, offset: usize) {
+        self.view_offset = offset.min(self.raw_matrix.len().saturating_sub(self.view_len));
+    }
+
+    /// Writes the current spectrum as CSV, the frequency of each bin followed
+    /// by its level in dB for every channel, up to nyquist.
+    fn write_spectrum_csv(&self, path: &std::path::Path) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let channels = self.spectrum.channels();
+        write!(file, "frequency_hz")?;
+        for channel in 0..channels.len() {
+            write!(file, ",channel_{channel}_db")?;
+        }
+        writeln!(file)?;
+        let fft_size = self.spectrum.fft_size();
+        let bins = channels.iter().map(Vec::len).min().unwrap_or(0);
+        for bin in 0..bins.min(spectrum_bins(fft_size)) {
+            write!(file, "{}", bin as f64 * self.rate as f64 / fft_size as f64)?;
+            for channel in channels {
+                write!(file, ",{}", channel[bin])?;
+            }
+            writeln!(file)?;
+        }
+        file.flush()
+    }
+
+    /// Channels making up every waveform drawn, the one it is named after
+    /// first.
+    fn channel_groups(&self) -> Vec<Vec<usize>> {
+        self.channel_selection.groups(self.raw_matrix.channel())
+    }
+
+    /// Channel whose color the spectrum of `index` takes.
+    fn spectrum_channel(&self, index: usize) -> usize {
+        self.channel_groups()
+            .get(index)
+            .map_or(index, |group| group[0])
+    }
+
+    fn lane_height(&self, height: f32) -> f32 {
+        if self.lanes {
+            let lanes = self.channel_selection.shown(self.raw_matrix.channel());
+            height / lanes.max(1) as f32
+        } else {
+            height
+        }
+    }
+
+    /// Width of a waveform of `points` across `width` pixels.
+    fn stroke_width(&self, points: usize, width: f32) -> f32 {
+        if self.auto_thin && points as f32 > width {
+            self.line_width.min(1.)
+        } else {
+            self.line_width
+        }
+    }
+
+    /// Vertical center of the waveform in `lane`.
+    fn lane_center(&self, lane: usize, height: f32) -> f32 {
+        if self.lanes {
+            (lane as f32 + 0.5) * self.lane_height(height)
+        } else {
+            height / 2.
+        }
+    }
+
+    /// Horizontal spans of the raw view where any channel of `group` clips,
+    /// at least a couple of pixels wide so single samples show up.
+    fn clipped_spans(&self, group: &[usize], width: f32) -> Vec<(f32, f32)> {
+        let step = width / self.view_len.max(1) as f32;
+        let view = self.view_range();
+        let mut spans: Vec<(f32, f32)> = vec![];
+        for index in 0..view.len() {
+            let clipped = group.iter().any(|channel| {
+                self.raw_matrix[*channel][view.start + index].abs() >= CLIP_THRESHOLD
+            });
+            if !clipped {
+                continue;
+            }
+            let start = index as f32 * step;
+            let end = start + step.max(2.);
+            match spans.last_mut() {
+                Some(span) if span.1 >= start => span.1 = end,
+                _ => spans.push((start, end)),
+            }
+        }
+        spans
+    }
+
+    /// `sample` as drawn by the raw view, compressed as
+    /// `sign(x) * log1p(k * |x|) / log1p(k)` while `log_amplitude` is on.
+    /// Full scale stays at full scale.
+    fn amplitude(&self, sample: f32) -> f32 {
+        if !self.log_amplitude {
+            return sample;
+        }
+        sample.signum() * (self.log_factor * sample.abs()).ln_1p() / self.log_factor.ln_1p()
+    }
+    fn waveform_gain(&self) -> f32 {
+        if self.auto_gain {
+            self.smoothed_gain
+        } else {
+            self.gain
+        }
+    }
+    /// Stores a new spectrum, averaged with the previous one by `smoothing`.
+    pub fn set_spectrum(&mut self, mut spectrum: SpectrumFrame) {
+        let old = self.spectrum.channels();
+        // a different FFT size, channel count or kind starts the average over
+        let same_shape = self.spectrum.kind() == spectrum.kind()
+            && old.len() == spectrum.channels().len()
+            && old
+                .iter()
+                .zip(spectrum.channels())
+                .all(|(old, new)| old.len() == new.len());
+        if self.smoothing > 0. && same_shape {
+            for (new, old) in spectrum.channels_mut().iter_mut().zip(old) {
+                for (new, old) in new.iter_mut().zip(old) {
+                    *new = *new * (1. - self.smoothing) + old * self.smoothing;
+                }
+            }
+        }
+        self.spectrum = spectrum;
+        self.update_mel();
+        if self.auto_range {
+            self.track_range();
+        }
+    }
+
+    /// Moves the auto range toward the levels of the current spectrum, a
+    /// limited step at a time so the axis doesn't jump every frame.
+    fn track_range(&mut self) {
+        let (low, high) = self
+            .spectrum
+            .channels()
+            .iter()
+            .flatten()
+            .fold((f32::MAX, f32::MIN), |(low, high), db| {
+                (low.min(*db), high.max(*db))
+            });
+        if low > high {
+            return;
+        }
+        let high = high + AUTO_RANGE_HEADROOM;
+        let low = low.min(high - AUTO_RANGE_MIN_SPAN);
+        let step = |current: f32, target: f32| {
+            current + (target - current).clamp(-AUTO_RANGE_SLEW, AUTO_RANGE_SLEW)
+        };
+        self.auto_db = (step(self.auto_db.0, low), step(self.auto_db.1, high));
+    }
+
+    /// dB values shown at the bottom and the top of the spectrum.
+    fn db_range(&self) -> (f32, f32) {
+        if self.auto_range {
+            self.auto_db
+        } else {
+            (self.spectrum.min_db(), self.spectrum.max_db())
+        }
+    }
+    fn reset_matrix(&mut self, len: usize, channel: usize) {
+        self.raw_matrix = MatrixFixed::new(len, channel);
+    }
+
+    fn num_points(&self) -> usize {
+        match self.axis_scale {
+            AxisScale::Mel => self.mel_bands,
+            AxisScale::Log => log_points(self.rate),
+            // more points than bins would interpolate between the same ones
+            AxisScale::Linear => {
+                log_points(self.rate).min(spectrum_bins(self.spectrum.fft_size()).max(MIN_POINTS))
+            }
+        }
+    }
+
+    /// Frequency shown at display point `point`. On the log scale it starts
+    /// at `MIN_FREQ` with `POINTS_PER_OCTAVE` points per octave, on the linear
+    /// one the points are spread evenly up to nyquist, on the mel one they are
+    /// the band centers.
+    fn point_frequency(&self, point: usize) -> f64 {
+        match self.axis_scale {
+            AxisScale::Mel => self.mel.center(point),
+            AxisScale::Log => 10_f64
+                .powf(MIN_FREQ.log10() + point as f64 / POINTS_PER_OCTAVE as f64 * 2_f64.log10()),
+            AxisScale::Linear => point as f64 / self.num_points() as f64 * self.rate as f64 / 2.,
+        }
+    }
+
+    /// Horizontal position of `freq`, the inverse of `point_frequency`.
+    fn frequency_x(&self, freq: f64, width: f32) -> f32 {
+        let point = match self.axis_scale {
+            AxisScale::Log => (freq / MIN_FREQ).log2() * POINTS_PER_OCTAVE as f64,
+            AxisScale::Linear => freq / (self.rate as f64 / 2.) * self.num_points() as f64,
+            AxisScale::Mel => self.mel.position(freq),
+        };
+        point as f32 * width / self.num_points() as f32
+    }
+
+    /// Frequency at horizontal position `x`, the inverse of `frequency_x`.
+    fn x_frequency(&self, x: f32, width: f32) -> f64 {
+        let point = (x / width.max(1.) * self.num_points() as f32) as f64;
+        match self.axis_scale {
+            AxisScale::Log => MIN_FREQ * 2_f64.powf(point / POINTS_PER_OCTAVE as f64),
+            AxisScale::Linear => point / self.num_points() as f64 * self.rate as f64 / 2.,
+            AxisScale::Mel => self.mel.frequency(point),
+        }
+    }
+
+    /// Level in dB at `freq` of the loudest channel, interpolated between the
+    /// two bins around it and weighted like the curve.
+    fn frequency_db(&self, freq: f64) -> Option<f32> {
+        let bin = freq * self.spectrum.fft_size() as f64 / self.rate.max(1) as f64;
+        let low = bin.floor() as usize;
+        let fraction = (bin - low as f64) as f32;
+        let db = self
+            .spectrum
+            .channels()
+            .iter()
+            .filter_map(|channel| {
+                let below = *channel.get(low)?;
+                let above = channel.get(low + 1).copied().unwrap_or(below);
+                Some(below + (above - below) * fraction)
+            })
+            .reduce(f32::max)?;
+        Some(db + self.weighting.offset_db(freq) as f32)
+    }
+
+    /// Frequencies of the vertical gridlines, and whether each gets a label.
+    fn frequency_ticks(&self) -> Vec<(f64, bool)> {
+        let nyquist = self.rate as f64 / 2.;
+        let mut ticks = vec![];
+        match self.axis_scale {
+            AxisScale::Log | AxisScale::Mel => {
+                let mut decade = 10_f64.powf(MIN_FREQ.log10().floor());
+                while decade <= nyquist {
+                    for multiple in 1..10 {
+                        let freq = decade * multiple as f64;
+                        if (MIN_FREQ..=nyquist).contains(&freq) {
+                            ticks.push((freq, multiple == 1));
+                        }
+                    }
+                    decade *= 10.;
+                }
+            }
+            AxisScale::Linear => {
+                // a 1, 2 or 5 step giving about ten lines
+                let rough = nyquist / 10.;
+                let magnitude = 10_f64.powf(rough.log10().floor());
+                let step = [1., 2., 5., 10.]
+                    .into_iter()
+                    .map(|factor| factor * magnitude)
+                    .find(|step| *step >= rough)
+                    .unwrap_or(magnitude * 10.);
+                let mut freq = step;
+                while freq <= nyquist {
+                    ticks.push((freq, true));
+                    freq += step;
+                }
+            }
+        }
+        ticks
+    }
+
+    /// Levels in `0..=1` of one channel, one per display point.
+    fn spectrum_levels(&self, channel: &[f32]) -> Vec<f32> {
+        let (min_db, max_db) = self.db_range();
+        let range = (max_db - min_db).max(f32::EPSILON);
+        let offsets = &self.mapping.offsets;
+        let levels: Vec<f32> = match self.axis_scale {
+            AxisScale::Mel => self
+                .mel
+                .apply(channel, self.spectrum.min_db())
+                .into_iter()
+                .zip(offsets)
+                .map(|(db, weighting)| db + weighting)
+                .collect(),
+            AxisScale::Linear | AxisScale::Log => self
+                .mapping
+                .bins
+                .iter()
+                .zip(offsets)
+                .filter_map(|(point, weighting)| {
+                    let (first, last) = (point.first, point.last.min(channel.len()));
+                    if last > first + 1 {
+                        let bins = &channel[first..last];
+                        let db = match self.bin_mapping {
+                            BinMapping::Average => {
+                                let power =
+                                    bins.iter().map(|db| 10_f32.powf(db / 10.)).sum::<f32>();
+                                10. * (power / bins.len() as f32).log10()
+                            }
+                            BinMapping::Peak => {
+                                bins.iter().copied().fold(f32::NEG_INFINITY, f32::max)
+                            }
+                        };
+                        return Some(db + weighting);
+                    }
+                    // interpolate between the two bins around the frequency
+                    let below = *channel.get(point.low)?;
+                    let above = channel.get(point.low + 1).copied().unwrap_or(below);
+                    Some(below + (above - below) * point.fraction + weighting)
+                })
+                .collect(),
+        };
+        levels
+            .into_iter()
+            .map(|db| ((db - min_db) / range).clamp(0., 1.))
+            .collect()
+    }
+
+    /// Nominal center and edges in Hz of the ISO bands of `bar_bands` below
+    /// nyquist, none for `BarBands::Even`.
+    fn iso_bands(&self) -> Vec<(f64, f64, f64)> {
+        let (first, step, half_width) = match self.bar_bands {
+            BarBands::Even => return vec![],
+            BarBands::Octave => (1, 3, 1. / 2.),
+            BarBands::ThirdOctave => (0, 1, 1. / 6.),
+        };
+        let nyquist = self.rate as f64 / 2.;
+        THIRD_OCTAVE_CENTERS
+            .iter()
+            .enumerate()
+            .skip(first)
+            .step_by(step)
+            .map(|(index, nominal)| {
+                let thirds = index as f64 - THIRD_OCTAVE_REFERENCE as f64;
+                let center = 1000. * 2_f64.powf(thirds / 3.);
+                (
+                    *nominal,
+                    center * 2_f64.powf(-half_width),
+                    center * 2_f64.powf(half_width),
+                )
+            })
+            .take_while(|(nominal, _, _)| *nominal < nyquist)
+            .collect()
+    }
+
+    /// Levels in `0..=1` of the bands of `bar_bands` in one channel, together
+    /// with the band edges in Hz. ISO bands sum the power of their bins,
+    /// even ones take their loudest bin.
+    fn band_levels(&self, index: usize) -> Vec<(f64, f64, f32)> {
+        let (min_db, max_db) = self.db_range();
+        let range = (max_db - min_db).max(f32::EPSILON);
+        let Some(channel) = self.spectrum.channels().get(index) else {
+            return vec![];
+        };
+        let bins_per_hz = self.spectrum.fft_size() as f64 / self.rate.max(1) as f64;
+        if self.bar_bands != BarBands::Even {
+            return self
+                .iso_bands()
+                .into_iter()
+                .filter_map(|(_, low, high)| {
+                    // bands narrower than a bin get the nearest one
+                    let center = (low * high).sqrt();
+                    let db = self
+                        .spectrum
+                        .band_level(index, self.rate, low, high)
+                        .or_else(|| {
+                            channel
+                                .get((center * bins_per_hz).round() as usize)
+                                .copied()
+                        })?;
+                    let weighting = self.weighting.offset_db(center) as f32;
+                    Some((low, high, ((db + weighting - min_db) / range).clamp(0., 1.)))
+                })
+                .collect();
+        }
+        let octaves = octaves(self.rate);
+        let edge =
+            |band: usize| MIN_FREQ * 2_f64.powf(octaves * band as f64 / self.bar_count as f64);
+        (0..self.bar_count)
+            .filter_map(|band| {
+                let (low, high) = (edge(band), edge(band + 1));
+                // the lowest bands can be narrower than a bin, they get the
+                // nearest one
+                let first = (low * bins_per_hz).round() as usize;
+                let last = ((high * bins_per_hz).round() as usize).max(first + 1);
+                let db = channel
+                    .get(first..last.min(channel.len()))?
+                    .iter()
+                    .copied()
+                    .reduce(f32::max)?;
+                let weighting = self.weighting.offset_db((low * high).sqrt()) as f32;
+                Some((low, high, ((db + weighting - min_db) / range).clamp(0., 1.)))
+            })
+            .collect()
+    }
+
+    fn generate_bars(&self, size: iced::Size) -> Vec<BarData> {
+        let channels = self.spectrum.channels();
+        let alpha = if channels.len() > 1 { 0.5 } else { 1. };
+        // same baseline as the curve
+        let bottom = size.height - 2.;
+        (0..channels.len())
+            .map(|index| {
+                let bars = self
+                    .band_levels(index)
+                    .into_iter()
+                    .map(|(low, high, level)| {
+                        let left = self.frequency_x(low, size.width);
+                        let width = self.frequency_x(high, size.width) - left;
+                        let height = level * size.height;
+                        Rectangle {
+                            x: left + width * self.bar_gap / 2.,
+                            y: bottom - height,
+                            width: width * (1. - self.bar_gap),
+                            height,
+                        }
+                    })
+                    .collect();
+                BarData {
+                    bars,
+                    color: Color {
+                        a: alpha,
+                        ..self.colors.spectrum(self.spectrum_channel(index))
+                    },
+                }
+            })
+            .collect()
+    }
+
+    /// Loudest level across channels, one per display point.
+    fn spectrogram_row(&self) -> Vec<f32> {
+        let mut row = vec![0_f32; self.num_points()];
+        for channel in self.spectrum.channels() {
+            for (value, level) in row.iter_mut().zip(self.spectrum_levels(channel)) {
+                *value = value.max(level);
+            }
+        }
+        row
+    }
+
+    fn generate_spectrum(&self, size: iced::Size) -> Vec<LineData> {
+        let step = size.width / self.mapping.points.max(1) as f32;
+        let channels = self.spectrum.channels();
+        // let overlapping channels show through each other
+        let alpha = if channels.len() > 1 { 0.5 } else { 1. };
+        let mut output: Vec<LineData> = vec![];
+        for (index, channel) in channels.iter().enumerate() {
+            let color = Color {
+                a: alpha,
+                ..self.colors.spectrum(self.spectrum_channel(index))
+            };
+            let data: Vec<Point> = self
+                .spectrum_levels(channel)
+                .iter()
+                .enumerate()
+                .map(|(index, level)| Point::new(index as f32 * step, -level * size.height))
+                .collect();
+            output.push(LineData { data, color });
+        }
+        output
+    }
+
+    fn generate_raw_datas(&self, size: iced::Size) -> Vec<LineData> {
+        let view = self.view_range();
+        let len = view.len();
+        let width = size.width;
+        let step = width / len as f32;
+        // negative so positive samples go up
+        let scale = -self.lane_height(size.height) / 2. * self.waveform_gain();
+        // with more than two samples per pixel, draw each pixel column as a
+        // line from its lowest to its highest sample, keeping the peaks
+        let columns = width.max(1.) as usize;
+        let decimate = len > columns * 2;
+        let mut output: Vec<LineData> = vec![];
+        for group in self.channel_groups() {
+            let mixed: Vec<f32>;
+            let data = if let [channel] = group[..] {
+                &self.raw_matrix[channel][view.clone()]
+            } else {
+                let gain = 1. / group.len() as f32;
+                mixed = view
+                    .clone()
+                    .map(|index| {
+                        group
+                            .iter()
+                            .map(|channel| self.raw_matrix[*channel][index])
+                            .sum::<f32>()
+                            * gain
+                    })
+                    .collect();
+                &mixed
+            };
+            let color = self.colors.line(group[0]);
+            let data: Vec<Point> = if decimate {
+                (0..columns)
+                    .flat_map(|column| {
+                        let samples =
+                            data[column * len / columns..(column + 1) * len / columns].iter();
+                        let (min, max) = samples.fold((f32::MAX, f32::MIN), |(min, max), wav| {
+                            (min.min(*wav), max.max(*wav))
+                        });
+                        let x = column as f32 * width / columns as f32;
+                        // monotonic, so the extremes stay the extremes
+                        [
+                            Point::new(x, self.amplitude(min) * scale),
+                            Point::new(x, self.amplitude(max) * scale),
+                        ]
+                    })
+                    .collect()
+            } else {
+                data.iter()
+                    .enumerate()
+                    .map(|(index, wav)| {
+                        Point::new(index as f32 * step, self.amplitude(*wav) * scale)
+                    })
+                    .collect()
+            };
+            output.push(LineData { data, color });
+        }
+        output
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    line_cache: canvas::Cache,
+    // the grid only changes with the rate, the dB range or the size
+    axis_cache: canvas::Cache,
+    // the raw view's grid follows the gain, lanes and amplitude scale
+    amplitude_cache: canvas::Cache,
+    spectrogram_cache: canvas::Cache,
+    data: LineDatas,
+    show_type: ShowType,
+    spectrogram: VecDeque<Vec<f32>>,
+    spectrogram_image: Option<image::Handle>,
+    /// Highest dB seen per bin across channels, falling by `PEAK_HOLD_DECAY`.
+    peak_hold: Vec<f32>,
+    show_peak_hold: bool,
+    /// Average dB per bin across channels of the spectra captured as the
+    /// background, one per bin of the current FFT size.
+    noise_floor: Vec<f32>,
+    /// Spectra averaged into `noise_floor` so far.
+    noise_floor_frames: usize,
+    /// When the running noise floor capture started.
+    noise_floor_capture: Option<Instant>,
+    /// Take the power of the noise floor out of every spectrum, so only what
+    /// rises above it shows.
+    subtract_noise_floor: bool,
+    /// dB per bin of a spectrum kept to compare the live one against,
+    /// averaged over the channels by power.
+    snapshot: Vec<f32>,
+    /// Fill the spectrum with a gradient and a glowing edge, flat otherwise.
+    spectrum_gradient: bool,
+    spectrum_orientation: SpectrumOrientation,
+    /// Mark the harmonics of the fundamental picked on the spectrum, or of
+    /// the detected `pitch` while none is.
+    show_harmonics: bool,
+    pitch: Option<f64>,
+    /// Mark the spectral centroid, the magnitude weighted mean frequency of
+    /// the spectrum, `None` in silence.
+    show_centroid: bool,
+    centroid: Option<f64>,
+    last_decay: Option<Instant>,
+    /// Bumped whenever the lines need generating again.
+    revision: u64,
+}
+
+/// Harmonics marked on the spectrum, counting the fundamental.
+const MAX_HARMONICS: usize = 16;
+const HARMONIC_COLOR: Color = Color::from_rgb(0.4, 0.9, 0.6);
+const CENTROID_COLOR: Color = Color::from_rgb(0.9, 0.5, 1.);
+/// Pixels either side of a right click searched for the peak it meant.
+const PEAK_SNAP: f32 = 8.;
+
+/// How fast the spectrum peak hold falls, in dB per second.
+const PEAK_HOLD_DECAY: f32 = 20.;
+const PEAK_HOLD_COLOR: Color = Color::from_rgb(1., 0.4, 0.2);
+
+/// How long the spectra making up a noise floor are averaged.
+const NOISE_FLOOR_CAPTURE: Duration = Duration::from_secs(1);
+const NOISE_FLOOR_COLOR: Color = Color::from_rgba(0.7, 0.7, 0.7, 0.4);
+const SNAPSHOT_COLOR: Color = Color::from_rgb(1., 0.85, 0.2);
+
+/// `levels` spread over `bins` bins covering the same frequencies, for the
+/// same rate at another FFT size.
+fn resample_bins(levels: &[f32], bins: usize) -> Vec<f32> {
+    let Some(last) = levels.len().checked_sub(1) else {
+        return vec![];
+    };
+    let scale = last as f32 / bins.saturating_sub(1).max(1) as f32;
+    (0..bins)
+        .map(|bin| {
+            let position = bin as f32 * scale;
+            let low = (position as usize).min(last);
+            let above = levels.get(low + 1).copied().unwrap_or(levels[low]);
+            levels[low] + (above - levels[low]) * position.fract()
+        })
+        .collect()
+}
+
+/// Takes the power of `floor` out of every bin of `channel`, the bins not
+/// above it drop to `bottom`.
+fn subtract_floor(channel: &mut [f32], floor: &[f32], bottom: f32) {
+    for (db, floor) in channel.iter_mut().zip(floor) {
+        let power = 10_f32.powf(*db / 10.) - 10_f32.powf(floor / 10.);
+        *db = if power > 0. {
+            (10. * power.log10()).max(bottom)
+        } else {
+            bottom
+        };
+    }
+}
+
+impl State {
+    pub fn new() -> State {
+        State {
+            line_cache: canvas::Cache::default(),
+            axis_cache: canvas::Cache::default(),
+            amplitude_cache: canvas::Cache::default(),
+            spectrogram_cache: canvas::Cache::default(),
+            data: LineDatas::new(),
+            show_type: ShowType::Raw,
+            spectrogram: VecDeque::with_capacity(SPECTROGRAM_HISTORY + 1),
+            spectrogram_image: None,
+            peak_hold: vec![],
+            show_peak_hold: false,
+            noise_floor: vec![],
+            noise_floor_frames: 0,
+            noise_floor_capture: None,
+            subtract_noise_floor: false,
+            snapshot: vec![],
+            spectrum_gradient: true,
+            spectrum_orientation: SpectrumOrientation::default(),
+            show_harmonics: false,
+            show_centroid: false,
+            centroid: None,
+            pitch: None,
+            last_decay: None,
+            revision: 0,
+        }
+    }
+
+    fn invalidate(&mut self) {
+        self.revision = self.revision.wrapping_add(1);
+        self.line_cache.clear();
+    }
+
+    pub fn set_rate(&mut self, rate: u32) {
+        if rate == self.data.rate {
+            return;
+        }
+        self.data.set_rate(rate);
+        self.axis_cache.clear();
+        // the bins of the old rate sit at other frequencies
+        self.spectrogram.clear();
+        self.noise_floor.clear();
+        self.snapshot.clear();
+        self.clear_spectrum();
+    }
+
+    pub fn set_spectrum(&mut self, mut spectrum: SpectrumFrame) {
+        let old_range = self.data.db_range();
+        let old_kind = self.data.spectrum.kind();
+        // levels of another kind are on another scale
+        if spectrum.kind() != old_kind {
+            self.noise_floor.clear();
+            self.noise_floor_frames = 0;
+            self.snapshot.clear();
+        }
+        self.update_noise_floor(&spectrum);
+        if self.subtract_noise_floor && !self.capturing_noise_floor() {
+            let bottom = spectrum.min_db();
+            for channel in spectrum.channels_mut() {
+                subtract_floor(channel, &self.noise_floor, bottom);
+            }
+        }
+        self.data.set_spectrum(spectrum);
+        if self.data.db_range() != old_range || self.data.spectrum.kind() != old_kind {
+            self.axis_cache.clear();
+        }
+        self.update_peak_hold();
+        self.centroid = self.data.spectrum.centroid(self.data.rate);
+        let bins = self.data.spectrum.channels().iter().map(Vec::len).min();
+        // a new FFT size, the same frequencies fall on other bins
+        if let Some(bins) = bins
+            && !self.snapshot.is_empty()
+            && self.snapshot.len() != bins
+        {
+            self.snapshot = resample_bins(&self.snapshot, bins);
+        }
+        self.push_spectrogram_row(self.data.spectrogram_row());
+    }
+
+    fn update_peak_hold(&mut self) {
+        let channels = self.data.spectrum.channels();
+        let bins = channels.iter().map(Vec::len).min().unwrap_or(0);
+        // a new FFT size has different bins
+        if self.peak_hold.len() != bins {
+            self.peak_hold = vec![f32::NEG_INFINITY; bins];
+        }
+        for channel in channels {
+            for (peak, db) in self.peak_hold.iter_mut().zip(channel) {
+                *peak = peak.max(*db);
+            }
+        }
+    }
+
+    /// Keeps the spectrum shown now to draw over the live one.
+    pub fn snapshot_spectrum(&mut self) {
+        let channels = self.data.spectrum.channels();
+        let bins = channels.iter().map(Vec::len).min().unwrap_or(0);
+        self.snapshot = (0..bins)
+            .map(|bin| {
+                let power = channels
+                    .iter()
+                    .map(|channel| 10_f32.powf(channel[bin] / 10.))
+                    .sum::<f32>();
+                10. * (power / channels.len() as f32).log10()
+            })
+            .collect();
+        self.invalidate();
+    }
+
+    pub fn clear_snapshot(&mut self) {
+        self.snapshot.clear();
+        self.invalidate();
+    }
+
+    /// Starts averaging the spectra of the next `NOISE_FLOOR_CAPTURE` into a
+    /// new noise floor.
+    pub fn capture_noise_floor(&mut self) {
+        self.noise_floor.clear();
+        self.noise_floor_frames = 0;
+        self.noise_floor_capture = Some(Instant::now());
+    }
+
+    pub fn capturing_noise_floor(&self) -> bool {
+        self.noise_floor_capture.is_some()
+    }
+
+    /// Averages `spectrum` into the noise floor while capturing, and keeps a
+    /// captured floor at the bin count of the FFT size.
+    fn update_noise_floor(&mut self, spectrum: &SpectrumFrame) {
+        let channels = spectrum.channels();
+        let bins = channels.iter().map(Vec::len).min().unwrap_or(0);
+        let Some(started) = self.noise_floor_capture else {
+            if !self.noise_floor.is_empty() && self.noise_floor.len() != bins {
+                self.noise_floor = resample_bins(&self.noise_floor, bins);
+            }
+            return;
+        };
+        // spectra only count once there are some, the idle gate can hold
+        // them back
+        if self.noise_floor_frames > 0 && started.elapsed() >= NOISE_FLOOR_CAPTURE {
+            self.noise_floor_capture = None;
+            return;
+        }
+        // a new FFT size mid capture starts it over
+        if self.noise_floor.len() != bins {
+            self.noise_floor = vec![f32::NEG_INFINITY; bins];
+            self.noise_floor_frames = 0;
+        }
+        for channel in channels {
+            let count = self.noise_floor_frames as f32;
+            for (floor, db) in self.noise_floor.iter_mut().zip(channel) {
+                // average the power, not the dB
+                let power =
+                    (10_f32.powf(*floor / 10.) * count + 10_f32.powf(db / 10.)) / (count + 1.);
+                *floor = 10. * power.log10();
+            }
+            self.noise_floor_frames += 1;
+        }
+    }
+
+    fn decay_peak_hold(&mut self) {
+        let now = Instant::now();
+        let elapsed = self
+            .last_decay
+            .map_or(0., |last| (now - last).as_secs_f32());
+        self.last_decay = Some(now);
+        for peak in &mut self.peak_hold {
+            *peak -= PEAK_HOLD_DECAY * elapsed;
+        }
+    }
+
+    pub fn set_show_peak_hold(&mut self, show_peak_hold: bool) {
+        self.show_peak_hold = show_peak_hold;
+        self.peak_hold.clear();
+    }
+
+    fn clear_spectrum(&mut self) {
+        self.data.set_spectrum(SpectrumFrame::default());
+        self.peak_hold.clear();
+        self.centroid = None;
+        self.invalidate();
+    }
+
+    /// Adds the newest spectrum at the bottom of the spectrogram, dropping the
+    /// oldest one, and only then redraws it.
+    fn push_spectrogram_row(&mut self, row: Vec<f32>) {
+        let width = row.len();
+        if self
+            .spectrogram
+            .front()
+            .is_some_and(|old| old.len() != width)
+        {
+            self.spectrogram.clear();
+        }
+        while self.spectrogram.len() < SPECTROGRAM_HISTORY {
+            self.spectrogram.push_front(vec![0.; width]);
+        }
+        self.spectrogram.push_back(row);
+        self.spectrogram.pop_front();
+
+        let pixels: Vec<u8> = self
+            .spectrogram
+            .iter()
+            .flat_map(|row| row.iter().flat_map(|level| heat_color(*level)))
+            .collect();
+        self.spectrogram_image = Some(image::Handle::from_rgba(
+            width as u32,
+            SPECTROGRAM_HISTORY as u32,
+            pixels,
+        ));
+        self.spectrogram_cache.clear();
+    }
+
+    /// Gridlines and labels at decade frequencies and every `DB_GRID_STEP`,
+    /// placed the same way as the spectrum curve.
+    fn draw_spectrum_axes(&self, frame: &mut canvas::Frame) {
+        let width = frame.width();
+        // the curve sits 2 pixels above the bottom edge
+        let bottom = frame.height() - 2.;
+        let grid = Stroke {
+            width: 1.,
+            style: stroke::Style::Solid(self.data.colors.grid),
+            ..Default::default()
+        };
+        let label = |content: String, position: Point| canvas::Text {
+            content,
+            position,
+            color: self.data.colors.label,
+            size: 12.into(),
+            ..Default::default()
+        };
+
+        let orientation = self.spectrum_orientation;
+        let height = frame.height();
+        for (freq, labeled) in self.data.frequency_ticks() {
+            let x = self.data.frequency_x(freq, width);
+            frame.stroke(&Path::line(Point::new(x, 0.), Point::new(x, height)), grid);
+            if labeled {
+                // next to the baseline, once even when mirrored
+                let y = orientation.place(bottom - 14., height)[0];
+                frame.fill_text(label(frequency_label(freq), Point::new(x + 2., y)));
+            }
+        }
+
+        let (min_db, max_db) = self.data.db_range();
+        let range = max_db - min_db;
+        if range <= 0. {
+            return;
+        }
+        let unit = self.data.spectrum.kind().unit();
+        // round steps, the auto range rarely ends on one
+        let mut db = (max_db / DB_GRID_STEP).floor() * DB_GRID_STEP;
+        while db >= min_db {
+            let y = bottom - (db - min_db) / range * height;
+            for y in orientation.place(y, height) {
+                frame.stroke(&Path::line(Point::new(0., y), Point::new(width, y)), grid);
+                frame.fill_text(label(format!("{db:.0} {unit}"), Point::new(2., y + 2.)));
+            }
+            db -= DB_GRID_STEP;
+        }
+    }
+
+    /// Lines at round levels above and below the middle of every lane of the
+    /// raw view, where samples of that level are drawn at the current gain,
+    /// labelled in full scale and dBFS.
+    fn draw_amplitude_axes(&self, frame: &mut canvas::Frame) {
+        let width = frame.width();
+        let lane_height = self.data.lane_height(frame.height());
+        let scale = lane_height / 2. * self.data.waveform_gain();
+        let lanes = if self.data.lanes {
+            self.data
+                .channel_selection
+                .shown(self.data.raw_matrix.channel())
+        } else {
+            1
+        };
+        let grid = Stroke {
+            width: 1.,
+            style: stroke::Style::Solid(self.data.colors.grid),
+            ..Default::default()
+        };
+        let label = |content: String, y: f32| canvas::Text {
+            content,
+            position: Point::new(width - 4., y + 2.),
+            color: self.data.colors.label,
+            size: 10.into(),
+            align_x: iced::widget::text::Alignment::Right,
+            ..Default::default()
+        };
+        for lane in 0..lanes {
+            let center = self.data.lane_center(lane, frame.height());
+            frame.stroke(
+                &Path::line(Point::new(0., center), Point::new(width, center)),
+                grid,
+            );
+            frame.fill_text(label("0".to_owned(), center));
+            let mut last_offset = 0_f32;
+            // outwards from the middle, until the levels leave the lane
+            for db in AMPLITUDE_GRID_DB.into_iter().rev() {
+                let level = 10_f32.powf(db / 20.);
+                let offset = self.data.amplitude(level) * scale;
+                if offset > lane_height / 2. {
+                    break;
+                }
+                if offset - last_offset < AMPLITUDE_GRID_SPACING {
+                    continue;
+                }
+                last_offset = offset;
+                for (sign, y) in [('+', center - offset), ('-', center + offset)] {
+                    frame.stroke(&Path::line(Point::new(0., y), Point::new(width, y)), grid);
+                    frame.fill_text(label(format!("{sign}{level:.2} · {db:.0} dB"), y));
+                }
+            }
+        }
+    }
+
+    /// Shades the selected band and labels it with its level in every
+    /// channel.
+    fn draw_band(
+        &self,
+        renderer: &Renderer,
+        bounds: Rectangle,
+        (start, end): (f64, f64),
+    ) -> Geometry {
+        let colors = self.data.colors;
+        let (low, high) = (start.min(end), start.max(end));
+        let mut frame = canvas::Frame::new(renderer, bounds.size());
+        let left = self.data.frequency_x(low, frame.width());
+        let right = self.data.frequency_x(high, frame.width());
+        frame.fill_rectangle(
+            Point::new(left, 0.),
+            iced::Size::new(right - left, frame.height()),
+            Color {
+                a: 0.15,
+                ..colors.label
+            },
+        );
+        let channels = self.data.spectrum.channels().len();
+        let levels: Vec<String> = (0..channels)
+            .map(|channel| {
+                let level = self
+                    .data
+                    .spectrum
+                    .band_level(channel, self.data.rate, low, high)
+                    .map_or_else(|| "-".to_owned(), |db| format!("{db:.1} dB"));
+                if channels > 1 {
+                    format!("{} {level}", channel_label(channel, channels))
+                } else {
+                    level
+                }
+            })
+            .collect();
+        frame.fill_text(canvas::Text {
+            content: format!(
+                "{} – {}: {}",
+                readout_frequency(low),
+                readout_frequency(high),
+                levels.join("  ")
+            ),
+            position: Point::new(left + 4., 20.),
+            color: colors.label,
+            size: 12.into(),
+            ..Default::default()
+        });
+        frame.into_geometry()
+    }
+
+    /// Vertical line at `x` labelled with the frequency and level there, the
+    /// label kept inside the canvas.
+    fn draw_cursor(
+        &self,
+        renderer: &Renderer,
+        bounds: Rectangle,
+        x: f32,
+        freq: f64,
+        db: f32,
+    ) -> Geometry {
+        let colors = self.data.colors;
+        let mut frame = canvas::Frame::new(renderer, bounds.size());
+        frame.stroke(
+            &Path::line(Point::new(x, 0.), Point::new(x, frame.height())),
+            Stroke {
+                width: 1.,
+                style: stroke::Style::Solid(colors.label),
+                ..Default::default()
+            },
+        );
+        let content = format!(
+            "{}  {db:.1} {}",
+            readout_frequency(freq),
+            self.data.spectrum.kind().unit()
+        );
+        // roughly the width of the text, to flip it left near the edge
+        let text_width = content.len() as f32 * 7.;
+        let text_x = if x + 4. + text_width > frame.width() {
+            x - 4. - text_width
+        } else {
+            x + 4.
+        };
+        frame.fill_text(canvas::Text {
+            content,
+            position: Point::new(text_x, 4.),
+            color: colors.label,
+            size: 12.into(),
+            ..Default::default()
+        });
+        frame.into_geometry()
+    }
+
+    /// Plots the first two channels against each other, older samples fading
+    /// out like the trace of an oscilloscope.
+    fn draw_xy_scope(&self, frame: &mut canvas::Frame) {
+        let colors = self.data.colors;
+        frame.fill_rectangle(Point::ORIGIN, frame.size(), colors.background);
+        let view = self.data.view_range();
+        let len = view.len();
+        let mut channels = self
+            .data
+            .raw_matrix
+            .iter_channels()
+            .map(|channel| channel.skip(view.start).take(len));
+        let (Some(left), Some(right)) = (channels.next(), channels.next()) else {
+            frame.fill_text(canvas::Text {
+                content: "the xy scope needs at least two channels".to_owned(),
+                position: Point::new(8., 8.),
+                color: colors.label,
+                size: 14.into(),
+                ..Default::default()
+            });
+            return;
+        };
+        let center = frame.center();
+        let radius = frame.width().min(frame.height()) / 2. * self.data.waveform_gain();
+        for (index, (l, r)) in left.zip(right).enumerate() {
+            let alpha = (index + 1) as f32 / len as f32;
+            frame.fill_rectangle(
+                Point::new(center.x + l * radius, center.y - r * radius),
+                iced::Size::new(2., 2.),
+                Color {
+                    a: alpha,
+                    ..colors.line(1)
+                },
+            );
+        }
+    }
+
+    /// Every sample pair as a point at (side, mid), older ones fading out,
+    /// over the L, R, M and S axes and a circle at full scale. A single
+    /// channel counts as mono and stays on the vertical.
+    fn draw_goniometer(&self, frame: &mut canvas::Frame) {
+        let colors = self.data.colors;
+        frame.fill_rectangle(Point::ORIGIN, frame.size(), colors.background);
+        let center = frame.center();
+        let radius = frame.width().min(frame.height()) / 2. - 16.;
+        let grid = Stroke {
+            width: 1.,
+            style: stroke::Style::Solid(colors.grid),
+            ..Default::default()
+        };
+        frame.stroke(&Path::circle(center, radius), grid);
+        let diagonal = radius * std::f32::consts::FRAC_1_SQRT_2;
+        for (label, (x, y)) in [
+            ("M", (0., -radius)),
+            ("S", (radius, 0.)),
+            ("L", (-diagonal, -diagonal)),
+            ("R", (diagonal, -diagonal)),
+        ] {
+            frame.stroke(
+                &Path::line(
+                    Point::new(center.x - x, center.y - y),
+                    Point::new(center.x + x, center.y + y),
+                ),
+                grid,
+            );
+            frame.fill_text(canvas::Text {
+                content: label.to_owned(),
+                // roughly centered on the end of the axis
+                position: Point::new(center.x + x * 1.08 - 4., center.y + y * 1.08 - 7.),
+                color: colors.label,
+                size: 12.into(),
+                ..Default::default()
+            });
+        }
+
+        let matrix = &self.data.raw_matrix;
+        if matrix.channel() == 0 {
+            return;
+        }
+        let view = self.data.view_range();
+        let len = view.len();
+        let left = &matrix[0][view.clone()];
+        let right = &matrix[1.min(matrix.channel() - 1)][view];
+        // full scale on both channels reaches the circle
+        let scale = diagonal * self.data.waveform_gain();
+        for (index, (l, r)) in left.iter().zip(right).enumerate() {
+            let mid = (l + r) * std::f32::consts::FRAC_1_SQRT_2;
+            let side = (l - r) * std::f32::consts::FRAC_1_SQRT_2;
+            let alpha = (index + 1) as f32 / len as f32;
+            frame.fill_rectangle(
+                Point::new(center.x + side * scale, center.y - mid * scale),
+                iced::Size::new(2., 2.),
+                Color {
+                    a: alpha,
+                    ..colors.line(1)
+                },
+            );
+        }
+    }
+
+    pub fn generate_datas(&self, size: iced::Size) -> Vec<LineData> {
+        self.data.generate_raw_datas(size)
+    }
+
+    pub fn generate_spectrum(&self, size: iced::Size) -> Vec<LineData> {
+        self.data.generate_spectrum(size)
+    }
+
+    pub fn update_canvas(&mut self) {
+        self.decay_peak_hold();
+        self.invalidate();
+    }
 
-impl LineDatas {
-    fn new() -> Self {
-        Self {
-            raw_matrix: MatrixFixed::new(500, 2),
-            spectrum: vec![0.; FFT_SIZE],
-            rate: 50000,
+    pub fn append_data(&mut self, matrix: Matrix) {
+        self.data.append_data(matrix);
+        // the gain eases along with every buffer
+        if self.data.auto_gain {
+            self.amplitude_cache.clear();
         }
     }
-    pub fn set_rate(&mut self, rate: u32) {
-        self.rate = rate;
+    pub fn set_gain(&mut self, gain: f32) {
+        self.data.gain = gain;
+        self.data.auto_gain = false;
+        self.amplitude_cache.clear();
     }
-
-    fn append_data(&mut self, matrix: Matrix) {
-        self.raw_matrix.append(matrix);
+    pub fn set_auto_gain(&mut self, auto_gain: bool) {
+        self.data.auto_gain = auto_gain;
+        self.amplitude_cache.clear();
     }
-    pub fn set_spectrum(&mut self, spectrum: Vec<f32>) {
-        self.spectrum = spectrum;
+    pub fn set_time_window(&mut self, len: usize) {
+        self.data.set_view_len(len);
+        self.invalidate();
     }
-    fn reset_matrix(&mut self, len: usize, channel: usize) {
-        self.raw_matrix = MatrixFixed::new(len, channel);
+    fn set_trigger(&mut self, trigger: Trigger) {
+        self.data.trigger = trigger;
+        self.invalidate();
     }
-
-    fn generate_spectrum(&self, size: iced::Size) -> LineData {
-        let rate = self.rate as f64;
-
-        let log_min = MIN_FREQ.log10();
-        let log_max = rate.log10();
-
-        let octaves = (log_max - log_min) / (2.0_f64).log10();
-        let num_points = (octaves * POINTS_PER_OCTAVE as f64).round().max(32.0) as usize;
-        let step = size.width as f64 / num_points as f64;
-        let color = COLOR_ALL[1];
-        let data: Vec<Point> = (0..num_points)
-            .zip(&self.spectrum)
-            .map(|(index, db)| Point::new(index as f32 * step as f32, db * -10.))
-            .collect();
-
-        LineData { data, color }
+    pub fn set_view_offset(&mut self, offset: usize) {
+        self.data.set_view_offset(offset);
+        self.invalidate();
     }
 
-    fn generate_raw_datas(&self, size: iced::Size) -> Vec<LineData> {
-        let len = self.raw_matrix.len();
-        let width = size.width;
-        let step = width / len as f32;
-        let datas = self.raw_matrix.data();
-        let mut output: Vec<LineData> = vec![];
-        for (index, data) in datas.iter().enumerate() {
-            let color = COLOR_ALL[index % COLOR_ALL.len()];
-            let data: Vec<Point> = data
-                .iter()
-                .enumerate()
-                .map(|(index, wav)| Point::new(index as f32 * step, *wav * -400.))
-                .collect();
-            output.push(LineData { data, color });
+    /// Sets the fundamental to the peak next to a right click, or clears it
+    /// when clicking its marker again. Returns whether it changed.
+    fn pick_fundamental(
+        &self,
+        state: &mut CarvaState,
+        event: &iced::Event,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> bool {
+        let iced::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Right)) = event else {
+            return false;
+        };
+        let Some(position) = cursor.position_in(bounds) else {
+            return false;
+        };
+        if state.fundamental.is_some_and(|fundamental| {
+            (self.data.frequency_x(fundamental, bounds.width) - position.x).abs() <= PEAK_SNAP
+        }) {
+            state.fundamental = None;
+            return true;
         }
-        output
+        // a click is rarely right on the top of the peak
+        let offsets = (-PEAK_SNAP as i32..=PEAK_SNAP as i32).map(|offset| offset as f32);
+        state.fundamental = offsets
+            .filter_map(|offset| {
+                let freq = self.data.x_frequency(position.x + offset, bounds.width);
+                Some((freq, self.data.frequency_db(freq)?))
+            })
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(freq, _)| freq);
+        true
     }
-}
-
-#[derive(Debug)]
-struct State {
-    line_cache: canvas::Cache,
-    data: LineDatas,
-    show_type: ShowType,
-}
 
-impl State {
-    pub fn new() -> State {
-        State {
-            line_cache: canvas::Cache::default(),
-            data: LineDatas::new(),
-            show_type: ShowType::Raw,
+    /// Runs `draw`, which lays the spectrum out bottom up, flipped or
+    /// squeezed into both halves of the frame as `spectrum_orientation` asks.
+    fn orient_spectrum(&self, frame: &mut canvas::Frame, draw: impl Fn(&mut canvas::Frame)) {
+        let height = frame.height();
+        let transforms: &[(f32, f32)] = match self.spectrum_orientation {
+            SpectrumOrientation::BottomUp => &[(0., 1.)],
+            SpectrumOrientation::TopDown => &[(1., -1.)],
+            SpectrumOrientation::Mirror => &[(0., 0.5), (1., -0.5)],
+        };
+        for (offset, scale) in transforms {
+            frame.with_save(|frame| {
+                frame.translate(Vector::new(0., offset * height));
+                frame.scale_nonuniform(Vector::new(1., *scale));
+                draw(frame);
+            });
         }
     }
 
-    pub fn set_rate(&mut self, rate: u32) {
-        self.data.set_rate(rate);
+    /// Thin line over the spectrum through the dB per bin in `bins`, mapped
+    /// to the display points the way the spectrum is.
+    fn stroke_bins(&self, frame: &mut canvas::Frame, bins: &[f32], color: Color) {
+        let levels = self.data.spectrum_levels(bins);
+        let step = frame.width() / self.data.num_points() as f32;
+        let bottom = frame.height() - 2.;
+        let line = Path::new(|path| {
+            for (index, level) in levels.iter().enumerate() {
+                path.line_to(Point::new(
+                    index as f32 * step,
+                    bottom - level * frame.height(),
+                ));
+            }
+        });
+        self.orient_spectrum(frame, |frame| {
+            frame.stroke(
+                &line,
+                Stroke {
+                    width: 1.,
+                    style: stroke::Style::Solid(color),
+                    ..Default::default()
+                },
+            );
+        });
     }
 
-    pub fn set_spectrum(&mut self, spectrum: Vec<f32>) {
-        self.data.set_spectrum(spectrum);
+    /// Lines at the multiples of `fundamental` up to nyquist, numbered.
+    fn draw_harmonics(&self, renderer: &Renderer, bounds: Rectangle, fundamental: f64) -> Geometry {
+        let mut frame = canvas::Frame::new(renderer, bounds.size());
+        let nyquist = self.data.rate as f64 / 2.;
+        for harmonic in 1..=MAX_HARMONICS {
+            let freq = fundamental * harmonic as f64;
+            if freq > nyquist {
+                break;
+            }
+            let x = self.data.frequency_x(freq, frame.width());
+            if !(0.0..=frame.width()).contains(&x) {
+                continue;
+            }
+            frame.stroke(
+                &Path::line(Point::new(x, 0.), Point::new(x, frame.height())),
+                Stroke {
+                    width: if harmonic == 1 { 2. } else { 1. },
+                    style: stroke::Style::Solid(Color {
+                        a: 0.6,
+                        ..HARMONIC_COLOR
+                    }),
+                    line_dash: canvas::LineDash {
+                        segments: &[4., 4.],
+                        offset: 0,
+                    },
+                    ..Default::default()
+                },
+            );
+            frame.fill_text(canvas::Text {
+                content: harmonic.to_string(),
+                position: Point::new(x + 2., 20.),
+                color: HARMONIC_COLOR,
+                size: 11.into(),
+                ..Default::default()
+            });
+        }
+        frame.into_geometry()
     }
 
-    pub fn generate_datas(&self, size: iced::Size) -> Vec<LineData> {
-        self.data.generate_raw_datas(size)
+    /// Line at the spectral centroid, labelled with its frequency.
+    fn draw_centroid(&self, renderer: &Renderer, bounds: Rectangle, centroid: f64) -> Geometry {
+        let mut frame = canvas::Frame::new(renderer, bounds.size());
+        let x = self.data.frequency_x(centroid, frame.width());
+        frame.stroke(
+            &Path::line(Point::new(x, 0.), Point::new(x, frame.height())),
+            Stroke {
+                width: 1.5,
+                style: stroke::Style::Solid(CENTROID_COLOR),
+                ..Default::default()
+            },
+        );
+        frame.fill_text(canvas::Text {
+            content: format!("centroid {}", readout_frequency(centroid)),
+            position: Point::new(x + 4., 36.),
+            color: CENTROID_COLOR,
+            size: 11.into(),
+            ..Default::default()
+        });
+        frame.into_geometry()
     }
 
-    pub fn generate_spectrum(&self, size: iced::Size) -> LineData {
-        self.data.generate_spectrum(size)
+    /// Selects a band of the spectrum by dragging across it, a click without
+    /// a drag clears it. Returns whether the selection changed.
+    fn select_band(
+        &self,
+        state: &mut CarvaState,
+        event: &iced::Event,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> bool {
+        let iced::Event::Mouse(event) = event else {
+            return false;
+        };
+        match event {
+            mouse::Event::ButtonPressed(mouse::Button::Left) => {
+                let Some(position) = cursor.position_in(bounds) else {
+                    return false;
+                };
+                let freq = self.data.x_frequency(position.x, bounds.width);
+                state.selection = Some((freq, freq));
+                state.selecting = true;
+                true
+            }
+            mouse::Event::CursorMoved { position } if state.selecting => {
+                // dragging past the edge selects up to it
+                let x = (position.x - bounds.x).clamp(0., bounds.width);
+                if let Some((_, end)) = &mut state.selection {
+                    *end = self.data.x_frequency(x, bounds.width);
+                }
+                true
+            }
+            mouse::Event::ButtonReleased(mouse::Button::Left) if state.selecting => {
+                state.selecting = false;
+                if state.selection.is_some_and(|(start, end)| start == end) {
+                    state.selection = None;
+                }
+                true
+            }
+            _ => false,
+        }
     }
 
-    pub fn update_canvas(&mut self) {
+    /// Pans the raw view while dragging it and zooms it with the wheel.
+    fn raw_view_message(
+        &self,
+        state: &mut CarvaState,
+        event: &iced::Event,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> Option<Message> {
+        let iced::Event::Mouse(event) = event else {
+            return None;
+        };
+        match event {
+            mouse::Event::ButtonPressed(mouse::Button::Left) => {
+                let position = cursor.position_in(bounds)?;
+                state.drag = Some((position.x, self.data.view_offset));
+                None
+            }
+            mouse::Event::ButtonReleased(mouse::Button::Left) => {
+                state.drag = None;
+                None
+            }
+            mouse::Event::CursorMoved { position } => {
+                let (start_x, start_offset) = state.drag?;
+                let samples_per_pixel = self.data.view_len as f32 / bounds.width.max(1.);
+                // dragging right brings older samples into view
+                let moved = ((position.x - bounds.x - start_x) * samples_per_pixel) as isize;
+                Some(Message::RawScrolled(
+                    start_offset.saturating_add_signed(moved),
+                ))
+            }
+            mouse::Event::WheelScrolled { delta } => {
+                cursor.position_in(bounds)?;
+                let notches = match delta {
+                    mouse::ScrollDelta::Lines { y, .. } => *y,
+                    mouse::ScrollDelta::Pixels { y, .. } => y / PIXELS_PER_LINE,
+                };
+                let len = self.data.view_len as f32 * RAW_ZOOM_STEP.powf(-notches);
+                Some(Message::TimeWindowChanged(len as usize))
+            }
+            _ => None,
+        }
+    }
+    pub fn set_log_amplitude(&mut self, log_amplitude: bool) {
+        self.data.log_amplitude = log_amplitude;
+        self.amplitude_cache.clear();
+        self.invalidate();
+    }
+    pub fn set_log_factor(&mut self, log_factor: f32) {
+        self.data.log_factor = log_factor;
+        self.amplitude_cache.clear();
+        self.invalidate();
+    }
+    pub fn set_line_width(&mut self, line_width: f32) {
+        self.data.line_width = line_width;
         self.line_cache.clear();
     }
-
-    pub fn append_data(&mut self, matrix: Matrix) {
-        self.data.append_data(matrix);
+    pub fn set_auto_thin(&mut self, auto_thin: bool) {
+        self.data.auto_thin = auto_thin;
+        self.line_cache.clear();
+    }
+    pub fn set_lanes(&mut self, lanes: bool) {
+        self.data.lanes = lanes;
+        self.amplitude_cache.clear();
+    }
+    pub fn set_smoothing(&mut self, smoothing: f32) {
+        self.data.smoothing = smoothing;
+    }
+    pub fn set_theme(&mut self, theme: &Theme) {
+        self.data.colors = PlotColors {
+            custom: self.data.colors.custom,
+            ..PlotColors::from_theme(theme)
+        };
+        self.invalidate();
+        self.axis_cache.clear();
+        self.amplitude_cache.clear();
+    }
+    fn set_custom_colors(&mut self, custom: CustomColors) {
+        self.data.colors.custom = custom;
+        self.invalidate();
+    }
+    fn set_channel_selection(&mut self, selection: ChannelSelection) {
+        self.data.channel_selection = selection;
+        self.amplitude_cache.clear();
+        self.invalidate();
+    }
+    pub fn set_show_harmonics(&mut self, show_harmonics: bool) {
+        self.show_harmonics = show_harmonics;
+    }
+    fn set_pitch(&mut self, pitch: Option<f64>) {
+        self.pitch = pitch;
+    }
+    pub fn set_spectrum_gradient(&mut self, spectrum_gradient: bool) {
+        self.spectrum_gradient = spectrum_gradient;
+        self.invalidate();
+    }
+    pub fn set_spectrum_orientation(&mut self, orientation: SpectrumOrientation) {
+        self.spectrum_orientation = orientation;
+        self.axis_cache.clear();
+        self.invalidate();
+    }
+    pub fn set_bars(&mut self, bars: bool) {
+        self.data.bars = bars;
+        self.invalidate();
+    }
+    pub fn set_bar_bands(&mut self, bar_bands: BarBands) {
+        self.data.bar_bands = bar_bands;
+        self.invalidate();
+    }
+    pub fn set_bar_count(&mut self, bar_count: usize) {
+        self.data.bar_count = bar_count;
+        self.invalidate();
+    }
+    pub fn set_bar_gap(&mut self, bar_gap: f32) {
+        self.data.bar_gap = bar_gap;
+        self.invalidate();
+    }
+    pub fn set_auto_range(&mut self, auto_range: bool) {
+        self.data.auto_range = auto_range;
+        // start from the fixed range and move away from it gradually
+        self.data.auto_db = (self.data.spectrum.min_db(), self.data.spectrum.max_db());
+        self.axis_cache.clear();
+    }
+    pub fn set_bin_mapping(&mut self, bin_mapping: BinMapping) {
+        self.data.bin_mapping = bin_mapping;
+    }
+    pub fn set_weighting(&mut self, weighting: Weighting) {
+        self.data.set_weighting(weighting);
+    }
+    pub fn set_axis_scale(&mut self, axis_scale: AxisScale) {
+        self.data.set_axis_scale(axis_scale);
+        // the bins land elsewhere, so do the gridlines and the spectrogram
+        self.axis_cache.clear();
+        self.spectrogram.clear();
+    }
+    pub fn set_mel_bands(&mut self, mel_bands: usize) {
+        self.data.set_mel_bands(mel_bands);
+        // a different number of points
+        self.axis_cache.clear();
+        self.spectrogram.clear();
+        self.invalidate();
     }
     pub fn reset_matrix(&mut self, len: usize, channel: usize) {
         self.data.reset_matrix(len, channel);
+        // the lanes follow the channels
+        self.amplitude_cache.clear();
+    }
+
+    /// Forgets everything received so far, keeping the rate, the channels
+    /// and how it is all shown.
+    fn reset(&mut self) {
+        let (len, channels) = (self.data.raw_matrix.len(), self.data.raw_matrix.channel());
+        self.data.reset_matrix(len, channels);
+        self.data.peak = 0.;
+        self.data.smoothed_gain = 1.;
+        self.clear_spectrum();
+        self.data.auto_db = (self.data.spectrum.min_db(), self.data.spectrum.max_db());
+        self.spectrogram.clear();
+        self.spectrogram_image = None;
+        self.spectrogram_cache.clear();
+        self.axis_cache.clear();
+        self.amplitude_cache.clear();
+        self.pitch = None;
+        self.noise_floor.clear();
+        self.noise_floor_frames = 0;
+        self.noise_floor_capture = None;
+        self.snapshot.clear();
+    }
+
+    /// Shows the data the way `other` does, for a stream compared with it.
+    /// Only what changed is set, so the spectrogram survives.
+    fn copy_view(&mut self, other: &State) {
+        let from = &other.data;
+        if self.data.axis_scale != from.axis_scale {
+            self.set_axis_scale(from.axis_scale);
+        }
+        if self.data.mel_bands != from.mel_bands {
+            self.set_mel_bands(from.mel_bands);
+        }
+        if self.data.auto_range != from.auto_range {
+            self.set_auto_range(from.auto_range);
+        }
+        if self.show_peak_hold != other.show_peak_hold {
+            self.set_show_peak_hold(other.show_peak_hold);
+        }
+        self.show_type = other.show_type;
+        self.spectrum_gradient = other.spectrum_gradient;
+        self.spectrum_orientation = other.spectrum_orientation;
+        self.show_harmonics = other.show_harmonics;
+        self.show_centroid = other.show_centroid;
+        self.data.set_view_len(from.view_len);
+        self.data.set_view_offset(from.view_offset);
+        self.data.trigger = from.trigger;
+        self.data.gain = from.gain;
+        self.data.auto_gain = from.auto_gain;
+        self.data.lanes = from.lanes;
+        self.data.channel_selection = from.channel_selection;
+        self.data.log_amplitude = from.log_amplitude;
+        self.data.log_factor = from.log_factor;
+        self.data.line_width = from.line_width;
+        self.data.auto_thin = from.auto_thin;
+        self.data.set_weighting(from.weighting);
+        self.data.bin_mapping = from.bin_mapping;
+        self.data.bars = from.bars;
+        self.data.bar_bands = from.bar_bands;
+        self.data.bar_count = from.bar_count;
+        self.data.bar_gap = from.bar_gap;
+        self.data.smoothing = from.smoothing;
+        self.data.colors = from.colors;
+        self.axis_cache.clear();
+        self.amplitude_cache.clear();
+        self.invalidate();
     }
 }
 
 #[derive(Default, Debug)]
 struct CarvaState {
     raw: Vec<LineData>,
-    spectrum: LineData,
+    spectrum: Vec<LineData>,
+    bars: Vec<BarData>,
+    /// What the lines were generated from, `None` before the first time.
+    generated: Option<(u64, iced::Size)>,
+    /// Position, frequency and level under the mouse over the spectrum.
+    cursor: Option<(f32, f64, f32)>,
+    /// Where a drag of the raw view started, and its offset then.
+    drag: Option<(f32, usize)>,
+    /// Frequencies where a band selection on the spectrum starts and ends.
+    selection: Option<(f64, f64)>,
+    /// Whether the selection is still being dragged.
+    selecting: bool,
+    /// Frequency whose harmonics are marked, picked with a right click.
+    fundamental: Option<f64>,
 }
 
 impl CarvaState {
     pub fn get_data(&self, show_type: ShowType) -> Vec<&LineData> {
         match show_type {
             ShowType::Raw => self.raw.iter().collect(),
-            ShowType::Spectrum => vec![&self.spectrum],
+            ShowType::Spectrum => self.spectrum.iter().collect(),
+            ShowType::Spectrogram | ShowType::XYScope | ShowType::Goniometer => vec![],
         }
     }
 }
 
-impl<Message> canvas::Program<Message> for State {
+impl canvas::Program<Message> for State {
     type State = CarvaState;
 
     fn update(
         &self,
         state: &mut Self::State,
-        _event: &iced::Event,
+        event: &iced::Event,
         bounds: Rectangle,
-        _cursor: mouse::Cursor,
+        cursor: mouse::Cursor,
     ) -> Option<canvas::Action<Message>> {
-        state.raw = self.generate_datas(bounds.size());
-        state.spectrum = self.generate_spectrum(bounds.size());
-        None
+        // mouse moves and the like come through here as well
+        let generated = Some((self.revision, bounds.size()));
+        if state.generated != generated {
+            state.raw = self.generate_datas(bounds.size());
+            state.spectrum = self.generate_spectrum(bounds.size());
+            state.bars = self.data.generate_bars(bounds.size());
+            state.generated = generated;
+        }
+        if matches!(self.show_type, ShowType::Raw)
+            && let Some(message) = self.raw_view_message(state, event, bounds, cursor)
+        {
+            return Some(canvas::Action::publish(message).and_capture());
+        }
+        let mut redraw = matches!(self.show_type, ShowType::Spectrum)
+            && (self.select_band(state, event, bounds, cursor)
+                || self.show_harmonics && self.pick_fundamental(state, event, bounds, cursor));
+        let readout = cursor
+            .position_in(bounds)
+            .filter(|_| matches!(self.show_type, ShowType::Spectrum))
+            .and_then(|position| {
+                let freq = self.data.x_frequency(position.x, bounds.width);
+                let db = self.data.frequency_db(freq)?;
+                Some((position.x, freq, db))
+            });
+        if readout != state.cursor {
+            state.cursor = readout;
+            redraw = true;
+        }
+        redraw.then(canvas::Action::request_redraw)
     }
     fn draw(
         &self,
@@ -268,21 +3851,103 @@ impl<Message> canvas::Program<Message> for State {
         bounds: Rectangle,
         _cursor: mouse::Cursor,
     ) -> Vec<Geometry> {
+        if matches!(self.show_type, ShowType::XYScope) {
+            let scope = self.line_cache.draw(renderer, bounds.size(), |frame| {
+                self.draw_xy_scope(frame);
+            });
+            return vec![scope];
+        }
+
+        if matches!(self.show_type, ShowType::Goniometer) {
+            let scope = self.line_cache.draw(renderer, bounds.size(), |frame| {
+                self.draw_goniometer(frame);
+            });
+            return vec![scope];
+        }
+
+        if matches!(self.show_type, ShowType::Spectrogram) {
+            let spectrogram = self
+                .spectrogram_cache
+                .draw(renderer, bounds.size(), |frame| {
+                    frame.fill_rectangle(Point::ORIGIN, frame.size(), Color::BLACK);
+                    if let Some(image) = &self.spectrogram_image {
+                        frame.draw_image(Rectangle::new(Point::ORIGIN, frame.size()), image);
+                    }
+                });
+            return vec![spectrogram];
+        }
+
         let background = self.line_cache.draw(renderer, bounds.size(), |frame| {
-            frame.fill_rectangle(Point::ORIGIN, frame.size(), Color::BLACK);
+            frame.fill_rectangle(Point::ORIGIN, frame.size(), self.data.colors.background);
 
-            let the_data = datas.get_data(self.show_type);
-            for data in the_data {
+            if matches!(self.show_type, ShowType::Raw) && self.data.lanes {
+                let channels = self.data.raw_matrix.channel();
+                for (lane, group) in self.data.channel_groups().iter().enumerate() {
+                    // the middle line comes with the amplitude grid
+                    let y = self.data.lane_center(lane, frame.height());
+                    frame.fill_text(canvas::Text {
+                        content: match group.len() {
+                            1 => channel_label(group[0], channels),
+                            // with the others mixed in
+                            len => format!("{} +{}", channel_label(group[0], channels), len - 1),
+                        },
+                        position: Point::new(4., y - self.data.lane_height(frame.height()) / 2.),
+                        color: self.data.colors.line(group[0]),
+                        size: 12.into(),
+                        ..Default::default()
+                    });
+                }
+            }
+
+            let bars = matches!(self.show_type, ShowType::Spectrum) && self.data.bars;
+            if bars {
+                self.orient_spectrum(frame, |frame| {
+                    for data in &datas.bars {
+                        for bar in &data.bars {
+                            frame.fill_rectangle(bar.position(), bar.size(), data.color);
+                        }
+                    }
+                });
+                for (nominal, low, high) in self.data.iso_bands() {
+                    let center = self.data.frequency_x((low * high).sqrt(), frame.width());
+                    frame.fill_text(canvas::Text {
+                        // the usual short names, 31.5 or 1.25k
+                        content: if nominal >= 1000. {
+                            format!("{}k", nominal / 1000.)
+                        } else {
+                            nominal.to_string()
+                        },
+                        position: Point::new(
+                            center,
+                            self.spectrum_orientation
+                                .place(frame.height() - 16., frame.height())[0],
+                        ),
+                        color: self.data.colors.label,
+                        size: 10.into(),
+                        align_x: iced::widget::text::Alignment::Center,
+                        ..Default::default()
+                    });
+                }
+            }
+            let the_data = if bars {
+                vec![]
+            } else {
+                datas.get_data(self.show_type)
+            };
+            let groups = self.data.channel_groups();
+            for (lane, data) in the_data.into_iter().enumerate() {
                 let chat = Path::new(|path| {
+                    let spectrum = matches!(self.show_type, ShowType::Spectrum);
+                    // the fill runs along the baseline under the points, so
+                    // it stays a polygon however few of them there are
+                    if spectrum && let Some(first) = data.data.first() {
+                        path.move_to(Point::new(first.x, 0.));
+                    }
                     for p in &data.data {
                         path.line_to(*p);
                     }
-                    if matches!(self.show_type, ShowType::Spectrum) {
-                        path.line_to(Point {
-                            x: frame.width(),
-                            y: 0.,
-                        });
-                        path.line_to(Point { x: 0., y: 0. });
+                    if spectrum && let Some(last) = data.data.last() {
+                        path.line_to(Point::new(last.x, 0.));
                         path.close();
                     }
                 });
@@ -290,7 +3955,7 @@ impl<Message> canvas::Program<Message> for State {
                 let translation = if matches!(self.show_type, ShowType::Raw) {
                     Point {
                         x: Point::ORIGIN.x,
-                        y: frame.center().y,
+                        y: self.data.lane_center(lane, frame.height()),
                     }
                 } else {
                     Point {
@@ -299,12 +3964,26 @@ impl<Message> canvas::Program<Message> for State {
                     }
                 };
 
-                frame.translate(translation - Point::ORIGIN);
                 if matches!(self.show_type, ShowType::Raw) {
+                    let lane_height = self.data.lane_height(frame.height());
+                    let group = groups.get(lane).map_or(&[][..], Vec::as_slice);
+                    for (start, end) in self.data.clipped_spans(group, frame.width()) {
+                        frame.fill_rectangle(
+                            Point::new(start, translation.y - lane_height / 2.),
+                            iced::Size::new(end - start, lane_height),
+                            Color {
+                                a: 0.35,
+                                ..CLIP_COLOR
+                            },
+                        );
+                    }
+                }
+                if matches!(self.show_type, ShowType::Raw) {
+                    frame.translate(translation - Point::ORIGIN);
                     frame.stroke(
                         &chat,
                         Stroke {
-                            width: 3.,
+                            width: self.data.stroke_width(data.data.len(), frame.width()),
                             style: stroke::Style::Solid(data.color),
                             line_dash: canvas::LineDash {
                                 offset: 0,
@@ -313,14 +3992,607 @@ impl<Message> canvas::Program<Message> for State {
                             ..Default::default()
                         },
                     );
+                    frame.translate(Point::ORIGIN - translation);
                 } else {
-                    frame.fill(&chat, data.color);
+                    self.orient_spectrum(frame, |frame| {
+                        frame.translate(translation - Point::ORIGIN);
+                        if self.spectrum_gradient {
+                            // bright at full scale, fading toward the baseline
+                            let fill = gradient::Linear::new(
+                                Point::new(0., -frame.height()),
+                                Point::ORIGIN,
+                            )
+                            .add_stop(0., data.color)
+                            .add_stop(
+                                1.,
+                                Color {
+                                    a: data.color.a * 0.1,
+                                    ..data.color
+                                },
+                            );
+                            frame.fill(&chat, fill);
+                            // a soft wide stroke under a thin one makes the edge glow
+                            let edge = Path::new(|path| {
+                                for p in &data.data {
+                                    path.line_to(*p);
+                                }
+                            });
+                            for (width, alpha) in [(5., 0.25), (1.5, 1.)] {
+                                frame.stroke(
+                                    &edge,
+                                    Stroke {
+                                        width,
+                                        style: stroke::Style::Solid(Color {
+                                            a: data.color.a * alpha,
+                                            ..data.color
+                                        }),
+                                        line_join: canvas::LineJoin::Round,
+                                        ..Default::default()
+                                    },
+                                );
+                            }
+                        } else {
+                            frame.fill(&chat, data.color);
+                        }
+                    });
                 }
+            }
 
-                frame.translate(Point::ORIGIN - translation);
+            if matches!(self.show_type, ShowType::Spectrum) && self.show_peak_hold {
+                self.stroke_bins(frame, &self.peak_hold, PEAK_HOLD_COLOR);
+            }
+            if matches!(self.show_type, ShowType::Spectrum)
+                && self.subtract_noise_floor
+                && !self.capturing_noise_floor()
+            {
+                self.stroke_bins(frame, &self.noise_floor, NOISE_FLOOR_COLOR);
+            }
+            if matches!(self.show_type, ShowType::Spectrum) && !self.snapshot.is_empty() {
+                self.stroke_bins(frame, &self.snapshot, SNAPSHOT_COLOR);
+                let live = self.data.colors.spectrum(self.data.spectrum_channel(0));
+                for (row, (content, color)) in [("live", live), ("snapshot", SNAPSHOT_COLOR)]
+                    .into_iter()
+                    .enumerate()
+                {
+                    frame.fill_text(canvas::Text {
+                        content: format!("— {content}"),
+                        position: Point::new(frame.width() - 8., 8. + row as f32 * 16.),
+                        color,
+                        size: 12.into(),
+                        align_x: iced::widget::text::Alignment::Right,
+                        ..Default::default()
+                    });
+                }
             }
         });
 
+        if matches!(self.show_type, ShowType::Spectrum) {
+            // drawn over the curve so the filled area doesn't hide it
+            let axes = self.axis_cache.draw(renderer, bounds.size(), |frame| {
+                self.draw_spectrum_axes(frame);
+            });
+            let mut geometries = vec![background, axes];
+            if let Some(selection) = datas.selection {
+                geometries.push(self.draw_band(renderer, bounds, selection));
+            }
+            if self.show_harmonics
+                && let Some(fundamental) = datas.fundamental.or(self.pitch)
+            {
+                geometries.push(self.draw_harmonics(renderer, bounds, fundamental));
+            }
+            if self.show_centroid
+                && let Some(centroid) = self.centroid
+            {
+                geometries.push(self.draw_centroid(renderer, bounds, centroid));
+            }
+            if let Some((x, freq, db)) = datas.cursor {
+                geometries.push(self.draw_cursor(renderer, bounds, x, freq, db));
+            }
+            return geometries;
+        }
+
+        if matches!(self.show_type, ShowType::Raw) {
+            let axes = self.amplitude_cache.draw(renderer, bounds.size(), |frame| {
+                self.draw_amplitude_axes(frame);
+            });
+            return vec![background, axes];
+        }
+
         vec![background]
     }
 }
+
+/// Height of one channel's bar in the level meter, gaps included.
+const METER_LANE: f32 = 12.;
+/// Lowest level the meter shows, in dBFS.
+const METER_FLOOR_DB: f32 = -60.;
+/// Time for the peak hold to fall across the whole meter.
+const PEAK_HOLD_FALL: Duration = Duration::from_secs(1);
+/// How long the clip indicator stays lit after a clipped sample.
+const CLIP_HOLD: Duration = Duration::from_secs(1);
+const CLIP_COLOR: Color = Color::from_rgb(1., 0.1, 0.1);
+
+/// Per-channel RMS and peak bars with a decaying peak hold.
+#[derive(Debug, Default)]
+struct LevelMeter {
+    rms: Vec<f32>,
+    peak: Vec<f32>,
+    hold: Vec<f32>,
+    last_clip: Vec<Option<Instant>>,
+    updated: Option<Instant>,
+}
+
+impl LevelMeter {
+    fn update(&mut self, rms: Vec<f32>, peak: Vec<f32>) {
+        let now = Instant::now();
+        if self.hold.len() != peak.len() {
+            self.hold = vec![0.; peak.len()];
+            self.last_clip = vec![None; peak.len()];
+        }
+        let elapsed = self.updated.map_or(Duration::ZERO, |updated| now - updated);
+        let fall = elapsed.as_secs_f32() / PEAK_HOLD_FALL.as_secs_f32();
+        for ((hold, last_clip), peak) in self.hold.iter_mut().zip(&mut self.last_clip).zip(&peak) {
+            *hold = (*hold - fall).max(Self::meter_level(*peak));
+            if *peak >= CLIP_THRESHOLD {
+                *last_clip = Some(now);
+            }
+        }
+        self.rms = rms;
+        self.peak = peak;
+        self.updated = Some(now);
+    }
+
+    fn height(&self) -> f32 {
+        self.peak.len() as f32 * METER_LANE
+    }
+
+    /// Position of an amplitude on the meter, in `0..=1` on a dB scale.
+    fn meter_level(amplitude: f32) -> f32 {
+        let db = 20. * amplitude.log10();
+        ((db - METER_FLOOR_DB) / -METER_FLOOR_DB).clamp(0., 1.)
+    }
+}
+
+impl<Message> canvas::Program<Message> for LevelMeter {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        renderer: &Renderer,
+        theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let colors = PlotColors::from_theme(theme);
+        let mut frame = canvas::Frame::new(renderer, bounds.size());
+        // leave room on the right for the clip indicator
+        let width = frame.width() - METER_LANE;
+        let bar = METER_LANE - 2.;
+        for (channel, (rms, peak)) in self.rms.iter().zip(&self.peak).enumerate() {
+            let y = channel as f32 * METER_LANE;
+            let color = colors.line(channel);
+            frame.fill_rectangle(Point::new(0., y), iced::Size::new(width, bar), colors.grid);
+            frame.fill_rectangle(
+                Point::new(0., y),
+                iced::Size::new(Self::meter_level(*peak) * width, bar),
+                Color { a: 0.4, ..color },
+            );
+            frame.fill_rectangle(
+                Point::new(0., y),
+                iced::Size::new(Self::meter_level(*rms) * width, bar),
+                color,
+            );
+            if let Some(hold) = self.hold.get(channel) {
+                frame.fill_rectangle(
+                    Point::new(hold * width - 1., y),
+                    iced::Size::new(2., bar),
+                    colors.label,
+                );
+            }
+            let clipped = self
+                .last_clip
+                .get(channel)
+                .copied()
+                .flatten()
+                .is_some_and(|last_clip| last_clip.elapsed() < CLIP_HOLD);
+            frame.fill_rectangle(
+                Point::new(width + 2., y),
+                iced::Size::new(bar, bar),
+                if clipped { CLIP_COLOR } else { colors.grid },
+            );
+            frame.fill_text(canvas::Text {
+                content: channel_label(channel, self.peak.len()),
+                position: Point::new(2., y),
+                color: colors.label,
+                size: 10.into(),
+                ..Default::default()
+            });
+        }
+        vec![frame.into_geometry()]
+    }
+}
+
+/// Loudness at the left end of the loudness bars, in LUFS.
+const LOUDNESS_FLOOR: f32 = -60.;
+
+/// Momentary and short-term loudness as bars, the integrated loudness
+/// marked across both and everything read out on the right.
+#[derive(Debug, Default)]
+struct LoudnessBars {
+    loudness: Loudness,
+}
+
+impl LoudnessBars {
+    /// Position of `lufs` on the bars, in `0..=1`.
+    fn level(lufs: Option<f32>) -> f32 {
+        lufs.map_or(0., |lufs| {
+            ((lufs - LOUDNESS_FLOOR) / -LOUDNESS_FLOOR).clamp(0., 1.)
+        })
+    }
+
+    fn readout(lufs: Option<f32>) -> String {
+        lufs.map_or_else(|| "-".to_owned(), |lufs| format!("{lufs:.1}"))
+    }
+}
+
+impl<Message> canvas::Program<Message> for LoudnessBars {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        renderer: &Renderer,
+        theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let colors = PlotColors::from_theme(theme);
+        let mut frame = canvas::Frame::new(renderer, bounds.size());
+        let label_width = 40.;
+        let readout_width = 140.;
+        let width = (frame.width() - label_width - readout_width).max(0.);
+        let bar = METER_LANE - 2.;
+        let loudness = self.loudness;
+        for (lane, (label, lufs)) in [("M", loudness.momentary), ("S", loudness.short_term)]
+            .into_iter()
+            .enumerate()
+        {
+            let y = lane as f32 * METER_LANE;
+            frame.fill_text(canvas::Text {
+                content: label.to_owned(),
+                position: Point::new(2., y),
+                color: colors.label,
+                size: 10.into(),
+                ..Default::default()
+            });
+            frame.fill_rectangle(
+                Point::new(label_width, y),
+                iced::Size::new(width, bar),
+                colors.grid,
+            );
+            frame.fill_rectangle(
+                Point::new(label_width, y),
+                iced::Size::new(Self::level(lufs) * width, bar),
+                colors.line(lane),
+            );
+        }
+        if loudness.integrated.is_some() {
+            let x = label_width + Self::level(loudness.integrated) * width;
+            frame.fill_rectangle(
+                Point::new(x - 1., 0.),
+                iced::Size::new(2., 2. * METER_LANE - 2.),
+                colors.label,
+            );
+        }
+        for (row, content) in [
+            format!(
+                "M {} S {}",
+                Self::readout(loudness.momentary),
+                Self::readout(loudness.short_term)
+            ),
+            format!("I {} LUFS", Self::readout(loudness.integrated)),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            frame.fill_text(canvas::Text {
+                content,
+                position: Point::new(label_width + width + 6., row as f32 * METER_LANE),
+                color: colors.label,
+                size: 10.into(),
+                ..Default::default()
+            });
+        }
+        vec![frame.into_geometry()]
+    }
+}
+
+/// Weight of the newest buffer in the stereo meter readings, they would
+/// flicker otherwise.
+const STEREO_SMOOTHING: f32 = 0.2;
+/// Shown where the correlation is negative, the channels cancel in mono.
+const ANTI_PHASE_COLOR: Color = Color::from_rgb(1., 0.3, 0.1);
+
+/// Phase correlation and left/right balance of the first two channels.
+#[derive(Debug)]
+struct StereoMeter {
+    /// From -1, inverted, to 1, identical.
+    correlation: f32,
+    /// From -1, all left, to 1, all right.
+    balance: f32,
+}
+
+impl Default for StereoMeter {
+    fn default() -> Self {
+        Self {
+            correlation: 1.,
+            balance: 0.,
+        }
+    }
+}
+
+impl StereoMeter {
+    fn update_correlation(&mut self, correlation: f32) {
+        self.correlation += (correlation - self.correlation) * STEREO_SMOOTHING;
+    }
+
+    fn update_balance(&mut self, rms: &[f32]) {
+        let balance = match rms {
+            [left, right, ..] if left + right > f32::EPSILON => (right - left) / (right + left),
+            _ => 0.,
+        };
+        self.balance += (balance - self.balance) * STEREO_SMOOTHING;
+    }
+}
+
+impl<Message> canvas::Program<Message> for StereoMeter {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        renderer: &Renderer,
+        theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let colors = PlotColors::from_theme(theme);
+        let mut frame = canvas::Frame::new(renderer, bounds.size());
+        let label_width = 40.;
+        let width = frame.width() - label_width;
+        let center = label_width + width / 2.;
+        let bar = METER_LANE - 2.;
+        for (lane, (label, value)) in [("corr", self.correlation), ("bal", self.balance)]
+            .into_iter()
+            .enumerate()
+        {
+            let y = lane as f32 * METER_LANE;
+            frame.fill_text(canvas::Text {
+                content: label.to_owned(),
+                position: Point::new(2., y),
+                color: colors.label,
+                size: 10.into(),
+                ..Default::default()
+            });
+            frame.fill_rectangle(
+                Point::new(label_width, y),
+                iced::Size::new(width, bar),
+                colors.grid,
+            );
+            frame.fill_rectangle(
+                Point::new(center - 1., y),
+                iced::Size::new(2., bar),
+                colors.label,
+            );
+            let x = center + value * width / 2.;
+            let color = if lane == 0 && value < 0. {
+                ANTI_PHASE_COLOR
+            } else {
+                colors.line(1)
+            };
+            // the correlation fills from the middle, the balance is a marker
+            if lane == 0 {
+                frame.fill_rectangle(
+                    Point::new(center.min(x), y),
+                    iced::Size::new((x - center).abs(), bar),
+                    color,
+                );
+            } else {
+                frame.fill_rectangle(Point::new(x - 2., y), iced::Size::new(4., bar), color);
+            }
+        }
+        vec![frame.into_geometry()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A spectrum rising evenly from the floor at DC to 0 dB at nyquist.
+    fn ramp(fft_size: usize) -> SpectrumFrame {
+        let bins = spectrum_bins(fft_size);
+        let ramp = (0..bins)
+            .map(|bin| -120. + 120. * bin as f32 / (bins - 1) as f32)
+            .collect();
+        SpectrumFrame::new(vec![ramp], fft_size, 48000, DbScale::default())
+    }
+
+    #[test]
+    fn trigger_starts_the_view_on_a_crossing() {
+        let mut data = LineDatas::new();
+        data.set_rate(48000);
+        // 100 Hz, starting a third of a period in so a crossing isn't at an
+        // obvious index
+        let sine: Vec<f32> = (0..48000)
+            .map(|i| (2. * std::f32::consts::PI * (i as f32 + 160.) / 480.).sin())
+            .collect();
+        data.append_data(Matrix::from_channels(vec![sine, vec![0.; 48000]]));
+        let samples = &data.raw_matrix[0];
+        let free = data.view_range();
+        assert_eq!(free.end, data.raw_matrix.len());
+
+        for edge in [TriggerEdge::Rising, TriggerEdge::Falling] {
+            data.trigger = Trigger {
+                enabled: true,
+                edge,
+                level: 0.,
+                channel: 0,
+            };
+            let view = data.view_range();
+            assert_eq!(view.len(), data.view_len);
+            let (before, at) = (samples[view.start - 1], samples[view.start]);
+            match edge {
+                TriggerEdge::Rising => assert!(before < 0. && at >= 0.),
+                TriggerEdge::Falling => assert!(before > 0. && at <= 0.),
+            }
+            // the latest crossing, less than a period before the free view
+            assert!(view.start <= free.start && free.start - view.start < 480);
+        }
+
+        // the silent channel never crosses, the view runs free
+        data.trigger.channel = 1;
+        assert_eq!(data.view_range(), free);
+        data.trigger.channel = 2;
+        assert_eq!(data.trigger_start(free.start), None);
+    }
+
+    /// A `LineDatas` showing `ramp(fft_size)` on `axis_scale`.
+    fn showing_ramp(axis_scale: AxisScale, fft_size: usize) -> LineDatas {
+        let mut data = LineDatas::new();
+        data.set_rate(48000);
+        data.set_axis_scale(axis_scale);
+        data.set_weighting(Weighting::A);
+        data.set_spectrum(ramp(fft_size));
+        data
+    }
+
+    #[test]
+    fn cached_mapping_matches_a_fresh_one() {
+        for axis_scale in AxisScale::ALL {
+            let mut data = showing_ramp(axis_scale, 4096);
+            let channel = data.spectrum.channels()[0].clone();
+            let cached = data.spectrum_levels(&channel);
+            data.mapping = SpectrumMapping::default();
+            data.update_mapping();
+            assert_eq!(data.spectrum_levels(&channel), cached, "{axis_scale}");
+        }
+    }
+
+    /// Run with `cargo test --release -- --ignored --nocapture`.
+    #[test]
+    #[ignore = "timing only"]
+    fn bench_cached_spectrum_mapping() {
+        const FRAMES: u32 = 500;
+        for axis_scale in AxisScale::ALL {
+            let mut data = showing_ramp(axis_scale, 8192);
+            let channel = data.spectrum.channels()[0].clone();
+
+            let started = Instant::now();
+            for _ in 0..FRAMES {
+                // what every frame did before the mapping was cached
+                data.mapping = SpectrumMapping::default();
+                data.update_mapping();
+                std::hint::black_box(data.spectrum_levels(&channel));
+            }
+            let uncached = started.elapsed() / FRAMES;
+
+            let started = Instant::now();
+            for _ in 0..FRAMES {
+                data.update_mapping();
+                std::hint::black_box(data.spectrum_levels(&channel));
+            }
+            let cached = started.elapsed() / FRAMES;
+
+            println!(
+                "{:>6}: uncached {:>8.1} µs, cached {:>8.1} µs per frame",
+                axis_scale.to_string(),
+                uncached.as_secs_f64() * 1e6,
+                cached.as_secs_f64() * 1e6
+            );
+        }
+    }
+
+    #[test]
+    fn tone_peaks_at_its_frequency_on_screen() {
+        // bin 100 is 1 kHz, the bins past it belong above nyquist and
+        // are loud enough to win if they were read
+        let fft_size = 4800;
+        let bins = spectrum_bins(fft_size);
+        let mut channel = vec![-120.; bins + 200];
+        channel[100] = 0.;
+        channel[bins..].fill(10.);
+        for axis_scale in [AxisScale::Linear, AxisScale::Log] {
+            let mut data = LineDatas::new();
+            data.set_rate(48000);
+            data.set_axis_scale(axis_scale);
+            // points wider than a bin keep the tone's height
+            data.bin_mapping = BinMapping::Peak;
+            data.set_spectrum(SpectrumFrame::new(
+                vec![channel.clone()],
+                fft_size,
+                48000,
+                DbScale::default(),
+            ));
+            // a pixel per point
+            let size = iced::Size::new(data.mapping.points as f32, 100.);
+            let lines = data.generate_spectrum(size);
+            let points = &lines[0].data;
+            let highest = points
+                .iter()
+                .min_by(|a, b| a.y.total_cmp(&b.y))
+                .expect("points up to nyquist");
+            let expected = data.frequency_x(1000., size.width);
+            assert!(
+                (highest.x - expected).abs() <= 1.,
+                "{axis_scale}: {} not {expected}",
+                highest.x
+            );
+            let nyquist = data.frequency_x(24000., size.width);
+            assert!(
+                points.iter().all(|point| point.x <= nyquist + 1e-3),
+                "{axis_scale}"
+            );
+        }
+    }
+
+    #[test]
+    fn ramp_interpolates_narrow_points_and_summarizes_wide_ones() {
+        let mut data = LineDatas::new();
+        data.set_rate(48000);
+        data.set_axis_scale(AxisScale::Log);
+        data.set_spectrum(ramp(4096));
+        let bins = spectrum_bins(4096);
+        let channel = data.spectrum.channels()[0].clone();
+        data.bin_mapping = BinMapping::Average;
+        let average = data.spectrum_levels(&channel);
+        data.bin_mapping = BinMapping::Peak;
+        let peak = data.spectrum_levels(&channel);
+        assert_eq!(average.len(), data.mapping.bins.len());
+        assert_eq!(peak.len(), average.len());
+
+        let (mut narrow, mut wide) = (0, 0);
+        for (point, bins_read) in data.mapping.bins.iter().enumerate() {
+            if bins_read.last > bins_read.first + 1 {
+                // the top bin of the ones covered, above their average
+                let top = (bins_read.last.min(bins) - 1) as f32 / (bins - 1) as f32;
+                assert!((peak[point] - top).abs() < 1e-4, "point {point}");
+                assert!(average[point] < peak[point], "point {point}");
+                wide += 1;
+            } else {
+                // between bins both follow the ramp
+                let bin = bins_read.low as f32 + bins_read.fraction;
+                let expected = bin / (bins - 1) as f32;
+                assert!((average[point] - expected).abs() < 1e-4, "point {point}");
+                assert_eq!(average[point], peak[point]);
+                narrow += 1;
+            }
+        }
+        // the log axis has both
+        assert!(narrow > 0 && wide > 0);
+        for levels in [&average, &peak] {
+            assert!(levels.windows(2).all(|pair| pair[0] <= pair[1] + 1e-4));
+        }
+    }
+}
@@ -0,0 +1,182 @@
+/// Mel of `freq`, the O'Shaughnessy formula.
+fn hz_to_mel(freq: f64) -> f64 {
+    2595. * (1. + freq / 700.).log10()
+}
+
+fn mel_to_hz(mel: f64) -> f64 {
+    700. * (10_f64.powf(mel / 2595.) - 1.)
+}
+
+/// One triangular filter, the weights of consecutive bins from `first_bin`.
+#[derive(Debug, Clone)]
+struct MelFilter {
+    center: f64,
+    first_bin: usize,
+    weights: Vec<f32>,
+}
+
+/// Triangular filters spaced evenly in mel from 0 to nyquist, each band
+/// overlapping half of its neighbours.
+#[derive(Debug, Clone, Default)]
+pub struct MelFilterbank {
+    rate: u32,
+    fft_size: usize,
+    /// Distance in mel between the centers of neighbouring bands.
+    mel_step: f64,
+    filters: Vec<MelFilter>,
+}
+
+impl MelFilterbank {
+    pub fn new(rate: u32, fft_size: usize, bands: usize) -> Self {
+        let nyquist = rate as f64 / 2.;
+        let bins_per_hz = fft_size as f64 / rate.max(1) as f64;
+        let mel_step = hz_to_mel(nyquist) / (bands + 1) as f64;
+        let filters = (0..bands)
+            .map(|band| {
+                let [low, center, high] =
+                    [band, band + 1, band + 2].map(|edge| mel_to_hz(edge as f64 * mel_step));
+                let first_bin = (low * bins_per_hz).ceil() as usize;
+                let last_bin = (high * bins_per_hz).floor() as usize;
+                let weights: Vec<f32> = (first_bin..=last_bin)
+                    .map(|bin| {
+                        let freq = bin as f64 / bins_per_hz;
+                        let weight = if freq <= center {
+                            (freq - low) / (center - low)
+                        } else {
+                            (high - freq) / (high - center)
+                        };
+                        weight.max(0.) as f32
+                    })
+                    .collect();
+                // the lowest bands can fall between two bins, they take the
+                // nearest one whole
+                if weights.iter().all(|weight| *weight <= 0.) {
+                    return MelFilter {
+                        center,
+                        first_bin: (center * bins_per_hz).round() as usize,
+                        weights: vec![1.],
+                    };
+                }
+                MelFilter {
+                    center,
+                    first_bin,
+                    weights,
+                }
+            })
+            .collect();
+        Self {
+            rate,
+            fft_size,
+            mel_step,
+            filters,
+        }
+    }
+
+    /// Whether the filters were computed for these parameters.
+    pub fn matches(&self, rate: u32, fft_size: usize, bands: usize) -> bool {
+        self.rate == rate && self.fft_size == fft_size && self.filters.len() == bands
+    }
+
+    /// Center frequency of `band`.
+    pub fn center(&self, band: usize) -> f64 {
+        self.filters.get(band).map_or(0., |filter| filter.center)
+    }
+
+    /// Fractional band whose center is at `freq`, `-1` at 0 Hz.
+    pub fn position(&self, freq: f64) -> f64 {
+        hz_to_mel(freq) / self.mel_step.max(f64::EPSILON) - 1.
+    }
+
+    /// Frequency at fractional band `position`, the inverse of `position`.
+    pub fn frequency(&self, position: f64) -> f64 {
+        mel_to_hz((position + 1.) * self.mel_step)
+    }
+
+    /// Level in dB of every band, the weighted average power of its bins.
+    /// Bands with no power, or none of their bins in `spectrum`, read
+    /// `floor_db`.
+    pub fn apply(&self, spectrum: &[f32], floor_db: f32) -> Vec<f32> {
+        self.filters
+            .iter()
+            .map(|filter| {
+                let (power, total) = spectrum
+                    .iter()
+                    .skip(filter.first_bin)
+                    .zip(&filter.weights)
+                    .fold((0., 0.), |(power, total), (db, weight)| {
+                        (power + weight * 10_f32.powf(db / 10.), total + weight)
+                    });
+                if total > 0. {
+                    // `max` also maps the log of no power to the floor
+                    (10. * (power / total).log10()).max(floor_db)
+                } else {
+                    floor_db
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bands_rise_from_0_hz_to_nyquist() {
+        let bank = MelFilterbank::new(48000, 4096, 40);
+        assert!(bank.matches(48000, 4096, 40));
+        assert!(!bank.matches(44100, 4096, 40));
+        // the outer edges sit one step past the outer centers
+        assert!(bank.frequency(-1.).abs() < 1e-9);
+        assert!((bank.frequency(40.) - 24000.).abs() < 1e-6);
+        for band in 1..40 {
+            assert!(bank.center(band) > bank.center(band - 1));
+            let center = bank.center(band);
+            assert!((bank.position(center) - band as f64).abs() < 1e-9);
+        }
+        // each band spans its neighbours' centers
+        for filter in &bank.filters {
+            let low = filter.first_bin as f64 * 48000. / 4096.;
+            let high = (filter.first_bin + filter.weights.len() - 1) as f64 * 48000. / 4096.;
+            assert!(low <= filter.center && filter.center <= high || filter.weights == [1.]);
+            assert!(filter.first_bin + filter.weights.len() <= 4096 / 2 + 1);
+        }
+        assert_eq!(bank.center(40), 0.);
+
+        // a flat spectrum is flat in every band
+        let levels = bank.apply(&vec![-30.; 4096 / 2 + 1], -120.);
+        assert_eq!(levels.len(), 40);
+        assert!(levels.iter().all(|level| (level + 30.).abs() < 1e-3));
+
+        // too short a spectrum for the upper bands, or silent
+        let levels = bank.apply(&[-30.; 10], -120.);
+        assert!(levels.iter().all(|level| level.is_finite()));
+        assert_eq!(levels[39], -120.);
+        let levels = bank.apply(&vec![f32::NEG_INFINITY; 4096 / 2 + 1], -120.);
+        assert!(levels.iter().all(|level| *level == -120.));
+    }
+
+    #[test]
+    fn tone_falls_into_the_nearest_band() {
+        let bank = MelFilterbank::new(48000, 4096, 40);
+        for bin in [20, 85, 400, 1500] {
+            let tone = bin as f64 * 48000. / 4096.;
+            let mut spectrum = vec![-120.; 4096 / 2 + 1];
+            spectrum[bin] = 0.;
+            let levels = bank.apply(&spectrum, -120.);
+            let loudest = levels
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.total_cmp(b.1))
+                .map(|(band, _)| band)
+                .unwrap();
+            let nearest = (0..40)
+                .min_by(|a, b| {
+                    let distance = |band: usize| (bank.frequency(band as f64) - tone).abs();
+                    distance(*a).total_cmp(&distance(*b))
+                })
+                .unwrap();
+            assert_eq!(loudest, nearest, "{tone} Hz");
+        }
+    }
+}
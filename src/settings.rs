@@ -0,0 +1,112 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use iced::Theme;
+use serde::{Deserialize, Serialize};
+
+use crate::{FrameRate, ShowType, SpectrumOrientation};
+use wav_viewer::backend::{ChannelSelection, DEFAULT_FFT_SIZE, FFT_SIZES, WindowType};
+
+const DEFAULT_THEME: Theme = Theme::Moonfly;
+
+/// The UI choices remembered between sessions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub show_type: ShowType,
+    /// Name of one of the built-in themes.
+    pub theme: String,
+    pub window: WindowType,
+    pub fft_size: usize,
+    pub smoothing: f32,
+    /// Hex colors of the first channels, empty ones follow the theme.
+    pub channel_colors: Vec<String>,
+    /// Hex color of the spectrum, the theme's if empty.
+    pub spectrum_color: String,
+    pub frame_rate: FrameRate,
+    /// Channels shown of captures with many.
+    pub channels: ChannelSelection,
+    /// Width of the raw waveform's lines, in logical pixels.
+    pub line_width: f32,
+    /// Draw the waveform at most a pixel wide while it has more points than
+    /// pixels.
+    pub auto_thin: bool,
+    /// Smooth the edges of everything drawn, read at startup only.
+    pub antialiasing: bool,
+    pub spectrum_orientation: SpectrumOrientation,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            show_type: ShowType::Raw,
+            theme: DEFAULT_THEME.to_string(),
+            window: WindowType::default(),
+            fft_size: DEFAULT_FFT_SIZE,
+            smoothing: 0.,
+            channel_colors: vec![],
+            spectrum_color: String::new(),
+            frame_rate: FrameRate::default(),
+            channels: ChannelSelection::default(),
+            line_width: 3.,
+            auto_thin: false,
+            antialiasing: false,
+            spectrum_orientation: SpectrumOrientation::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// `$XDG_CONFIG_HOME/wav_viewer/settings.toml`, falling back to
+    /// `~/.config`.
+    fn path() -> Option<PathBuf> {
+        let config = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config.join("wav_viewer").join("settings.toml"))
+    }
+
+    /// Reads the saved settings, anything missing or unreadable is left at
+    /// its default.
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Self::default(),
+            Err(err) => {
+                eprintln!("cannot read {}: {err}", path.display());
+                return Self::default();
+            }
+        };
+        let mut settings: Self = toml::from_str(&content).unwrap_or_else(|err| {
+            eprintln!("ignoring malformed {}: {err}", path.display());
+            Self::default()
+        });
+        if !FFT_SIZES.contains(&settings.fft_size) {
+            settings.fft_size = DEFAULT_FFT_SIZE;
+        }
+        settings
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = Self::path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let content = toml::to_string(self).map_err(io::Error::other)?;
+        fs::write(path, content)
+    }
+
+    pub fn theme(&self) -> Theme {
+        Theme::ALL
+            .iter()
+            .find(|theme| theme.to_string() == self.theme)
+            .cloned()
+            .unwrap_or(DEFAULT_THEME)
+    }
+}
@@ -0,0 +1,32 @@
+use iced::futures::SinkExt;
+use iced::futures::channel::mpsc::Sender;
+use wav_viewer::backend::{BackendError, PwEvent, StreamConfig, spawn_capture};
+
+/// Captures as set up by `config`.
+///
+/// The subscription is identified by the config, so asking for a different
+/// one (another device, mode, latency...) ends the running capture and starts
+/// a new one, without restarting anything else. The new stream announces
+/// itself with a fresh `PwEvent::Ready`, the settings sent with
+/// `BackendCommand` have to be sent again then. Dropping the subscription
+/// stops the capture thread and waits for it.
+pub fn listen_pw(config: StreamConfig) -> iced::Subscription<PwEvent> {
+    iced::Subscription::run_with(config, |config| {
+        let config = config.clone();
+        iced::stream::channel(100, move |mut output: Sender<PwEvent>| async move {
+            let (sync_receiver, _guard) = spawn_capture(config);
+            loop {
+                let Ok(data) = sync_receiver.recv() else {
+                    let _ = output
+                        .send(PwEvent::Error(BackendError::Disconnected))
+                        .await;
+                    break;
+                };
+                // iced dropped the subscription, `_guard` stops the thread
+                if output.send(data).await.is_err() {
+                    break;
+                }
+            }
+        })
+    })
+}
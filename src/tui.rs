@@ -0,0 +1,186 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use wav_viewer::backend::{self, BackendCommand, MIN_FREQ, PwEvent, SpectrumFrame, octaves};
+
+use crate::args::Args;
+use crate::channel_label;
+
+/// Least time between two frames, terminals don't keep up with much more.
+const FRAME_INTERVAL: Duration = Duration::from_millis(50);
+/// How often the terminal size is looked up again.
+const RESIZE_INTERVAL: Duration = Duration::from_millis(500);
+/// Rows and columns when they can't be read, e.g. outside a terminal.
+const DEFAULT_SIZE: (usize, usize) = (24, 80);
+/// Level at the left end of the meters, in dBFS.
+const METER_FLOOR_DB: f32 = -60.;
+/// Eighths of a cell, from empty to full.
+const BLOCKS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Rows and columns of the controlling terminal, as `stty size` reports.
+fn terminal_size() -> Option<(usize, usize)> {
+    let tty = File::open("/dev/tty").ok()?;
+    let output = Command::new("stty")
+        .arg("size")
+        .stdin(tty)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let output = String::from_utf8(output.stdout).ok()?;
+    let (rows, cols) = output.trim().split_once(' ')?;
+    Some((rows.parse().ok()?, cols.parse().ok()?))
+}
+
+/// What is on screen, drawn over the previous frame from the top left.
+#[derive(Default)]
+struct Screen {
+    rows: usize,
+    cols: usize,
+    rate: u32,
+    status: String,
+    error: Option<String>,
+    spectrum: Option<SpectrumFrame>,
+    rms: Vec<f32>,
+    peak: Vec<f32>,
+}
+
+impl Screen {
+    /// Level in `0..=1` of every column, the loudest bin across channels in
+    /// its share of the octaves from `MIN_FREQ` to nyquist.
+    fn columns(&self, spectrum: &SpectrumFrame) -> Vec<f32> {
+        let octaves = octaves(self.rate);
+        let bins_per_hz = spectrum.fft_size() as f64 / self.rate.max(1) as f64;
+        let range = (spectrum.max_db() - spectrum.min_db()).max(f32::EPSILON);
+        let edge = |column: usize| {
+            let freq = MIN_FREQ * 2_f64.powf(octaves * column as f64 / self.cols.max(1) as f64);
+            (freq * bins_per_hz).round() as usize
+        };
+        (0..self.cols)
+            .map(|column| {
+                // the lowest columns can be narrower than a bin
+                let (first, last) = (edge(column), edge(column + 1).max(edge(column) + 1));
+                let db = spectrum
+                    .channels()
+                    .iter()
+                    .filter_map(|channel| channel.get(first..last.min(channel.len())))
+                    .flatten()
+                    .copied()
+                    .fold(f32::NEG_INFINITY, f32::max);
+                ((db - spectrum.min_db()) / range).clamp(0., 1.)
+            })
+            .collect()
+    }
+
+    /// `FL  █████▒▒▒      -12.0 dB`, the RMS solid, up to the peak shaded.
+    fn meter(&self, channel: usize, rms: f32, peak: f32) -> String {
+        let label = format!("{:<4}", channel_label(channel, self.peak.len()));
+        let readout = format!(" {:6.1} dB", 20. * peak.log10());
+        let width = self
+            .cols
+            .saturating_sub(label.chars().count() + readout.len());
+        let cells = |amplitude: f32| {
+            let db = 20. * amplitude.log10();
+            (((db - METER_FLOOR_DB) / -METER_FLOOR_DB).clamp(0., 1.) * width as f32) as usize
+        };
+        let rms_cells = cells(rms);
+        let peak_cells = cells(peak).max(rms_cells);
+        format!(
+            "{label}{}{}{}{readout}",
+            "█".repeat(rms_cells),
+            "▒".repeat(peak_cells - rms_cells),
+            " ".repeat(width - peak_cells)
+        )
+    }
+
+    /// Writes the status line, the spectrum and a meter per channel,
+    /// leaving the last row free so nothing scrolls.
+    fn draw(&self, out: &mut impl Write) -> io::Result<()> {
+        write!(out, "\x1b[H")?;
+        let status = self.error.as_deref().unwrap_or(&self.status);
+        writeln!(
+            out,
+            "{}\x1b[K",
+            status.chars().take(self.cols).collect::<String>()
+        )?;
+        let height = self.rows.saturating_sub(self.peak.len() + 2);
+        let columns = self
+            .spectrum
+            .as_ref()
+            .filter(|_| self.rate > 0)
+            .map_or_else(Vec::new, |spectrum| self.columns(spectrum));
+        for row in (0..height).rev() {
+            let line: String = columns
+                .iter()
+                .map(|level| {
+                    let eighths = (level * height as f32 - row as f32) * 8.;
+                    BLOCKS[eighths.clamp(0., 8.) as usize]
+                })
+                .collect();
+            writeln!(out, "{line}\x1b[K")?;
+        }
+        for (channel, (rms, peak)) in self.rms.iter().zip(&self.peak).enumerate() {
+            writeln!(out, "{}\x1b[K", self.meter(channel, *rms, *peak))?;
+        }
+        write!(out, "\x1b[J")?;
+        out.flush()
+    }
+}
+
+/// Captures and draws the spectrum with block characters in the terminal,
+/// redrawing in place until interrupted.
+pub fn run(args: &Args) -> io::Result<()> {
+    let (events, _guard) = backend::spawn_capture(args.stream_config());
+    let mut screen = Screen {
+        status: "connecting…".to_owned(),
+        ..Screen::default()
+    };
+    let mut stdout = io::stdout().lock();
+    let mut last_frame: Option<Instant> = None;
+    let mut last_resize: Option<Instant> = None;
+    for event in events {
+        match event {
+            PwEvent::Ready(backend) => {
+                if let Some(window) = args.window {
+                    backend.send(BackendCommand::Window(window));
+                }
+                backend.send(BackendCommand::SilenceThreshold(args.idle_below));
+            }
+            PwEvent::FormatChange(format) => {
+                screen.rate = format.rate();
+                screen.status = format!("{} Hz · {} ch", format.rate(), format.channels());
+                screen.error = None;
+            }
+            PwEvent::Spectrum(spectrum) => screen.spectrum = Some(spectrum),
+            PwEvent::Levels { rms, peak } => {
+                screen.rms = rms;
+                screen.peak = peak;
+            }
+            PwEvent::TargetNotFound(target) => {
+                screen.error = Some(format!("capture target \"{target}\" not found"));
+            }
+            PwEvent::Reconnecting(delay) => {
+                screen.error = Some(format!("reconnecting in {:.1}s", delay.as_secs_f32()));
+            }
+            PwEvent::Error(err) => screen.error = Some(err.to_string()),
+            _ => {}
+        }
+        if last_frame.is_some_and(|last| last.elapsed() < FRAME_INTERVAL) {
+            continue;
+        }
+        last_frame = Some(Instant::now());
+        if last_resize.is_none_or(|last| last.elapsed() >= RESIZE_INTERVAL) {
+            last_resize = Some(Instant::now());
+            let (rows, cols) = terminal_size().unwrap_or(DEFAULT_SIZE);
+            // whatever the old size left outside the new one has to go
+            if (rows, cols) != (screen.rows, screen.cols) {
+                write!(stdout, "\x1b[2J")?;
+                screen.rows = rows;
+                screen.cols = cols;
+            }
+        }
+        screen.draw(&mut stdout)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,137 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use wav_viewer::backend::{AudioInfo, Matrix};
+
+/// WAVE_FORMAT_IEEE_FLOAT, samples are stored as they come from the backend.
+const FORMAT_FLOAT: u16 = 3;
+const BYTES_PER_SAMPLE: u32 = 4;
+/// Size of everything before the sample data.
+const HEADER_LEN: u32 = 44;
+
+/// Writes 32-bit float WAV files, patching the sizes in the header on
+/// `finish`.
+#[derive(Debug)]
+pub struct WavWriter {
+    file: BufWriter<File>,
+    path: PathBuf,
+    channels: u32,
+    data_len: u32,
+}
+
+impl WavWriter {
+    pub fn create(path: impl AsRef<Path>, format: &AudioInfo) -> io::Result<Self> {
+        let path = path.as_ref().to_owned();
+        let mut file = BufWriter::new(File::create(&path)?);
+        let channels = format.channels();
+        let block_align = channels * BYTES_PER_SAMPLE;
+
+        file.write_all(b"RIFF")?;
+        // both sizes are filled in by `finish`
+        file.write_all(&0_u32.to_le_bytes())?;
+        file.write_all(b"WAVE")?;
+        file.write_all(b"fmt ")?;
+        file.write_all(&16_u32.to_le_bytes())?;
+        file.write_all(&FORMAT_FLOAT.to_le_bytes())?;
+        file.write_all(&(channels as u16).to_le_bytes())?;
+        file.write_all(&format.rate().to_le_bytes())?;
+        file.write_all(&(format.rate() * block_align).to_le_bytes())?;
+        file.write_all(&(block_align as u16).to_le_bytes())?;
+        file.write_all(&(BYTES_PER_SAMPLE as u16 * 8).to_le_bytes())?;
+        file.write_all(b"data")?;
+        file.write_all(&0_u32.to_le_bytes())?;
+
+        Ok(Self {
+            file,
+            path,
+            channels,
+            data_len: 0,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends the buffer, interleaving the channels back into frames.
+    pub fn write(&mut self, matrix: &Matrix) -> io::Result<()> {
+        if matrix.channel() as u32 != self.channels {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "channel count differs from the file's",
+            ));
+        }
+        for sample in matrix.interleaved() {
+            self.file.write_all(&sample.to_le_bytes())?;
+            self.data_len += BYTES_PER_SAMPLE;
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file
+            .write_all(&(HEADER_LEN - 8 + self.data_len).to_le_bytes())?;
+        self.file.seek(SeekFrom::Start(u64::from(HEADER_LEN) - 4))?;
+        self.file.write_all(&self.data_len.to_le_bytes())?;
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes(bytes[offset..offset + 2].try_into().unwrap())
+    }
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn header_describes_the_recording() {
+        let path = std::env::temp_dir().join(format!("wav_viewer-{}.wav", std::process::id()));
+        let mut writer = WavWriter::create(&path, &AudioInfo::new(44100, 2)).unwrap();
+        assert_eq!(writer.path(), path);
+        for _ in 0..3 {
+            writer
+                .write(&Matrix::from_channels(vec![vec![0.5; 10], vec![-0.25; 10]]))
+                .unwrap();
+        }
+        // recorded before a format change, left out
+        assert!(
+            writer
+                .write(&Matrix::from_channels(vec![vec![1.]]))
+                .is_err()
+        );
+        writer.finish().unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let data_len = 3 * 10 * 2 * 4;
+        assert_eq!(bytes.len(), HEADER_LEN as usize + data_len);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(u32_at(&bytes, 4) as usize, bytes.len() - 8);
+        assert_eq!(&bytes[8..12], b"WAVE");
+        assert_eq!(&bytes[12..16], b"fmt ");
+        assert_eq!(u32_at(&bytes, 16), 16);
+        assert_eq!(u16_at(&bytes, 20), FORMAT_FLOAT);
+        assert_eq!(u16_at(&bytes, 22), 2);
+        assert_eq!(u32_at(&bytes, 24), 44100);
+        // byte rate and block align
+        assert_eq!(u32_at(&bytes, 28), 44100 * 8);
+        assert_eq!(u16_at(&bytes, 32), 8);
+        assert_eq!(u16_at(&bytes, 34), 32);
+        assert_eq!(&bytes[36..40], b"data");
+        assert_eq!(u32_at(&bytes, 40) as usize, data_len);
+        // frames interleaved
+        let samples: Vec<f32> = bytes[HEADER_LEN as usize..]
+            .chunks_exact(4)
+            .map(|sample| f32::from_le_bytes(sample.try_into().unwrap()))
+            .collect();
+        assert_eq!(samples[..4], [0.5, -0.25, 0.5, -0.25]);
+    }
+}